@@ -1,7 +1,7 @@
 use gloo_file::{callbacks::FileReader, FileList};
 use results::svg_result::SVGResult;
 use smt_log_parser::parsers::z3::z3parser::Z3Parser;
-use smt_log_parser::parsers::{AsyncBufferRead, AsyncCursorRead, AsyncParser, LogParser};
+use smt_log_parser::parsers::{AsyncBufferRead, LogParser};
 use wasm_bindgen::JsCast;
 use wasm_streams::ReadableStream;
 use web_sys::{Event, HtmlInputElement};
@@ -22,7 +22,7 @@ pub mod results;
 mod utils;
 // mod select_dropdown;
 pub enum Msg {
-    LoadedFile(String, Z3Parser),
+    LoadedFile(String, Box<Z3Parser>),
     Files(Option<FileList>),
 }
 
@@ -52,7 +52,7 @@ impl Component for FileDataComponent {
                 self.files.clear();
                 self.readers.clear();
                 log::info!("Files selected: {}", files.len());
-                for file in files.into_iter() {
+                for file in files.iter() {
                     let file_name = file.name();
                     // Turn into stream
                     let blob: &web_sys::Blob = file.as_ref();
@@ -69,7 +69,7 @@ impl Component for FileDataComponent {
                                     // TODO: make this clear in the UI
                                     log::info!("Stopped parsing at 1GB");
                                 }
-                                link.send_message(Msg::LoadedFile(file_name, parser.take_parser()))
+                                link.send_message(Msg::LoadedFile(file_name, Box::new(parser.take_parser())))
                             });
                         }
                         Err((_err, _stream)) => {
@@ -86,7 +86,7 @@ impl Component for FileDataComponent {
                                     // TODO: make this clear in the UI
                                     log::info!("Stopped parsing at 1GB");
                                 }
-                                link.send_message(Msg::LoadedFile(file_name, parser.take_parser()))
+                                link.send_message(Msg::LoadedFile(file_name, Box::new(parser.take_parser())))
                             });
                             self.readers.push(reader);
                         }
@@ -96,7 +96,14 @@ impl Component for FileDataComponent {
             }
             Msg::LoadedFile(file_name, parser) => {
                 log::info!("Processing: {file_name}");
-                self.files.push(RcParser::new(parser));
+                let diagnostics = parser.diagnostics();
+                if !diagnostics.is_empty() {
+                    log::warn!("{} parse diagnostics in {file_name}", diagnostics.len());
+                    for diagnostic in diagnostics.iter() {
+                        log::warn!("{diagnostic}");
+                    }
+                }
+                self.files.push(RcParser::new(*parser));
                 true
             }
         }
@@ -125,7 +132,7 @@ impl Component for FileDataComponent {
                         <p><small>{version_info}</small></p>
                     </div>
                     <div>
-                        <input type="file" accept=".log" onchange={on_change} multiple=false/>
+                        <input type="file" accept=".log,.gz,.zst" onchange={on_change} multiple=false/>
                     </div>
                 </div>
                 <div style="display: flex; ">
@@ -166,6 +173,7 @@ fn test() -> Html {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Routable, Clone, PartialEq)]
 enum Route {
     #[at("/")]
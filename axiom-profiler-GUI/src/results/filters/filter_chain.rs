@@ -1,6 +1,7 @@
 use super::super::svg_result::{UserPermission, DEFAULT_NODE_COUNT};
-use super::graph_filters::{Filter, GraphFilter};
+use super::graph_filters::{Filter, FilterStep, GraphFilter, SetOp};
 use gloo::console::log;
+use smt_log_parser::parsers::z3::cost::CostModelKind;
 use yew::prelude::*;
 // use gloo_console::log;
 use material_yew::WeakComponentLink;
@@ -10,23 +11,29 @@ pub enum Msg {
     RemoveNthFilter(usize),
     ResetFilters,
     SetToPrevious,
+    SetCostModel(CostModelKind),
+    SetOp(SetOp),
 }
 
 pub struct FilterChain {
-    filter_chain: Vec<Filter>,
-    prev_filter_chain: Vec<Filter>,
+    filter_chain: Vec<FilterStep>,
+    prev_filter_chain: Vec<FilterStep>,
+    cost_model: CostModelKind,
+    /// How newly added filters are combined with the chain so far.
+    op: SetOp,
 }
 
-const DEFAULT_FILTER_CHAIN: &[Filter] = &[
-    Filter::IgnoreTheorySolving,
-    Filter::MaxInsts(DEFAULT_NODE_COUNT),
+const DEFAULT_FILTER_CHAIN: &[FilterStep] = &[
+    FilterStep { op: SetOp::Then, filter: Filter::IgnoreTheorySolving },
+    FilterStep { op: SetOp::Then, filter: Filter::MaxInsts(DEFAULT_NODE_COUNT) },
 ];
 
 #[derive(Properties, PartialEq)]
 pub struct FilterChainProps {
-    pub apply_filter: Callback<Filter>,
-    pub reset_graph: Callback<()>,
+    pub apply_filter: Callback<FilterStep>,
+    pub truncate_filters: Callback<usize>,
     pub render_graph: Callback<UserPermission>,
+    pub set_cost_model: Callback<CostModelKind>,
     pub dependency: *const smt_log_parser::Z3Parser,
     pub weak_link: WeakComponentLink<FilterChain>,
 }
@@ -41,14 +48,16 @@ impl yew::html::Component for FilterChain {
             .borrow_mut()
             .replace(ctx.link().clone());
         let filter_chain = DEFAULT_FILTER_CHAIN.to_vec();
-        for &filter in &filter_chain {
-            ctx.props().apply_filter.emit(filter);
+        for &step in &filter_chain {
+            ctx.props().apply_filter.emit(step);
         }
         ctx.props().render_graph.emit(UserPermission::default());
         let prev_filter_chain = filter_chain.clone();
         Self {
             filter_chain,
             prev_filter_chain,
+            cost_model: CostModelKind::default(),
+            op: SetOp::default(),
         }
     }
 
@@ -57,9 +66,10 @@ impl yew::html::Component for FilterChain {
             Msg::AddFilters(filters) => {
                 self.prev_filter_chain = self.filter_chain.clone();
                 for filter in filters {
-                    log!("Adding filter ", filter.to_string());
-                    self.filter_chain.push(filter);
-                    ctx.props().apply_filter.emit(filter);
+                    let step = FilterStep { op: self.op, filter };
+                    log!("Adding filter ", step.to_string());
+                    self.filter_chain.push(step);
+                    ctx.props().apply_filter.emit(step);
                 }
                 ctx.props().render_graph.emit(UserPermission::default());
                 true
@@ -67,32 +77,44 @@ impl yew::html::Component for FilterChain {
             Msg::RemoveNthFilter(n) => {
                 log!("Removing filter", n);
                 self.prev_filter_chain = self.filter_chain.clone();
-                self.filter_chain.remove(n);
-                ctx.props().reset_graph.emit(());
-                for &filter in &self.filter_chain {
-                    ctx.props().apply_filter.emit(filter);
-                }
+                let mut filter_chain = self.filter_chain.clone();
+                filter_chain.remove(n);
+                self.replace_filter_chain(ctx, filter_chain);
                 ctx.props().render_graph.emit(UserPermission::default());
                 true
             }
             Msg::ResetFilters => {
                 log!("resetting filters");
                 self.prev_filter_chain = self.filter_chain.clone();
-                self.filter_chain = DEFAULT_FILTER_CHAIN.to_vec();
-                ctx.props().reset_graph.emit(());
-                for &filter in &self.filter_chain {
-                    ctx.props().apply_filter.emit(filter);
-                }
+                self.replace_filter_chain(ctx, DEFAULT_FILTER_CHAIN.to_vec());
                 ctx.props().render_graph.emit(UserPermission::default());
                 true
             }
             Msg::SetToPrevious => {
                 log!("Setting to previous filter chain");
-                self.filter_chain = self.prev_filter_chain.clone();
-                ctx.props().reset_graph.emit(());
-                for &filter in &self.filter_chain {
-                    ctx.props().apply_filter.emit(filter);
+                self.replace_filter_chain(ctx, self.prev_filter_chain.clone());
+                true
+            }
+            Msg::SetCostModel(cost_model) => {
+                log!("Setting cost model to", cost_model.to_string());
+                self.cost_model = cost_model;
+                // Only `MaxInsts` depends on the costs, so re-apply the chain
+                // from the first one.
+                ctx.props().set_cost_model.emit(cost_model);
+                let first_costly = self
+                    .filter_chain
+                    .iter()
+                    .position(|step| matches!(step.filter, Filter::MaxInsts(_)))
+                    .unwrap_or(self.filter_chain.len());
+                ctx.props().truncate_filters.emit(first_costly);
+                for &step in &self.filter_chain[first_costly..] {
+                    ctx.props().apply_filter.emit(step);
                 }
+                ctx.props().render_graph.emit(UserPermission::default());
+                true
+            }
+            Msg::SetOp(op) => {
+                self.op = op;
                 true
             }
         }
@@ -110,6 +132,18 @@ impl yew::html::Component for FilterChain {
             })
             .collect();
         let reset_filters = ctx.link().callback(|_| Msg::ResetFilters);
+        let cost_models = CostModelKind::ALL.map(|cost_model| html! {
+            <button
+                onclick={ctx.link().callback(move |_| Msg::SetCostModel(cost_model))}
+                disabled={cost_model == self.cost_model}
+            >{cost_model.name()}</button>
+        });
+        let set_ops = SetOp::ALL.map(|op| html! {
+            <button
+                onclick={ctx.link().callback(move |_| Msg::SetOp(op))}
+                disabled={op == self.op}
+            >{op.name()}</button>
+        });
 
         let add_filters = ctx.link().callback(Msg::AddFilters);
         html!(
@@ -123,7 +157,30 @@ impl yew::html::Component for FilterChain {
                 <div>
                     <button onclick={reset_filters}>{"Reset to default"}</button>
                 </div>
+                <h2>{"Combine added filters by:"}</h2>
+                <div>{for set_ops}</div>
+                <h2>{"Cost model:"}</h2>
+                <div>{for cost_models}</div>
             </>
         )
     }
 }
+
+impl FilterChain {
+    /// Switch to `filter_chain`, only re-applying the filters after the
+    /// longest prefix it shares with the current chain. The results of the
+    /// filters in the prefix are kept.
+    fn replace_filter_chain(&mut self, ctx: &Context<Self>, filter_chain: Vec<FilterStep>) {
+        let common_prefix = self
+            .filter_chain
+            .iter()
+            .zip(&filter_chain)
+            .take_while(|(old, new)| old == new)
+            .count();
+        ctx.props().truncate_filters.emit(common_prefix);
+        for &step in &filter_chain[common_prefix..] {
+            ctx.props().apply_filter.emit(step);
+        }
+        self.filter_chain = filter_chain;
+    }
+}
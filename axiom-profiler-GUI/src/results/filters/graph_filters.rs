@@ -1,14 +1,15 @@
 use super::node_actions::NodeActions;
 use crate::utils::input_state::{InputValue, UsizeInput};
 use petgraph::{stable_graph::NodeIndex, Direction};
+use roaring::RoaringBitmap;
 use smt_log_parser::{
-    items::QuantIdx,
+    items::{QuantIdx, QueryIdx},
     parsers::z3::inst_graph::{InstGraph, InstInfo, NodeData},
 };
 use std::fmt::Display;
 use yew::prelude::*;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Filter {
     MaxNodeIdx(usize),
     IgnoreTheorySolving,
@@ -22,6 +23,7 @@ pub enum Filter {
     MaxDepth(usize),
     ShowLongestPath(NodeIndex),
     ShowMatchingLoops,
+    OnlyQuery(QueryIdx),
 }
 
 impl Display for Filter {
@@ -62,36 +64,141 @@ impl Display for Filter {
                 write!(f, "Showing longest path through node {}", node.index())
             }
             Self::ShowMatchingLoops => write!(f, "Showing matching loops"),
+            Self::OnlyQuery(query) => write!(f, "Only show instantiations of query {query}"),
         }
     }
 }
 
 impl Filter {
-    pub fn apply(self: Filter, graph: &mut InstGraph) -> Option<Vec<NodeIndex>> {
-        match self {
-            Filter::MaxNodeIdx(max) => graph
-                .retain_nodes(|node: &NodeData| node.orig_graph_idx.index() <= max),
+    /// The nodes which are visible after applying the filter to the
+    /// `visible` nodes, i.e. the result of the filter chain so far. Filters
+    /// do not modify the graph, so the result of each step of a chain can be
+    /// kept and the chain replayed from any step. `ShowLongestPath` also
+    /// returns the path so that it can be selected.
+    pub fn apply(
+        self: Filter,
+        graph: &InstGraph,
+        visible: &RoaringBitmap,
+    ) -> (RoaringBitmap, Option<Vec<NodeIndex>>) {
+        let nodes = match self {
+            Filter::MaxNodeIdx(max) => {
+                visible & graph.nodes_where(|node: &NodeData| node.orig_graph_idx.index() <= max)
+            }
             Filter::IgnoreTheorySolving => {
-                graph.retain_nodes(|node: &NodeData| !node.is_theory_inst)
+                visible - graph.nodes_where(|node: &NodeData| node.is_theory_inst)
             }
             Filter::IgnoreQuantifier(qidx) => {
-                graph.retain_nodes(|node: &NodeData| node.mkind.quant_idx() != qidx)
+                visible - graph.nodes_where(|node: &NodeData| node.mkind.quant_idx() == qidx)
             }
             Filter::IgnoreAllButQuantifier(qidx) => {
-                graph.retain_nodes(|node: &NodeData| node.mkind.quant_idx() == qidx)
+                visible & graph.nodes_where(|node: &NodeData| node.mkind.quant_idx() == qidx)
             }
-            Filter::MaxInsts(n) => graph.keep_n_most_costly(n),
-            Filter::MaxBranching(n) => graph.keep_n_most_branching(n),
-            Filter::ShowNeighbours(nidx, direction) => graph.show_neighbours(nidx, direction),
-            Filter::VisitSubTreeWithRoot(nidx, retain) => graph.visit_descendants(nidx, retain),
-            Filter::VisitSourceTree(nidx, retain) => graph.visit_ancestors(nidx, retain),
+            Filter::MaxInsts(n) => graph.n_most_costly(n, visible),
+            Filter::MaxBranching(n) => graph.n_most_branching(n, visible),
+            Filter::ShowNeighbours(nidx, direction) => visible | graph.neighbours(nidx, direction),
+            Filter::VisitSubTreeWithRoot(nidx, true) => visible | graph.descendants(nidx),
+            Filter::VisitSubTreeWithRoot(nidx, false) => visible - graph.descendants(nidx),
+            Filter::VisitSourceTree(nidx, true) => visible | graph.ancestors(nidx),
+            Filter::VisitSourceTree(nidx, false) => visible - graph.ancestors(nidx),
             Filter::MaxDepth(depth) => {
-                graph.retain_nodes(|node: &NodeData| node.min_depth.unwrap() <= depth)
+                visible & graph.nodes_where(|node: &NodeData| node.min_depth.unwrap() <= depth)
+            }
+            Filter::ShowLongestPath(nidx) => {
+                let path = graph.longest_path_through(nidx);
+                let nodes = path.iter().map(|node| node.index() as u32).collect();
+                return (nodes, Some(path));
             }
-            Filter::ShowLongestPath(nidx) => return Some(graph.show_longest_path_through(nidx)),
-            Filter::ShowMatchingLoops => graph.show_matching_loops(),
+            Filter::ShowMatchingLoops => graph
+                .matching_loops()
+                .into_iter()
+                .flat_map(|l| l.nodes)
+                .map(|node| node.index() as u32)
+                .collect(),
+            Filter::OnlyQuery(query) => {
+                visible & graph.nodes_where(|node: &NodeData| node.query == Some(query))
+            }
+        };
+        (nodes, None)
+    }
+}
+
+impl Filter {
+    /// The nodes this filter selects on its own: those it keeps out of all
+    /// nodes, or for the filters which show nodes, those it adds.
+    pub fn selection(self, graph: &InstGraph) -> (RoaringBitmap, Option<Vec<NodeIndex>>) {
+        let nodes = match self {
+            Filter::ShowNeighbours(..)
+            | Filter::VisitSubTreeWithRoot(_, true)
+            | Filter::VisitSourceTree(_, true) => RoaringBitmap::new(),
+            _ => graph.all_nodes(),
+        };
+        self.apply(graph, &nodes)
+    }
+}
+
+/// How a step of the filter chain combines the nodes of its filter with the
+/// result of the chain so far.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum SetOp {
+    /// Apply the filter to the result so far.
+    #[default]
+    Then,
+    /// Add the nodes selected by the filter (see [`Filter::selection`]).
+    Union,
+    /// Only keep the nodes also selected by the filter.
+    Intersection,
+    /// Remove the nodes selected by the filter.
+    Difference,
+}
+
+impl SetOp {
+    pub const ALL: [Self; 4] = [Self::Then, Self::Union, Self::Intersection, Self::Difference];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Then => "then",
+            Self::Union => "union",
+            Self::Intersection => "intersection",
+            Self::Difference => "difference",
         }
-        None
+    }
+}
+
+/// A filter of the filter chain. What a step combined with a set operation
+/// other than [`SetOp::Then`] selects does not depend on the steps before it,
+/// e.g. to show the union of the instantiations of two quantifiers.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FilterStep {
+    pub op: SetOp,
+    pub filter: Filter,
+}
+
+impl Display for FilterStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.op {
+            SetOp::Then => write!(f, "{}", self.filter),
+            SetOp::Union => write!(f, "Also: {}", self.filter),
+            SetOp::Intersection => write!(f, "Only if: {}", self.filter),
+            SetOp::Difference => write!(f, "Except: {}", self.filter),
+        }
+    }
+}
+
+impl FilterStep {
+    /// See [`Filter::apply`].
+    pub fn apply(
+        self,
+        graph: &InstGraph,
+        visible: &RoaringBitmap,
+    ) -> (RoaringBitmap, Option<Vec<NodeIndex>>) {
+        let combine: fn(&RoaringBitmap, RoaringBitmap) -> RoaringBitmap = match self.op {
+            SetOp::Then => return self.filter.apply(graph, visible),
+            SetOp::Union => |visible, selected| visible | selected,
+            SetOp::Intersection => |visible, selected| visible & selected,
+            SetOp::Difference => |visible, selected| visible - selected,
+        };
+        let (selected, path) = self.filter.selection(graph);
+        (combine(visible, selected), path)
     }
 }
 
@@ -107,6 +214,7 @@ pub fn graph_filter(props: &GraphFilterProps) -> Html {
     let max_instantiations = use_reducer(InputValue::default);
     let max_branching = use_reducer(InputValue::default);
     let max_depth = use_reducer(InputValue::default);
+    let query = use_reducer(InputValue::default);
     let selected_insts = use_context::<Vec<InstInfo>>().expect("no ctx found");
 
     let add_max_line_nr_filter = {
@@ -133,6 +241,13 @@ pub fn graph_filter(props: &GraphFilterProps) -> Html {
         let callback = props.add_filters.clone();
         Callback::from(move |_| callback.emit(vec![Filter::MaxDepth(max_depth.value)]))
     };
+    let add_query_filter = {
+        let query = query.clone();
+        let callback = props.add_filters.clone();
+        Callback::from(move |_| {
+            callback.emit(vec![Filter::OnlyQuery(QueryIdx::from(query.value))])
+        })
+    };
     let show_matching_loops = {
         let callback = props.add_filters.clone();
         Callback::from(move |_| callback.emit(vec![Filter::ShowMatchingLoops]))
@@ -184,6 +299,16 @@ pub fn graph_filter(props: &GraphFilterProps) -> Html {
                 />
                 <button onclick={add_max_depth_filter}>{"Add"}</button>
             </div>
+            <div>
+                <UsizeInput
+                    label={"Only show instantiations of query "}
+                    dependency={props.dependency}
+                    input_value={query}
+                    default_value={0}
+                    placeholder={""}
+                />
+                <button onclick={add_query_filter}>{"Add"}</button>
+            </div>
             <div>
                 <label for="matching_loops">{"Show matching loops"}</label>
                 <button onclick={show_matching_loops} id="matching_loops">{"Add"}</button>
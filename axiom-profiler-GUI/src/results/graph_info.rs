@@ -69,7 +69,7 @@ impl Component for GraphInfo {
                 let node_index = NodeIndex::new(node_index);
                 if self.selected_nodes.get(&node_index).is_some() {
                     self.selected_nodes.shift_remove(&node_index);
-                    self.is_expanded_node.remove(&node_index);
+                    self.is_expanded_node.shift_remove(&node_index);
                 } else {
                     let inst_info = ctx.props().node_info.emit((
                         node_index,
@@ -97,7 +97,7 @@ impl Component for GraphInfo {
                 let edge_index = EdgeIndex::new(edge_index);
                 if self.selected_edges.get(&edge_index).is_some() {
                     self.selected_edges.shift_remove(&edge_index);
-                    self.is_expanded_edge.remove(&edge_index);
+                    self.is_expanded_edge.shift_remove(&edge_index);
                 } else {
                     let edge_info = ctx.props().edge_info.emit((
                         edge_index,
@@ -289,13 +289,15 @@ fn selected_nodes_info(
                     on_click.emit(selected_inst.node_index)
                 })
             };
+            let query = selected_inst.query.map(|query| format!(" in query {query}")).unwrap_or_default();
             let z3_gen = selected_inst.z3_gen.map(|gen| format!(", Z3 generation {gen}")).unwrap_or_default();
             html! {
             <details id={format!("{}", selected_inst.node_index.index())} onclick={on_select}>
                 <summary>{format!("Node {}", selected_inst.node_index.index())}</summary>
                 <ul>
-                    <li><h4>{"Instantiation number: "}</h4><p>{format!("{}", selected_inst.inst_idx)}</p></li>
-                    <li><h4>{"Cost: "}</h4><p>{"Calculated "}{selected_inst.cost}{z3_gen}</p></li>
+                    <li><h4>{"Instantiation number: "}</h4><p>{format!("{}{query}", selected_inst.inst_idx)}</p></li>
+                    <li><h4>{"Log position: "}</h4><p>{format!("Matched on {}, instantiated on {}", selected_inst.match_pos, selected_inst.inst_pos)}</p></li>
+                    <li><h4>{"Cost: "}</h4><p>{"Calculated "}{selected_inst.cost}{z3_gen}{format!(", useful in {} conflicts", selected_inst.useful)}</p></li>
                     <li><h4>{"Instantiated formula: "}</h4><p>{&selected_inst.formula}</p></li>
                     <li>{get_ul("Blamed terms: ", &selected_inst.blamed_terms)}</li>
                     <li>{get_ul("Bound terms: ", &selected_inst.bound_terms)}</li>
@@ -345,7 +347,16 @@ fn selected_edges_info(
                         <h4>{"Equality: "}</h4><p>{selected_edge.blame_term.clone()}</p>
                         </div>
                     },
-                    _ => html! {}
+                    BlameKind::UnknownEquality { proof: Some(proof), .. } => html! {
+                        <div>
+                        <h4>{format!("Equality (not explained by Z3, proved by {proof}): ")}</h4><p>{selected_edge.blame_term.clone()}</p>
+                        </div>
+                    },
+                    BlameKind::UnknownEquality { proof: None, .. } => html! {
+                        <div>
+                        <h4 style="color: red;">{"Unexplained equality: "}</h4><p>{selected_edge.blame_term.clone()}</p>
+                        </div>
+                    },
                 }}
             </details>
             }
@@ -7,7 +7,7 @@ use self::colours::HSVColour;
 use super::{
     filters::{
         filter_chain::{FilterChain, Msg as FilterChainMsg},
-        graph_filters::Filter,
+        graph_filters::FilterStep,
     },
     worker::Worker,
 };
@@ -15,8 +15,10 @@ use material_yew::WeakComponentLink;
 use num_format::{Locale, ToFormattedString};
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::{EdgeIndex, NodeIndex};
+use roaring::RoaringBitmap;
 use smt_log_parser::{
     items::{BlameKind, MatchKind},
+    parsers::z3::cost::CostModelKind,
     parsers::z3::inst_graph::{EdgeInfo, EdgeType, InstGraph, InstInfo, VisibleGraphInfo},
 };
 use std::num::NonZeroUsize;
@@ -32,11 +34,13 @@ pub const NODE_COLOUR_VALUE: f64 = 0.95;
 pub enum Msg {
     UpdateSvgText(AttrValue, bool),
     RenderGraph(UserPermission),
-    ApplyFilter(Filter),
-    ResetGraph,
+    ApplyFilter(FilterStep),
+    /// Only keep the results of the first this many filters of the chain.
+    TruncateFilters(usize),
     GetUserPermission,
     WorkerOutput(super::worker::WorkerOutput),
     UpdateSelectedNodes(Vec<InstInfo>),
+    SetCostModel(CostModelKind),
 }
 
 #[derive(Default)]
@@ -59,6 +63,9 @@ pub struct SVGResult {
     parser: RcParser,
     colour_map: QuantIdxToColourMap,
     inst_graph: InstGraph,
+    /// The nodes visible after each filter of the chain, the last of which
+    /// are those of the graph.
+    filter_results: Vec<RoaringBitmap>,
     svg_text: AttrValue,
     filter_chain_link: WeakComponentLink<FilterChain>,
     insts_info_link: WeakComponentLink<GraphInfo>,
@@ -85,12 +92,7 @@ impl Component for SVGResult {
         let inst_graph = InstGraph::from(&parser);
         let (quant_count, non_quant_insts) = parser.quant_count_incl_theory_solving();
         let colour_map = QuantIdxToColourMap::from(quant_count, non_quant_insts);
-        let get_node_info = Callback::from({
-            let node_info_map = inst_graph.get_node_info_map();
-            move |(node, ignore_ids, parser): (NodeIndex, bool, RcParser)| {
-                node_info_map.get_instantiation_info(node.index(), &parser, ignore_ids)
-            }
-        });
+        let get_node_info = Self::node_info_callback(&inst_graph);
         let get_edge_info = Callback::from({
             let edge_info_map = inst_graph.get_edge_info_map();
             move |(edge, ignore_ids, parser): (EdgeIndex, bool, RcParser)| {
@@ -101,6 +103,7 @@ impl Component for SVGResult {
             parser,
             colour_map,
             inst_graph,
+            filter_results: Vec::new(),
             svg_text: AttrValue::default(),
             filter_chain_link: WeakComponentLink::default(),
             insts_info_link: WeakComponentLink::default(),
@@ -121,7 +124,12 @@ impl Component for SVGResult {
             Msg::WorkerOutput(_out) => false,
             Msg::ApplyFilter(filter) => {
                 log::debug!("Applying filter {}", filter);
-                if let Some(ref path) = filter.apply(&mut self.inst_graph) {
+                let all_nodes = self.inst_graph.all_nodes();
+                let visible = self.filter_results.last().unwrap_or(&all_nodes);
+                let (nodes, path) = filter.apply(&self.inst_graph, visible);
+                self.inst_graph.set_visible_nodes(nodes.clone());
+                self.filter_results.push(nodes);
+                if let Some(ref path) = path {
                     self.insts_info_link
                         .borrow()
                         .clone()
@@ -132,9 +140,17 @@ impl Component for SVGResult {
                     false
                 }
             }
-            Msg::ResetGraph => {
-                log::debug!("Resetting graph");
-                self.inst_graph.reset_visibility_to(true);
+            Msg::SetCostModel(cost_model) => {
+                log::debug!("Setting cost model to {cost_model}");
+                self.inst_graph.set_costs(&self.parser, &cost_model);
+                self.get_node_info = Self::node_info_callback(&self.inst_graph);
+                false
+            }
+            Msg::TruncateFilters(n) => {
+                log::debug!("Keeping the first {n} filters");
+                self.filter_results.truncate(n);
+                let nodes = self.filter_results.last().cloned();
+                self.inst_graph.set_visible_nodes(nodes.unwrap_or_else(|| self.inst_graph.all_nodes()));
                 false
             }
             Msg::RenderGraph(UserPermission { permission }) => {
@@ -187,10 +203,12 @@ impl Component for SVGResult {
                                     EdgeType::Indirect => "indirect".to_string(),
                                 },
                                 match edge_data.weight() {
+                                    EdgeType::Direct { kind, .. } if kind.is_unexplained() => "dotted",
                                     EdgeType::Direct { .. } => "solid",
                                     EdgeType::Indirect => "dashed",
                                 },
                                 match edge_data.weight() {
+                                    EdgeType::Direct { kind, .. } if kind.is_unexplained() => "\"direct unexplained\"",
                                     EdgeType::Direct { .. } => "direct",
                                     EdgeType::Indirect => "indirect",
                                 },
@@ -326,8 +344,9 @@ impl Component for SVGResult {
             html! {}
         };
         let apply_filter = ctx.link().callback(Msg::ApplyFilter);
-        let reset_graph = ctx.link().callback(|_| Msg::ResetGraph);
+        let truncate_filters = ctx.link().callback(Msg::TruncateFilters);
         let render_graph = ctx.link().callback(Msg::RenderGraph);
+        let set_cost_model = ctx.link().callback(Msg::SetCostModel);
         let update_selected_nodes = ctx.link().callback(Msg::UpdateSelectedNodes);
         html! {
             <>
@@ -335,8 +354,9 @@ impl Component for SVGResult {
                 <ContextProvider<Vec<InstInfo>> context={self.selected_insts.clone()}>
                     <FilterChain
                         apply_filter={apply_filter.clone()}
-                        reset_graph={reset_graph.clone()}
+                        {truncate_filters}
                         render_graph={render_graph.clone()}
+                        {set_cost_model}
                         weak_link={self.filter_chain_link.clone()}
                         dependency={ctx.props().parser.as_ptr()}
                     />
@@ -371,6 +391,15 @@ impl SVGResult {
         self.worker.as_mut().unwrap().send(input);
     }
 
+    /// Looks up node information in the current state of `inst_graph`, needs
+    /// recreating whenever the costs change.
+    fn node_info_callback(inst_graph: &InstGraph) -> Callback<(NodeIndex, bool, RcParser), InstInfo> {
+        let node_info_map = inst_graph.get_node_info_map();
+        Callback::from(move |(node, ignore_ids, parser): (NodeIndex, bool, RcParser)| {
+            node_info_map.get_instantiation_info(node.index(), &parser, ignore_ids)
+        })
+    }
+
     /// Used internally.
     fn create_worker(link: yew::html::Scope<Self>) -> Box<dyn yew_agent::Bridge<Worker>> {
         use yew_agent::Bridged;
@@ -419,7 +448,7 @@ impl QuantIdxToColourMap {
         // Workaround since `unwrap` isn't allowed in const functions.
         const ONE: NonZeroUsize = match NonZeroUsize::new(1) {
             Some(nz) => nz,
-            None => [][0],
+            None => panic!(),
         };
         let nz = NonZeroUsize::new(n);
         if let Some(nz) = nz {
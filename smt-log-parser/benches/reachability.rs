@@ -0,0 +1,138 @@
+//! Compares the reachability indexes used for the instantiation graph on the
+//! logs in `../logs`, or on those given as arguments:
+//!
+//! `cargo bench --bench reachability -- [LOGS]`
+use std::path::PathBuf;
+use std::time::Instant;
+
+use petgraph::{graph::NodeIndex, Direction::Incoming, Graph};
+use smt_log_parser::{
+    items::InstIdx,
+    parsers::z3::{
+        inst_graph::blamed_creators,
+        reachability::{IntervalLabels, ReachabilityIndex, TransitiveClosure},
+    },
+    LogParser, Z3Parser,
+};
+
+const QUERIES: usize = 100_000;
+
+fn main() {
+    // `cargo bench` passes `--bench`.
+    let mut logs: Vec<_> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .collect();
+    if logs.is_empty() {
+        match std::fs::read_dir("../logs") {
+            // Skip e.g. the `.cache` files saved next to the logs.
+            Ok(dir) => logs.extend(
+                dir.map(|entry| entry.unwrap().path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "log")),
+            ),
+            Err(err) => {
+                eprintln!("No logs given and `../logs` could not be read: {err}");
+                return;
+            }
+        }
+    }
+    logs.sort();
+    println!(
+        "{:40}  {:>9}  {:>9}  {:>20}  {:>10}  {:>9}  {:>10}",
+        "log", "nodes", "edges", "index", "build", "memory", "per query"
+    );
+    for log in logs {
+        let (_, parser) = Z3Parser::from_file(&log).unwrap();
+        let parser = parser.process_all();
+        let graph = dependency_graph(&parser);
+        let queries = queries(&graph);
+        let name = log.file_name().unwrap().to_string_lossy();
+        println!("{name:40}  {:>9}  {:>9}", graph.node_count(), graph.edge_count());
+        let labels = bench::<IntervalLabels>("interval labels", &graph, &queries);
+        let closure = bench::<TransitiveClosure>("transitive closure", &graph, &queries);
+        assert_eq!(labels, closure, "the indexes disagree on {name}");
+    }
+}
+
+/// The instantiations of the log, with the same edges as the instantiation
+/// graph: from each instantiation to those which were triggered by a term it
+/// created.
+fn dependency_graph(parser: &Z3Parser) -> Graph<(), ()> {
+    let mut graph = Graph::new();
+    for idx in 0..parser.inst_count() {
+        let iidx = InstIdx::from(idx);
+        let node = graph.add_node(());
+        let match_ = &parser[parser[iidx].match_];
+        for (_, _, parent) in blamed_creators(parser, match_) {
+            if parent < iidx {
+                graph.add_edge(NodeIndex::new(parent.into()), node, ());
+            }
+        }
+    }
+    graph
+}
+
+/// Pairs of nodes, half of them picked at random (which are mostly
+/// unreachable) and half by walking up from a random node.
+fn queries(graph: &Graph<(), ()>) -> Vec<(NodeIndex, NodeIndex)> {
+    let mut state = 0x2545_F491_4F6C_DD1D_u64;
+    let mut next = move |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound.max(1) as u64) as usize
+    };
+    let count = graph.node_count();
+    if count == 0 {
+        return Vec::new();
+    }
+    (0..QUERIES)
+        .map(|idx| {
+            let to = NodeIndex::new(next(count));
+            if idx % 2 == 0 {
+                return (NodeIndex::new(next(count)), to);
+            }
+            let mut from = to;
+            for _ in 0..next(50) {
+                let parents: Vec<_> = graph.neighbors_directed(from, Incoming).collect();
+                if parents.is_empty() {
+                    break;
+                }
+                from = parents[next(parents.len())];
+            }
+            (from, to)
+        })
+        .collect()
+}
+
+/// Build the index and run the queries, returning which were reachable.
+fn bench<R: ReachabilityIndex>(
+    name: &str,
+    graph: &Graph<(), ()>,
+    queries: &[(NodeIndex, NodeIndex)],
+) -> Vec<bool> {
+    let start = Instant::now();
+    let mut index = R::default();
+    for node in graph.node_indices() {
+        index.add_node(graph, node);
+    }
+    let build = start.elapsed();
+    let start = Instant::now();
+    let reachable: Vec<_> = queries
+        .iter()
+        .map(|&(from, to)| index.reaches(graph, from, to))
+        .collect();
+    let per_query = start.elapsed() / queries.len().max(1) as u32;
+    println!(
+        "{:40}  {:>9}  {:>9}  {name:>20}  {:>10}  {:>6} MB  {:>10}",
+        "",
+        "",
+        "",
+        format!("{:.1?}", build),
+        index.heap_size() / (1024 * 1024),
+        format!("{:.1?}", per_query),
+    );
+    reachable
+}
+
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use smt_log_parser::items::{InstIdx, LinePos};
+use smt_log_parser::parsers::z3::z3parser::Z3Parser;
+
+use super::CommandResult;
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to check.
+    log: PathBuf,
+    /// Also fail if there are any warnings.
+    #[arg(long)]
+    strict: bool,
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        let parser = super::load(&self.log, settings)?;
+        let diagnostics = parser.diagnostics();
+        for diagnostic in diagnostics.iter() {
+            println!("{diagnostic}");
+        }
+        let violations = violations(&parser);
+        for (pos, violation) in &violations {
+            println!("invariant violated on {pos}: {violation}");
+        }
+        let unexplained = parser
+            .matches()
+            .filter(|(_, m)| m.blamed.iter().any(|blame| blame.is_unexplained()))
+            .count();
+        if unexplained != 0 {
+            println!("{unexplained} matches depend on equalities which could not be explained");
+        }
+        let errors = diagnostics.errors().count();
+        let warnings = diagnostics.warnings().count();
+        println!(
+            "{errors} errors, {warnings} warnings and {} invariant violations",
+            violations.len()
+        );
+        let failed = errors != 0 || !violations.is_empty() || (self.strict && warnings != 0);
+        Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+    }
+}
+
+/// Checks that the parsed items are consistent with each other, a violation
+/// indicates either a malformed log or a bug in the parser.
+fn violations(parser: &Z3Parser) -> Vec<(LinePos, String)> {
+    let mut violations = Vec::new();
+    let (quant_count, _) = parser.quant_count_incl_theory_solving();
+    let enode_count = parser.enodes().count();
+    for idx in 0..parser.inst_count() {
+        let iidx = InstIdx::from(idx);
+        let inst = &parser[iidx];
+        let match_ = &parser[inst.match_];
+        if let Some(qidx) = match_.kind.quant_idx() {
+            if usize::from(qidx) >= quant_count {
+                violations.push((inst.pos, format!("{iidx:?} is of unknown quantifier {qidx:?}")));
+                continue;
+            }
+            let expected = parser[qidx].num_vars;
+            let bound = match_.kind.bound_terms(|_| (), |_| ()).len();
+            if bound != expected {
+                violations.push((
+                    inst.pos,
+                    format!("{iidx:?} binds {bound} terms but {qidx:?} has {expected} variables"),
+                ));
+            }
+        }
+        // Instantiations can only depend on earlier ones, otherwise the
+        // instantiation graph would have a cycle.
+        for (_, enode) in match_.due_to_enodes() {
+            if usize::from(enode) >= enode_count {
+                violations.push((match_.pos, format!("{iidx:?} blames unknown {enode:?}")));
+            } else if parser[enode].created_by.is_some_and(|by| by >= iidx) {
+                violations.push((
+                    match_.pos,
+                    format!("{iidx:?} blames {enode:?} which was created after it"),
+                ));
+            }
+        }
+        for &enode in inst.yields_terms.iter() {
+            if parser[enode].created_by != Some(iidx) {
+                violations.push((
+                    inst.pos,
+                    format!("{iidx:?} yields {enode:?} which it did not create"),
+                ));
+            }
+        }
+    }
+    for (eidx, enode) in parser.enodes() {
+        for eq in enode.equalities() {
+            if eq.expl.from() != eidx || usize::from(eq.to) >= enode_count {
+                violations.push((eq.pos, format!("equality of {eidx:?} is malformed")));
+            }
+        }
+    }
+    let mut prev_end = InstIdx::from(0);
+    for (qidx, query) in parser.queries().iter_enumerated() {
+        if query.insts.start < prev_end || query.insts.end < query.insts.start {
+            violations.push((query.begin, format!("instantiations of {qidx:?} overlap")));
+        }
+        prev_end = query.insts.end;
+    }
+    violations
+}
@@ -0,0 +1,108 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use fxhash::FxHashMap;
+use smt_log_parser::items::InstIdx;
+use smt_log_parser::parsers::z3::z3parser::Z3Parser;
+
+use super::CommandResult;
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to compare against.
+    old: PathBuf,
+    /// The log to compare.
+    new: PathBuf,
+    /// Only show the this many quantifiers with the largest change.
+    #[arg(long)]
+    top: Option<usize>,
+}
+
+/// The summary of a single log which is compared.
+struct Summary {
+    terms: usize,
+    insts: usize,
+    conflicts: usize,
+    errors: usize,
+    /// The number of instantiations and their total cost for each quantifier
+    /// by name.
+    quants: FxHashMap<String, (usize, f32)>,
+}
+
+impl Summary {
+    fn new(parser: &Z3Parser) -> Self {
+        let mut quants = FxHashMap::<String, (usize, f32)>::default();
+        for idx in 0..parser.inst_count() {
+            let inst = &parser[InstIdx::from(idx)];
+            let name = super::quant_name(parser, &parser[inst.match_].kind);
+            let (count, cost) = quants.entry(name.into_owned()).or_default();
+            *count += 1;
+            *cost += inst.cost;
+        }
+        Self {
+            terms: parser.terms().count(),
+            insts: parser.inst_count(),
+            conflicts: parser.search().conflicts().count(),
+            errors: parser.diagnostics().errors().count(),
+            quants,
+        }
+    }
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        let old = Summary::new(&super::load(&self.old, settings)?);
+        let new = Summary::new(&super::load(&self.new, settings)?);
+
+        let mut out = std::io::stdout().lock();
+        let totals = [
+            ("terms", old.terms, new.terms),
+            ("instantiations", old.insts, new.insts),
+            ("conflicts", old.conflicts, new.conflicts),
+            ("errors", old.errors, new.errors),
+        ];
+        for (name, old, new) in totals {
+            writeln!(out, "{name:14}  {old:>9} -> {new:>9}  ({:+})", new as i64 - old as i64)?;
+        }
+
+        let mut names: Vec<_> = old.quants.keys().chain(new.quants.keys()).collect();
+        names.sort();
+        names.dedup();
+        let mut rows: Vec<_> = names
+            .into_iter()
+            .map(|name| {
+                let old = old.quants.get(name).copied().unwrap_or_default();
+                let new = new.quants.get(name).copied().unwrap_or_default();
+                (name, old, new)
+            })
+            .filter(|(_, old, new)| old.0 != new.0)
+            .collect();
+        rows.sort_by_key(|(_, old, new)| std::cmp::Reverse(old.0.abs_diff(new.0)));
+        rows.truncate(self.top.unwrap_or(usize::MAX));
+        if rows.is_empty() {
+            writeln!(out, "\nNo quantifier has a different number of instantiations")?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        let width = rows.iter().map(|(name, ..)| name.len()).max().unwrap_or_default();
+        let width = width.max("quantifier".len());
+        writeln!(
+            out,
+            "\n{:width$}  {:>9}  {:>9}  {:>9}  {:>10}  {:>10}",
+            "quantifier", "old", "new", "change", "old cost", "new cost"
+        )?;
+        for (name, old, new) in rows {
+            writeln!(
+                out,
+                "{name:width$}  {:>9}  {:>9}  {:>+9}  {:>10}  {:>10}",
+                old.0,
+                new.0,
+                new.0 as i64 - old.0 as i64,
+                old.1,
+                new.1
+            )?;
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use smt_log_parser::display_with::{DisplayCtxt, DisplayWithCtxt};
+use smt_log_parser::items::{EqualityExpl, InstIdx, LinePos, MatchKind, QuantIdx, TermKind};
+use smt_log_parser::parsers::z3::z3parser::Z3Parser;
+use smt_log_parser::Cvc5Parser;
+
+use super::{write_records, CommandResult, Format, Solver};
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to export from.
+    log: PathBuf,
+    /// The solver which wrote the log. Only quantifiers and instantiations
+    /// can be exported from cvc5 logs.
+    #[arg(long, value_enum, default_value_t = Solver::Z3)]
+    solver: Solver,
+    /// The kind of items to export.
+    #[arg(long, value_enum)]
+    items: ItemKind,
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// The file to write to, defaults to standard output.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ItemKind {
+    Terms,
+    Quantifiers,
+    Instantiations,
+    /// Matches which were never instantiated.
+    PendingMatches,
+    Equalities,
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        if self.solver == Solver::Cvc5 {
+            return self.run_cvc5();
+        }
+        let parser = super::load(&self.log, settings)?;
+        let out = super::output(self.output.as_deref())?;
+        let ctxt = DisplayCtxt {
+            parser: &parser,
+
+            display_term_ids: false,
+            display_quantifier_name: false,
+            use_mathematical_symbols: false,
+        };
+        match self.items {
+            ItemKind::Terms => write_records(self.format, out, terms(&ctxt)),
+            ItemKind::Quantifiers => write_records(self.format, out, quantifiers(&ctxt)),
+            ItemKind::Instantiations => write_records(self.format, out, insts(&ctxt)),
+            ItemKind::PendingMatches => write_records(self.format, out, pending_matches(&ctxt)),
+            ItemKind::Equalities => write_records(self.format, out, equalities(&parser)),
+        }?;
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn run_cvc5(self) -> CommandResult {
+        if !matches!(self.items, ItemKind::Quantifiers | ItemKind::Instantiations) {
+            return Err("only quantifiers and instantiations can be exported from cvc5 logs".into());
+        }
+        let parser = super::load_cvc5(&self.log)?;
+        let out = super::output(self.output.as_deref())?;
+        match self.items {
+            ItemKind::Quantifiers => write_records(self.format, out, cvc5_quantifiers(&parser)),
+            _ => write_records(self.format, out, cvc5_insts(&parser)),
+        }?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Lines are one-based, as displayed by [`LinePos`].
+fn line(pos: LinePos) -> usize {
+    pos.line_no + 1
+}
+
+/// Lists of indices are space separated, such that they fit in a CSV cell.
+fn join<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    items.into_iter().map(|item| item.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// Terms contain spaces, so lists of them are separated by semicolons instead.
+fn join_terms(terms: impl IntoIterator<Item = String>) -> String {
+    terms.into_iter().collect::<Vec<_>>().join("; ")
+}
+
+#[derive(Serialize)]
+struct TermRecord {
+    idx: usize,
+    id: String,
+    line: usize,
+    kind: &'static str,
+    name: String,
+    children: String,
+    text: String,
+}
+
+fn terms<'a>(ctxt: &'a DisplayCtxt) -> impl Iterator<Item = TermRecord> + 'a {
+    let parser = ctxt.parser;
+    parser.terms().map(|(tidx, term)| {
+        let id = term.id.id.map(|id| id.to_string()).unwrap_or_default();
+        let (kind, name) = match &term.kind {
+            TermKind::Var(idx) => ("var", idx.to_string()),
+            TermKind::ProofOrApp(app) => {
+                let kind = if app.is_proof { "proof" } else { "app" };
+                (kind, parser.strings[app.name].to_string())
+            }
+            TermKind::Quant(qidx) => ("quant", parser[*qidx].kind.name(&parser.strings).into_owned()),
+        };
+        TermRecord {
+            idx: tidx.into(),
+            id: format!("{}#{id}", &parser.strings[term.id.namespace]),
+            line: line(term.pos),
+            kind,
+            name,
+            children: join(term.child_ids.iter()),
+            text: tidx.with(ctxt).to_string(),
+        }
+    })
+}
+
+#[derive(Serialize)]
+struct QuantRecord {
+    idx: usize,
+    name: String,
+    line: usize,
+    num_vars: usize,
+    cost: f32,
+    useful: u32,
+    text: Option<String>,
+}
+
+fn quantifiers<'a>(ctxt: &'a DisplayCtxt) -> impl Iterator<Item = QuantRecord> + 'a {
+    let parser = ctxt.parser;
+    let (quant_count, _) = parser.quant_count_incl_theory_solving();
+    (0..quant_count).map(|idx| {
+        let quant = &parser[QuantIdx::from(idx)];
+        QuantRecord {
+            idx,
+            name: quant.kind.name(&parser.strings).into_owned(),
+            line: line(quant.pos),
+            num_vars: quant.num_vars,
+            cost: quant.cost,
+            useful: quant.useful,
+            text: quant.term.map(|term| term.with(ctxt).to_string()),
+        }
+    })
+}
+
+#[derive(Serialize)]
+struct InstRecord {
+    idx: usize,
+    line: usize,
+    match_line: usize,
+    kind: &'static str,
+    quantifier: String,
+    query: Option<usize>,
+    cost: f32,
+    useful: u32,
+    z3_generation: Option<u32>,
+    fingerprint: String,
+    /// The enodes which triggered the match.
+    blamed: String,
+    /// The enodes created by the instantiation.
+    yields: String,
+}
+
+fn insts<'a>(ctxt: &'a DisplayCtxt) -> impl Iterator<Item = InstRecord> + 'a {
+    let parser = ctxt.parser;
+    (0..parser.inst_count()).map(|idx| {
+        let iidx = InstIdx::from(idx);
+        let inst = &parser[iidx];
+        let match_ = &parser[inst.match_];
+        let kind = match match_.kind {
+            MatchKind::MBQI { .. } => "mbqi",
+            MatchKind::TheorySolving { .. } => "theory-solving",
+            MatchKind::Axiom { .. } => "axiom",
+            MatchKind::Quantifier { .. } => "quantifier",
+            MatchKind::Untriggered { .. } => "untriggered",
+        };
+        InstRecord {
+            idx,
+            line: line(inst.pos),
+            match_line: line(match_.pos),
+            kind,
+            quantifier: super::quant_name(parser, &match_.kind).into_owned(),
+            query: parser.query_of_inst(iidx).map(usize::from),
+            cost: inst.cost,
+            useful: inst.useful,
+            z3_generation: inst.z3_generation,
+            fingerprint: inst.fingerprint.to_string(),
+            blamed: join(match_.due_to_enodes().map(|(_, enode)| enode)),
+            yields: join(inst.yields_terms.iter()),
+        }
+    })
+}
+
+fn cvc5_quantifiers(parser: &Cvc5Parser) -> impl Iterator<Item = QuantRecord> + '_ {
+    parser.quantifiers().iter_enumerated().map(|(qidx, quant)| QuantRecord {
+        idx: qidx.into(),
+        name: quant.kind.name(&parser.strings).into_owned(),
+        line: line(quant.pos),
+        num_vars: quant.num_vars,
+        cost: quant.cost,
+        useful: quant.useful,
+        text: None,
+    })
+}
+
+/// cvc5 instantiations are not triggered by any terms and create none.
+fn cvc5_insts(parser: &Cvc5Parser) -> impl Iterator<Item = InstRecord> + '_ {
+    parser.insts().iter_enumerated().map(|(iidx, inst)| {
+        let match_ = &parser[inst.match_];
+        let quant = match_.kind.quant_idx().unwrap();
+        InstRecord {
+            idx: iidx.into(),
+            line: line(inst.pos),
+            match_line: line(match_.pos),
+            kind: "untriggered",
+            quantifier: parser[quant].kind.name(&parser.strings).into_owned(),
+            query: parser.queries().iter().position(|query| query.insts.contains(&iidx)),
+            cost: inst.cost,
+            useful: inst.useful,
+            z3_generation: inst.z3_generation,
+            fingerprint: inst.fingerprint.to_string(),
+            blamed: String::new(),
+            yields: String::new(),
+        }
+    })
+}
+
+#[derive(Serialize)]
+struct PendingMatchRecord {
+    idx: usize,
+    line: usize,
+    quantifier: String,
+    pattern: Option<String>,
+    /// The terms which triggered the match.
+    triggered_by: String,
+    bound_terms: String,
+}
+
+fn pending_matches<'a>(ctxt: &'a DisplayCtxt) -> impl Iterator<Item = PendingMatchRecord> + 'a {
+    let parser = ctxt.parser;
+    parser.pending_matches().map(|(midx, match_)| {
+        PendingMatchRecord {
+            idx: midx.into(),
+            line: line(match_.pos),
+            quantifier: super::quant_name(parser, &match_.kind).into_owned(),
+            pattern: match_.kind.pattern().map(|pattern| pattern.with(ctxt).to_string()),
+            triggered_by: join_terms(match_.due_to_terms().map(|enode| enode.with(ctxt).to_string())),
+            bound_terms: join_terms(
+                match_
+                    .kind
+                    .bound_terms(|e| e.with(ctxt).to_string(), |t| t.with(ctxt).to_string()),
+            ),
+        }
+    })
+}
+
+#[derive(Serialize)]
+struct EqualityRecord {
+    line: usize,
+    from: usize,
+    to: usize,
+    kind: &'static str,
+    /// The theory (of a `theory` equality) or the explanation (of an
+    /// `unknown` equality).
+    detail: Option<String>,
+}
+
+fn equalities(parser: &Z3Parser) -> impl Iterator<Item = EqualityRecord> + '_ {
+    let equalities = parser.enodes().flat_map(|(_, enode)| enode.equalities());
+    equalities.filter_map(|eq| {
+        let (kind, detail) = match &eq.expl {
+            EqualityExpl::Root { .. } => return None,
+            EqualityExpl::Literal { .. } => ("literal", None),
+            EqualityExpl::Congruence { .. } => ("congruence", None),
+            EqualityExpl::Theory { theory, .. } => ("theory", Some(*theory)),
+            EqualityExpl::Axiom { .. } => ("axiom", None),
+            EqualityExpl::Unknown { kind, .. } => ("unknown", Some(*kind)),
+        };
+        Some(EqualityRecord {
+            line: line(eq.pos),
+            from: eq.expl.from().into(),
+            to: eq.to.into(),
+            kind,
+            detail: detail.map(|detail| parser.strings[detail].to_string()),
+        })
+    })
+}
@@ -0,0 +1,157 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, ExitCode, Stdio};
+
+use clap::ValueEnum;
+use petgraph::dot::{Config, Dot};
+use smt_log_parser::items::BlameKind;
+use smt_log_parser::parsers::z3::inst_graph::{EdgeType, InstGraph};
+
+use super::CommandResult;
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to render the instantiation graph of.
+    log: PathBuf,
+    /// SVG output requires Graphviz's `dot` to be installed.
+    #[arg(long, value_enum, default_value_t = Format::Dot)]
+    format: Format,
+    /// The file to write to, defaults to standard output.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Only show instantiations made while checking this query, as numbered
+    /// in the summary printed without a command.
+    #[arg(long)]
+    query: Option<usize>,
+    /// Only show instantiations of these quantifiers (or theories).
+    #[arg(long)]
+    quantifier: Vec<String>,
+    /// Hide theory solving instantiations.
+    #[arg(long)]
+    hide_theory: bool,
+    /// Only show instantiations at most this far from the roots.
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Only show the this many most costly of the remaining instantiations.
+    #[arg(long)]
+    most_costly: Option<usize>,
+    /// Only show the this many instantiations with the most children of the
+    /// remaining instantiations.
+    #[arg(long)]
+    most_branching: Option<usize>,
+    /// Also show matches which were never instantiated, as dotted nodes
+    /// below the visible instantiations they were triggered by.
+    #[arg(long)]
+    pending: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Dot,
+    Svg,
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        let parser = super::load(&self.log, settings)?;
+        let query = super::query(parser.queries().len(), self.query)?;
+        let mut graph = InstGraph::from(&parser);
+        graph.set_costs(&parser, &settings.cost_model);
+        if let Some(qidx) = query {
+            graph.retain_nodes(|node| node.query == Some(qidx));
+        }
+        if !self.quantifier.is_empty() {
+            graph.retain_nodes(|node| {
+                let name = super::quant_name(&parser, &node.mkind);
+                self.quantifier.iter().any(|q| *q == name)
+            });
+        }
+        if self.hide_theory {
+            graph.retain_nodes(|node| !node.is_theory_inst);
+        }
+        if let Some(max_depth) = self.max_depth {
+            graph.retain_nodes(|node| node.min_depth.is_some_and(|depth| depth <= max_depth));
+        }
+        if let Some(n) = self.most_costly {
+            graph.keep_n_most_costly(n);
+        }
+        if let Some(n) = self.most_branching {
+            graph.keep_n_most_branching(n);
+        }
+        graph.retain_visible_nodes_and_reconnect();
+        let mut ghosts = String::new();
+        if self.pending {
+            graph.add_pending_matches(&parser);
+            for (midx, parents) in graph.visible_ghosts() {
+                let name = super::quant_name(&parser, &parser[midx].kind);
+                ghosts += &format!(
+                    "    m{} [ label=\"pending\\n{}\" shape=box style=dotted ]\n",
+                    usize::from(midx),
+                    name.replace('\\', "\\\\").replace('"', "\\\""),
+                );
+                for parent in parents {
+                    let (parent, midx) = (parent.index(), usize::from(midx));
+                    ghosts += &format!("    {parent} -> m{midx} [ style=dotted ]\n");
+                }
+            }
+        }
+
+        let visible = &graph.visible_graph;
+        let dot = format!(
+            "digraph {{\n{:?}{ghosts}\n}}\n",
+            Dot::with_attr_getters(
+                visible,
+                &[Config::EdgeNoLabel, Config::NodeNoLabel, Config::GraphContentOnly],
+                &|_, edge| {
+                    let style = match edge.weight() {
+                        EdgeType::Direct { .. } => "solid",
+                        EdgeType::Indirect => "dashed",
+                    };
+                    let arrowhead = match edge.weight() {
+                        EdgeType::Direct {
+                            kind: BlameKind::Equality { .. },
+                            ..
+                        } => "empty",
+                        _ => "normal",
+                    };
+                    format!("style={style} arrowhead={arrowhead}")
+                },
+                &|_, (_, node)| {
+                    let name = super::quant_name(&parser, &node.mkind);
+                    format!(
+                        "label=\"{}\\n{}\" tooltip=\"cost {}\" shape=box style=\"{}\"",
+                        node.inst_idx,
+                        name.replace('\\', "\\\\").replace('"', "\\\""),
+                        node.cost(),
+                        if node.mkind.is_mbqi() { "dashed" } else { "solid" },
+                    )
+                },
+            )
+        );
+        let dot = match self.format {
+            Format::Dot => dot.into_bytes(),
+            Format::Svg => render_svg(&dot)?,
+        };
+        let mut out = super::output(self.output.as_deref())?;
+        out.write_all(&dot)?;
+        out.flush()?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Render a DOT graph with Graphviz.
+fn render_svg(dot: &str) -> std::io::Result<Vec<u8>> {
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| std::io::Error::new(err.kind(), format!("failed to run `dot`: {err}")))?;
+    child.stdin.take().unwrap().write_all(dot.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("`dot` failed with {}", output.status)));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,59 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use smt_log_parser::display_with::{DisplayCtxt, DisplayWithCtxt};
+use smt_log_parser::parsers::z3::inst_graph::InstGraph;
+
+use super::CommandResult;
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to search for matching loops.
+    log: PathBuf,
+    /// Only show the first this many loops.
+    #[arg(long)]
+    top: Option<usize>,
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        let parser = super::load(&self.log, settings)?;
+        let graph = InstGraph::from(&parser);
+        let loops = graph.matching_loops();
+        let ctxt = DisplayCtxt {
+            parser: &parser,
+            display_term_ids: false,
+            display_quantifier_name: false,
+            use_mathematical_symbols: false,
+        };
+        let mut out = std::io::stdout().lock();
+        writeln!(out, "{} matching loops", loops.len())?;
+        for (idx, matching_loop) in loops
+            .iter()
+            .take(self.top.unwrap_or(usize::MAX))
+            .enumerate()
+        {
+            let quants: Vec<_> = matching_loop
+                .quants
+                .iter()
+                .map(|&q| parser[q].kind.name(&parser.strings))
+                .collect();
+            writeln!(
+                out,
+                "\nLoop {idx}: {} ({} iterations, {} instantiations from {} to {})",
+                quants.join(" -> "),
+                matching_loop.iterations(),
+                matching_loop.nodes.len(),
+                matching_loop.chain[0],
+                matching_loop.chain.last().unwrap(),
+            )?;
+            let explanation = matching_loop.explain(&parser);
+            for line in explanation.with(&ctxt).to_string().lines() {
+                writeln!(out, "  {line}")?;
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
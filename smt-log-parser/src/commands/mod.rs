@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+use smt_log_parser::items::{MatchKind, QueryIdx};
+use smt_log_parser::parsers::z3::z3parser::Z3Parser;
+use smt_log_parser::{Cvc5Parser, LogParser};
+use wasm_timer::Instant;
+
+use crate::Settings;
+
+mod check;
+mod diff;
+mod export;
+mod graph;
+mod loops;
+mod patterns;
+mod stats;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print a table of statistics for each quantifier.
+    Stats(stats::Args),
+    /// Rank quantifier patterns by how many of their matches lead nowhere.
+    Patterns(patterns::Args),
+    /// Export the items of a log as JSON or CSV.
+    Export(export::Args),
+    /// Render the (filtered) instantiation graph as DOT or SVG.
+    Graph(graph::Args),
+    /// Find matching loops and explain how their quantifiers trigger each
+    /// other.
+    Loops(loops::Args),
+    /// Compare the instantiations of each quantifier in two logs.
+    Diff(diff::Args),
+    /// Check that a log parses without errors and is internally consistent.
+    Check(check::Args),
+}
+
+pub type CommandResult = Result<ExitCode, Box<dyn Error>>;
+
+impl Command {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        match self {
+            Self::Stats(args) => args.run(settings),
+            Self::Patterns(args) => args.run(settings),
+            Self::Export(args) => args.run(settings),
+            Self::Graph(args) => args.run(settings),
+            Self::Loops(args) => args.run(settings),
+            Self::Diff(args) => args.run(settings),
+            Self::Check(args) => args.run(settings),
+        }
+    }
+}
+
+/// Parse a log, the output of commands should not depend on whether it was
+/// loaded from the cache.
+fn load(log: &Path, settings: &Settings) -> io::Result<Z3Parser> {
+    crate::parse(log, settings, Instant::now())
+}
+
+/// The solver which wrote a log.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Solver {
+    Z3,
+    /// The output of cvc5 with `--dump-instantiations` and/or `-o inst`.
+    Cvc5,
+}
+
+/// Parse a cvc5 log, which is never cached and has no costs to compute.
+fn load_cvc5(log: &Path) -> io::Result<Cvc5Parser> {
+    let (_metadata, parser) = Cvc5Parser::from_file(log)?;
+    Ok(parser.process_all())
+}
+
+/// The query selected with `--query` out of the `count` queries of a log, as
+/// numbered in the summary printed without a command.
+fn query(count: usize, query: Option<usize>) -> io::Result<Option<QueryIdx>> {
+    let Some(query) = query else {
+        return Ok(None);
+    };
+    if query >= count {
+        let msg = format!("no query {query}, the log has {count} queries");
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    }
+    Ok(Some(QueryIdx::from(query)))
+}
+
+/// Where commands write their output: a file if one was given, otherwise
+/// standard output.
+fn output(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// A single array of objects.
+    Json,
+    /// One row per item, with a header.
+    Csv,
+}
+
+/// Write machine readable records, each record must be flat to be written as
+/// CSV.
+pub fn write_records<R: Serialize>(
+    format: Format,
+    mut out: impl Write,
+    records: impl Iterator<Item = R>,
+) -> io::Result<()> {
+    match format {
+        Format::Json => {
+            let records: Vec<_> = records.collect();
+            serde_json::to_writer_pretty(&mut out, &records)?;
+            writeln!(out)?;
+        }
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(out);
+            for record in records {
+                csv.serialize(record)?;
+            }
+            out = csv.into_inner().map_err(|err| err.into_error())?;
+        }
+    }
+    out.flush()
+}
+
+/// The name of the quantifier (or theory for theory solving instantiations)
+/// that was instantiated.
+fn quant_name<'a>(parser: &'a Z3Parser, kind: &MatchKind) -> Cow<'a, str> {
+    match kind {
+        MatchKind::TheorySolving { axiom_id, .. } => {
+            Cow::Borrowed(&parser.strings[axiom_id.namespace])
+        }
+        _ => parser[kind.quant_idx().unwrap()].kind.name(&parser.strings),
+    }
+}
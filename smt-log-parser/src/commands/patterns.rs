@@ -0,0 +1,76 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use smt_log_parser::parsers::z3::pattern_stats::PatternStats;
+
+use super::{write_records, CommandResult, Format};
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to analyse.
+    log: PathBuf,
+    /// Only consider the matches and instantiations of this query, as
+    /// numbered in the summary printed without a command.
+    #[arg(long)]
+    query: Option<usize>,
+    /// Only show the first this many patterns.
+    #[arg(long)]
+    top: Option<usize>,
+    /// Output JSON or CSV instead of a table.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        let parser = super::load(&self.log, settings)?;
+        let mut rows = match super::query(parser.queries().len(), self.query)? {
+            Some(qidx) => PatternStats::compute_for_query(&parser, qidx),
+            None => PatternStats::compute(&parser),
+        };
+        rows.truncate(self.top.unwrap_or(usize::MAX));
+        let out = std::io::stdout().lock();
+        match self.format {
+            Some(format) => write_records(format, out, rows.into_iter())?,
+            None => write_table(out, &rows)?,
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn write_table(mut out: impl Write, rows: &[PatternStats]) -> std::io::Result<()> {
+    let width = rows.iter().map(|row| row.name.len()).max().unwrap_or_default();
+    let width = width.max("quantifier".len());
+    writeln!(
+        out,
+        "{:width$}  {:>7}  {:>9}  {:>7}  {:>8}  {:>10}  {:>10}  {:>8}  {:>14}  pattern",
+        "quantifier",
+        "matches",
+        "instances",
+        "pending",
+        "bindings",
+        "modulo eq",
+        "productive",
+        "children",
+        "permissiveness"
+    )?;
+    for row in rows {
+        writeln!(
+            out,
+            "{:width$}  {:>7}  {:>9}  {:>7}  {:>8}  {:>10}  {:>10}  {:>8}  {:>14}  {}",
+            row.name,
+            row.matches,
+            row.instantiations,
+            row.pending_matches,
+            row.distinct_bindings,
+            row.distinct_bindings_modulo_eq,
+            row.productive_instantiations,
+            row.children,
+            row.permissiveness,
+            row.pattern_text
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::cmp::Reverse;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::ValueEnum;
+use smt_log_parser::parsers::z3::quant_stats::QuantifierStats;
+
+use super::{write_records, CommandResult, Format, Solver};
+use crate::Settings;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The log to analyse.
+    log: PathBuf,
+    /// The solver which wrote the log.
+    #[arg(long, value_enum, default_value_t = Solver::Z3)]
+    solver: Solver,
+    /// Only consider the matches and instantiations of this query, as
+    /// numbered in the summary printed without a command.
+    #[arg(long)]
+    query: Option<usize>,
+    /// The column to sort by, largest first.
+    #[arg(long, value_enum, default_value_t = Column::Cost)]
+    sort: Column,
+    /// Only show the first this many quantifiers.
+    #[arg(long)]
+    top: Option<usize>,
+    /// Output JSON or CSV instead of a table.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Column {
+    Name,
+    Matches,
+    Instances,
+    Unused,
+    Cost,
+    Depth,
+    Children,
+}
+
+impl Args {
+    pub fn run(self, settings: &Settings) -> CommandResult {
+        let rows = if self.solver == Solver::Cvc5 {
+            let parser = super::load_cvc5(&self.log)?;
+            let query = super::query(parser.queries().len(), self.query)?;
+            QuantifierStats::compute_cvc5(&parser, query)
+        } else {
+            let parser = super::load(&self.log, settings)?;
+            match super::query(parser.queries().len(), self.query)? {
+                Some(qidx) => QuantifierStats::compute_for_query(&parser, qidx),
+                None => QuantifierStats::compute(&parser),
+            }
+        };
+        let mut rows = rows.into_iter().collect::<Vec<_>>();
+        match self.sort {
+            Column::Name => rows.sort_by(|a, b| a.name.cmp(&b.name)),
+            Column::Matches => rows.sort_by_key(|row| Reverse(row.matches)),
+            Column::Instances => rows.sort_by_key(|row| Reverse(row.instantiations)),
+            Column::Unused => rows.sort_by_key(|row| Reverse(row.unused_matches)),
+            Column::Cost => rows.sort_by(|a, b| b.cost.total_cmp(&a.cost)),
+            Column::Depth => rows.sort_by_key(|row| Reverse(row.max_depth)),
+            Column::Children => rows.sort_by_key(|row| Reverse(row.children)),
+        }
+        rows.truncate(self.top.unwrap_or(usize::MAX));
+        let out = std::io::stdout().lock();
+        match self.format {
+            Some(format) => write_records(format, out, rows.into_iter())?,
+            None => write_table(out, &rows)?,
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn write_table(mut out: impl Write, rows: &[QuantifierStats]) -> std::io::Result<()> {
+    let width = rows.iter().map(|row| row.name.len()).max().unwrap_or_default();
+    let width = width.max("quantifier".len());
+    writeln!(
+        out,
+        "{:width$}  {:>7}  {:>9}  {:>6}  {:>10}  {:>7}  {:>8}  {:>5}  {:>6}  {:>8}  {:>7}",
+        "quantifier",
+        "matches",
+        "instances",
+        "unused",
+        "cost",
+        "max gen",
+        "mean gen",
+        "depth",
+        "yields",
+        "children",
+        "foreign"
+    )?;
+    for row in rows {
+        let max_gen = row.max_generation.map(|gen| gen.to_string()).unwrap_or_default();
+        let mean_gen = row.mean_generation.map(|gen| format!("{gen:.1}")).unwrap_or_default();
+        writeln!(
+            out,
+            "{:width$}  {:>7}  {:>9}  {:>6}  {:>10}  {max_gen:>7}  {mean_gen:>8}  {:>5}  {:>6}  {:>8}  {:>7}",
+            row.name,
+            row.matches,
+            row.instantiations,
+            row.unused_matches,
+            row.cost,
+            row.max_depth,
+            row.yielded_enodes,
+            row.children,
+            row.foreign_children
+        )?;
+    }
+    Ok(())
+}
@@ -228,6 +228,10 @@ impl DisplayWithCtxt<DisplayCtxt<'_>, ()> for &MatchKind {
                 axiom.fmt_with(f, ctxt, data)
             }
             MatchKind::Quantifier { quant, .. } => quant.fmt_with(f, ctxt, data),
+            MatchKind::Untriggered { quant, .. } => {
+                write!(f, "[Untriggered]")?;
+                quant.fmt_with(f, ctxt, data)
+            }
         }
     }
 }
@@ -259,7 +263,7 @@ impl<'a: 'b, 'b> DisplayWithCtxt<DisplayCtxt<'b>, DisplayData<'b>> for &'a Term
     }
 }
 
-impl<'a, 'b> DisplayWithCtxt<DisplayCtxt<'b>, DisplayData<'b>> for &'a TermKind {
+impl<'b> DisplayWithCtxt<DisplayCtxt<'b>, DisplayData<'b>> for &TermKind {
     fn fmt_with(
         self,
         f: &mut fmt::Formatter<'_>,
@@ -285,7 +289,7 @@ enum ProofOrAppKind<'a> {
     OtherApp(&'a str),
     Proof(&'a str),
 }
-impl<'a, 'b> DisplayWithCtxt<DisplayCtxt<'b>, DisplayData<'b>> for &'a ProofOrApp {
+impl<'b> DisplayWithCtxt<DisplayCtxt<'b>, DisplayData<'b>> for &ProofOrApp {
     fn fmt_with(
         self,
         f: &mut fmt::Formatter<'_>,
@@ -466,16 +470,7 @@ impl<'a> DisplayWithCtxt<DisplayCtxt<'a>, DisplayData<'a>> for &'a QuantKind {
             write!(f, "FORALL ")?;
         }
         if ctxt.display_quantifier_name {
-            write!(f, "\"")?;
-            match *self {
-                QuantKind::Other(kind) => write!(f, "{}", &ctxt.parser.strings[kind])?,
-                QuantKind::Lambda => write!(f, "<null>")?,
-                QuantKind::NamedQuant(name) => write!(f, "{}", &ctxt.parser.strings[name])?,
-                QuantKind::UnnamedQuant { name, id } => {
-                    write!(f, "{}!{id}", &ctxt.parser.strings[name])?
-                }
-            };
-            write!(f, "\" ")?;
+            write!(f, "\"{}\" ", self.name(&ctxt.parser.strings))?;
         }
         Ok(())
     }
@@ -42,11 +42,31 @@ idx!(InstIdx, "i{}");
 idx!(StackIdx, "s{}");
 idx!(ENodeIdx, "e{}");
 idx!(MatchIdx, "m{}");
+idx!(EventIdx, "ev{}");
+idx!(QueryIdx, "qr{}");
+
+/// A position in a log file: the (zero-based) line number and the byte offset
+/// of the start of that line. Items record the position of the line which
+/// created them. Displayed with a one-based line number, as in editors.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct LinePos {
+    pub line_no: usize,
+    pub byte_offset: usize,
+}
+
+impl fmt::Display for LinePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} (byte {})", self.line_no + 1, self.byte_offset)
+    }
+}
 
 /// A Z3 term and associated data.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Term {
     pub id: TermId,
+    pub pos: LinePos,
     pub kind: TermKind,
     // Reduces memory usage compared to a Vec
     pub child_ids: Box<[TermIdx]>,
@@ -99,9 +119,16 @@ pub struct Meaning {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Quantifier {
     pub kind: QuantKind,
+    pub pos: LinePos,
     pub num_vars: usize,
     pub term: Option<TermIdx>,
+    /// For Z3 logs, zero until computed with a
+    /// [`CostModel`](crate::parsers::z3::cost::CostModel), see
+    /// [`Z3Parser::compute_costs`](crate::Z3Parser::compute_costs).
     pub cost: f32,
+    /// The number of conflicts that instances of this quantifier took part in
+    /// (see [`Instantiation::useful`]).
+    pub useful: u32,
     pub instances: Vec<InstIdx>,
     pub vars: Option<VarNames>,
 }
@@ -141,6 +168,14 @@ impl QuantKind {
     pub fn is_discovered(&self) -> bool {
         matches!(self, Self::Other(_))
     }
+    pub fn name<'a>(&self, strings: &'a StringTable) -> Cow<'a, str> {
+        match *self {
+            Self::Other(kind) => Cow::Borrowed(&strings[kind]),
+            Self::Lambda => Cow::Borrowed("<null>"),
+            Self::NamedQuant(name) => Cow::Borrowed(&strings[name]),
+            Self::UnnamedQuant { name, id } => Cow::Owned(format!("{}!{id}", &strings[name])),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -172,16 +207,61 @@ impl VarNames {
             Self::NameAndType(names) => names.len(),
         }
     }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A single `check-sat` query, from a `[begin-check]` to the corresponding
+/// `[query-done]`. Logs from frontends such as Viper contain many of these.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Query {
+    /// The stack height at which the check was started.
+    pub level: usize,
+    pub begin: LinePos,
+    /// `None` if the query never finished (e.g. the log was truncated).
+    pub end: Option<LinePos>,
+    pub result: Option<QueryResult>,
+    /// The instantiations made while checking this query. The end of this
+    /// range is only accurate once the query has finished.
+    pub insts: std::ops::Range<InstIdx>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueryResult {
+    Sat,
+    Unsat,
+    Unknown,
+    Other(IString),
+}
+
+impl QueryResult {
+    pub(crate) fn parse(strings: &mut StringTable, value: &str) -> Self {
+        match value {
+            "sat" | "l_true" => Self::Sat,
+            "unsat" | "l_false" => Self::Unsat,
+            "unknown" | "l_undef" => Self::Unknown,
+            other => Self::Other(strings.get_or_intern(other)),
+        }
+    }
 }
 
 /// A Z3 instantiation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Instantiation {
     pub match_: MatchIdx,
+    pub pos: LinePos,
     pub fingerprint: Fingerprint,
     pub proof_id: Option<Result<TermIdx, TermId>>,
     pub z3_generation: Option<u32>,
+    /// For Z3 logs, zero until computed with a
+    /// [`CostModel`](crate::parsers::z3::cost::CostModel), see
+    /// [`Z3Parser::compute_costs`](crate::Z3Parser::compute_costs).
     pub cost: f32,
+    /// The number of `[conflict]` or `[resolve-lit]` lines which had a literal
+    /// created by this instantiation. Instantiations which are never useful
+    /// did not contribute to the search.
+    pub useful: u32,
     pub yields_terms: Box<[ENodeIdx]>,
 }
 
@@ -194,6 +274,7 @@ impl Instantiation {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Match {
     pub kind: MatchKind,
+    pub pos: LinePos,
     pub blamed: Box<[BlameKind]>,
 }
 
@@ -238,19 +319,26 @@ pub enum MatchKind {
         pattern: TermIdx,
         bound_terms: Vec<ENodeIdx>,
     },
+    /// An instantiation reported without the match which caused it (e.g. by
+    /// cvc5), only the quantifier and the bound terms are known.
+    Untriggered {
+        quant: QuantIdx,
+        bound_terms: Vec<TermIdx>,
+    },
 }
 impl MatchKind {
     pub fn quant_idx(&self) -> Option<QuantIdx> {
         match self {
             Self::MBQI { quant, .. }
             | Self::Axiom { axiom: quant, .. }
-            | Self::Quantifier { quant, .. } => Some(*quant),
+            | Self::Quantifier { quant, .. }
+            | Self::Untriggered { quant, .. } => Some(*quant),
             _ => None,
         }
     }
     pub fn pattern(&self) -> Option<TermIdx> {
         match self {
-            Self::MBQI { .. } | Self::TheorySolving { .. } => None,
+            Self::MBQI { .. } | Self::TheorySolving { .. } | Self::Untriggered { .. } => None,
             Self::Axiom { pattern, .. } | Self::Quantifier { pattern, .. } => Some(*pattern),
         }
     }
@@ -263,7 +351,9 @@ impl MatchKind {
             Self::MBQI { bound_terms, .. } | Self::Quantifier { bound_terms, .. } => {
                 bound_terms.iter().map(|&x| enode(x)).collect()
             }
-            Self::TheorySolving { bound_terms, .. } | Self::Axiom { bound_terms, .. } => {
+            Self::TheorySolving { bound_terms, .. }
+            | Self::Axiom { bound_terms, .. }
+            | Self::Untriggered { bound_terms, .. } => {
                 bound_terms.iter().map(|&x| term(x)).collect()
             }
         }
@@ -286,12 +376,18 @@ impl MatchKind {
 /// The kind of dependency between two quantifier instantiations.
 /// - Term: one instantiation produced a term that the other triggered on
 /// - Equality: dependency based on an equality.
+/// - UnknownEquality: dependency based on an equality which Z3 did not
+///   explain with an `[eq-expl]` and which could not be recovered otherwise.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BlameKind {
     Term { term: ENodeIdx },
     Equality { eq: ENodeIdx },
-    // TODO: why aren't all equalities explained by a prior `eq-expl`?
-    UnknownEquality { from: ENodeIdx, to: ENodeIdx },
+    UnknownEquality {
+        from: ENodeIdx,
+        to: ENodeIdx,
+        /// A `[mk-proof]` step which concludes this equality, if there is one.
+        proof: Option<TermIdx>,
+    },
 }
 
 impl BlameKind {
@@ -302,6 +398,10 @@ impl BlameKind {
             Self::UnknownEquality { .. } => None,
         }
     }
+    /// Is this an equality for which we have no justification at all?
+    pub fn is_unexplained(&self) -> bool {
+        matches!(self, Self::UnknownEquality { proof: None, .. })
+    }
 }
 
 /// An identifier for a Z3 quantifier instantiation (called "fingerprint" in the original Axiom Profiler).
@@ -361,7 +461,7 @@ impl TermId {
 /// of terms but `TermId`s don't map to this nicely, additionally the `TermId`s
 /// may repeat and so we want to map to the latest current `TermIdx`. Has a
 /// special fast path for the common empty namespace case.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TermIdToIdxMap {
     empty_string: IString,
     empty_namespace: Vec<Option<TermIdx>>,
@@ -431,10 +531,15 @@ pub enum EqualityExpl {
     Theory {
         from: ENodeIdx,
         theory: IString,
+        /// The equality term justifying this (e.g. yielded by a
+        /// theory-solving instantiation), if there is one.
+        eq: Option<ENodeIdx>,
         to: ENodeIdx,
     },
     Axiom {
         from: ENodeIdx,
+        /// The equality term justifying this, if there is one.
+        eq: Option<ENodeIdx>,
         to: ENodeIdx,
     },
     Unknown {
@@ -468,16 +573,29 @@ impl EqualityExpl {
             | Unknown { to, .. } => to,
         }
     }
-    pub fn dependency_on(&self) -> Option<ENodeIdx> {
+    /// What this equality holds because of, empty if it is unexplained.
+    pub fn dependency_on(&self) -> Vec<EqualityDependency> {
         use EqualityExpl::*;
-        match *self {
-            Root { .. } => None,
-            Literal { eq, .. } => Some(eq),
-            // TODO: other cases
-            Congruence { .. } => None,
-            Theory { .. } => None,
-            Axiom { .. } => None,
-            Unknown { .. } => None,
+        match self {
+            Root { .. } | Unknown { .. } => Vec::new(),
+            &Literal { eq, .. } => vec![EqualityDependency::Term(eq)],
+            Theory { eq, .. } | Axiom { eq, .. } => {
+                eq.iter().copied().map(EqualityDependency::Term).collect()
+            }
+            Congruence { arg_eqs, .. } => arg_eqs
+                .iter()
+                .map(|&(from, to)| EqualityDependency::Equality { from, to })
+                .collect(),
         }
     }
 }
+
+/// A single reason for an [`EqualityExpl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EqualityDependency {
+    /// An equality term which was asserted or derived.
+    Term(ENodeIdx),
+    /// Two arguments of a congruence which are themselves equal, the
+    /// explanation of which can be looked up in the e-graph.
+    Equality { from: ENodeIdx, to: ENodeIdx },
+}
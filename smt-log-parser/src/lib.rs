@@ -7,5 +7,6 @@ pub mod parsers;
 /// Pretty printing for items.
 pub mod display_with;
 
+pub use parsers::cvc5::cvc5parser::Cvc5Parser;
 pub use parsers::z3::z3parser::Z3Parser;
 pub use parsers::LogParser;
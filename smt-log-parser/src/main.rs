@@ -1,20 +1,81 @@
+use clap::{CommandFactory, Parser};
+use fxhash::FxHashMap;
 use serde::Deserialize;
+use smt_log_parser::display_with::{DisplayCtxt, DisplayWithCtxt};
+use smt_log_parser::parsers::z3::cost::CostModelKind;
+use smt_log_parser::parsers::z3::inst_graph::InstGraph;
+use smt_log_parser::parsers::z3::proof::ProofGraph;
 use smt_log_parser::parsers::z3::z3parser::Z3Parser;
-use smt_log_parser::parsers::LogParser;
-use std::{borrow::Cow, env, time::Duration};
+use smt_log_parser::items::{EqualityExpl, InstIdx, QuantIdx, QueryIdx, QueryResult};
+use smt_log_parser::parsers::{cache::{cache_path, CacheError}, LogParser};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{borrow::Cow, cmp::Reverse, time::Duration};
 use wasm_timer::Instant;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let settings = get_settings();
-    let filenames = if args.len() < 2 {
-        Cow::Owned(vec![settings.file])
+mod commands;
+
+/// Parse and analyse Z3 logs. Without a subcommand, print a summary of each
+/// log as configured in `settings.json`.
+#[derive(Parser)]
+// Subcommands take precedence so that global flags can come before them.
+#[command(version, subcommand_precedence_over_arg = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<commands::Command>,
+    /// The logs to summarise, defaults to the `file` in `settings.json`.
+    files: Vec<PathBuf>,
+    /// Stop parsing after this many seconds, overrides `settings.json`.
+    #[arg(long, global = true)]
+    timeout: Option<f32>,
+    /// Parse only up to this many lines, overrides `settings.json`.
+    #[arg(long, global = true)]
+    line_limit: Option<usize>,
+    /// How to compute the cost of instantiations (`even-split`, `yields`,
+    /// `generation` or `descendants`), overrides `settings.json`.
+    #[arg(long, global = true)]
+    cost_model: Option<CostModelKind>,
+    /// Save a cache of each parsed log next to it, overrides `settings.json`.
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    cache: bool,
+    /// Neither load nor save caches of parsed logs, overrides
+    /// `settings.json`.
+    #[arg(long, global = true)]
+    no_cache: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.command.is_some() && !cli.files.is_empty() {
+        let msg = "logs to summarise cannot be given together with a subcommand";
+        Cli::command().error(clap::error::ErrorKind::ArgumentConflict, msg).exit();
+    }
+    let mut settings = get_settings();
+    settings.timeout = cli.timeout.unwrap_or(settings.timeout);
+    settings.line_limit = cli.line_limit.unwrap_or(settings.line_limit);
+    settings.cost_model = cli.cost_model.unwrap_or(settings.cost_model);
+    if cli.cache {
+        settings.cache = CacheMode::Save;
+    } else if cli.no_cache {
+        settings.cache = CacheMode::Off;
+    }
+    if let Some(command) = cli.command {
+        return match command.run(&settings) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let filenames = if cli.files.is_empty() {
+        vec![PathBuf::from(&settings.file)]
     } else {
-        Cow::Borrowed(&args[1..])
+        cli.files
     };
-
-    for path in &*filenames {
-        let path = std::path::Path::new(path);
+    for path in &filenames {
         let filename = path
             .file_name()
             .map(|f| f.to_string_lossy())
@@ -29,15 +90,64 @@ fn main() {
         // let file = std::fs::read_to_string(path).unwrap();
         // let len = file.chars().filter(|c| *c == '\n').count();
         // let parsed = StreamParser::parse_entire_string(&file, Duration::from_secs_f32(10.0));
-        let to = Duration::from_secs_f32(15.0);
-        let (_metadata, parser) = Z3Parser::from_file(path).unwrap();
-        let (timeout, _result) = parser.process_all_timeout(to);
-        let elapsed_time = time.elapsed();
+        let result = match parse(path, &settings, time) {
+            Ok(result) => result,
+            Err(err) => {
+                println!("Failed to parse {filename:?}: {err}");
+                continue;
+            }
+        };
+        if let Some(version) = result.version_info() {
+            println!("{} {}", version.solver(), version.version());
+        }
+        let search = result.search();
+        println!(
+            "{} decisions, {} conflicts, backtracked {} levels (max level {})",
+            search.decisions().count(),
+            search.conflicts().count(),
+            search.total_backjump(),
+            search.max_level()
+        );
+        let proofs = ProofGraph::from(&result);
         println!(
-            "{} parsing after {} seconds (timeout {timeout:?})\n",
-            if timeout.is_some() { "Timeout" } else { "Finished" }, elapsed_time.as_secs_f32()
+            "{} of {} instantiations used in proofs",
+            proofs.used_insts().len(),
+            result.inst_count()
         );
-        // result.save_output_to_files(&settings, &time);
+        let (quant_count, _) = result.quant_count_incl_theory_solving();
+        let mut quants: Vec<_> = (0..quant_count).map(|q| &result[QuantIdx::from(q)]).collect();
+        quants.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+        for quant in quants.iter().take(10) {
+            println!(
+                "{}: cost {}, useful in {} conflicts",
+                quant.kind.name(&result.strings),
+                quant.cost,
+                quant.useful
+            );
+        }
+        let mut queries: Vec<_> = result
+            .queries()
+            .keys()
+            .map(|qidx| (qidx, result.query_insts(qidx).count()))
+            .collect();
+        queries.sort_by_key(|&(_, insts)| Reverse(insts));
+        println!("{} queries", queries.len());
+        for &(qidx, insts) in queries.iter().take(10) {
+            print_query(&result, qidx, insts);
+        }
+        let diagnostics = result.diagnostics();
+        for diagnostic in diagnostics.iter() {
+            println!("{diagnostic}");
+        }
+        println!(
+            "{} errors and {} warnings\n",
+            diagnostics.errors().count(),
+            diagnostics.warnings().count()
+        );
+        if let Err(err) = dump(&result, path, &settings) {
+            eprintln!("Error: {err}");
+            return ExitCode::FAILURE;
+        }
         // let render_engine = GraphVizRender;
         // let _svg_result = render_engine.make_svg(OUT_DOT, OUT_SVG);
         // add_link_to_svg(OUT_SVG, OUT_SVG_2);
@@ -49,33 +159,401 @@ fn main() {
         // let elapsed_time = time.elapsed();
         // println!("Done, run took {} seconds.", elapsed_time.as_secs_f32());
     }
+    ExitCode::SUCCESS
+}
+
+/// Print (with `verbose`) and save (with `save_all_data`) the items of the
+/// parsed log at `path`. Failing to save a file is reported and skipped, but
+/// failing to print is an error, e.g. if standard output was closed.
+fn dump(result: &Z3Parser, path: &Path, settings: &Settings) -> io::Result<()> {
+    if !settings.verbose && !settings.save_all_data {
+        return Ok(());
+    }
+    let dump = Dump::new(result, settings.sort_by, settings.reuses);
+    for items in Items::ALL {
+        if settings.verbose {
+            let mut stdout = io::stdout().lock();
+            writeln!(stdout, "{} (sorted by {:?}):", items.name(), settings.sort_by)?;
+            dump.write(items, &mut stdout)?;
+            writeln!(stdout)?;
+        }
+        if settings.save_all_data {
+            let out = items.path(path);
+            let saved = std::fs::File::create(&out)
+                .and_then(|file| dump.write(items, &mut BufWriter::new(file)));
+            match saved {
+                Ok(()) => println!("Saved {} to {}", items.name(), out.display()),
+                Err(err) => println!("Failed to save {}: {err}", items.name()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checking the time after every line is too slow, only do so once every this
+/// many lines.
+const LINES_PER_TIME_CHECK: usize = 1024;
+
+/// Parse the log at `path` (or load it from the cache), stopping early if
+/// the `timeout` or `line_limit` settings say so, and compute costs with the
+/// `cost_model`. Progress is reported on standard error.
+fn parse(path: &Path, settings: &Settings, time: Instant) -> io::Result<Z3Parser> {
+    let mut result = parse_uncosted(path, settings, time)?;
+    result.compute_costs(&settings.cost_model);
+    Ok(result)
+}
+
+fn parse_uncosted(path: &Path, settings: &Settings, time: Instant) -> io::Result<Z3Parser> {
+    // The cache always holds the entire log.
+    let cached = if settings.cache == CacheMode::Off {
+        None
+    } else if settings.line_limit != 0 {
+        Some(Err(format!("line limit of {}", settings.line_limit)))
+    } else {
+        match Z3Parser::load_cache(path) {
+            // No cache was saved for this log, nothing to report.
+            Err(CacheError::Io(err)) if err.kind() == io::ErrorKind::NotFound => None,
+            cached => Some(cached.map_err(|err| err.to_string())),
+        }
+    };
+    match cached {
+        Some(Ok(result)) => {
+            eprintln!("Loaded from cache after {} seconds", time.elapsed().as_secs_f32());
+            return Ok(result);
+        }
+        Some(Err(err)) => eprintln!("Not using cache: {err}"),
+        None => (),
+    }
+    // Only replace a cache which already exists, unless asked to save one.
+    let save_cache = match settings.cache {
+        CacheMode::Off => false,
+        CacheMode::Existing => cache_path(path).exists(),
+        CacheMode::Save => true,
+    };
+    let deadline = settings.timeout().map(|timeout| time + timeout);
+    let line_limit = settings.line_limit;
+    let (_metadata, mut parser) = Z3Parser::from_file(path)?;
+    let mut entries = 0_usize;
+    let stopped = parser.process_until(|_, state| {
+        if line_limit != 0 && state.lines_read >= line_limit {
+            return false;
+        }
+        entries += 1;
+        !entries.is_multiple_of(LINES_PER_TIME_CHECK)
+            || deadline.is_none_or(|deadline| Instant::now() < deadline)
+    });
+    let elapsed_time = time.elapsed();
+    let status = match stopped {
+        None => "Finished parsing",
+        Some(state) if line_limit != 0 && state.lines_read >= line_limit => {
+            "Reached line limit"
+        }
+        Some(_) => "Timeout",
+    };
+    eprintln!(
+        "{status} after {} seconds ({} lines)",
+        elapsed_time.as_secs_f32(),
+        parser.reader_state().lines_read
+    );
+    let result = parser.take_parser();
+    if stopped.is_none() && save_cache {
+        if let Err(err) = result.save_cache(path) {
+            eprintln!("Failed to save cache: {err}");
+        }
+    }
+    Ok(result)
+}
+
+fn print_query(parser: &Z3Parser, qidx: QueryIdx, insts: usize) {
+    let query = &parser.queries()[qidx];
+    let result = match query.result {
+        Some(QueryResult::Other(other)) => Cow::Borrowed(&parser.strings[other]),
+        Some(result) => Cow::Owned(format!("{result:?}")),
+        None => Cow::Borrowed("unfinished"),
+    };
+    let mut quant_insts = fxhash::FxHashMap::<QuantIdx, usize>::default();
+    for iidx in parser.query_insts(qidx) {
+        if let Some(quant) = parser[parser[iidx].match_].kind.quant_idx() {
+            *quant_insts.entry(quant).or_default() += 1;
+        }
+    }
+    let top = quant_insts.into_iter().max_by_key(|&(quant, count)| (count, quant));
+    let top = top
+        .map(|(quant, count)| format!(", most by {} ({count})", parser[quant].kind.name(&parser.strings)))
+        .unwrap_or_default();
+    println!("Query {qidx} on {} ({result}): {insts} instantiations{top}", query.begin);
+}
+
+/// The kinds of items which are printed with `verbose` and saved with
+/// `save_all_data`.
+#[derive(Clone, Copy, Debug)]
+enum Items {
+    Terms,
+    Quantifiers,
+    Instantiations,
+    Equalities,
+}
+
+impl Items {
+    const ALL: [Self; 4] = [Self::Terms, Self::Quantifiers, Self::Instantiations, Self::Equalities];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Terms => "terms",
+            Self::Quantifiers => "quantifiers",
+            Self::Instantiations => "instantiations",
+            Self::Equalities => "equalities",
+        }
+    }
+
+    /// Items are saved next to the log, e.g. the terms of `foo.log` are saved
+    /// in `foo.log.terms.txt`.
+    fn path(self, log: &Path) -> PathBuf {
+        let mut path = log.as_os_str().to_owned();
+        path.push(format!(".{}.txt", self.name()));
+        PathBuf::from(path)
+    }
+}
+
+/// Writes the items of a parsed log one per line, in the order given by
+/// `sort_by`.
+struct Dump<'a> {
+    ctxt: DisplayCtxt<'a>,
+    sort_by: SortBy,
+    /// Whether terms reusing an identifier are written separately.
+    reuses: bool,
+    /// Only built if the sort order needs it.
+    graph: Option<InstGraph>,
+}
+
+impl<'a> Dump<'a> {
+    fn new(parser: &'a Z3Parser, sort_by: SortBy, reuses: bool) -> Self {
+        let ctxt = DisplayCtxt {
+            parser,
+
+            display_term_ids: true,
+            display_quantifier_name: false,
+            use_mathematical_symbols: false,
+        };
+        let needs_graph = matches!(
+            sort_by,
+            SortBy::Depth | SortBy::Branching | SortBy::SubgraphSize
+        );
+        let graph = needs_graph.then(|| InstGraph::from(parser));
+        Self {
+            ctxt,
+            sort_by,
+            reuses,
+            graph,
+        }
+    }
+
+    fn write(&self, items: Items, out: &mut impl Write) -> io::Result<()> {
+        match items {
+            Items::Terms => self.write_terms(out),
+            Items::Quantifiers => self.write_quantifiers(out),
+            Items::Instantiations => self.write_insts(out),
+            Items::Equalities => self.write_equalities(out),
+        }
+    }
+
+    fn write_terms(&self, out: &mut impl Write) -> io::Result<()> {
+        let terms = self.ctxt.parser.terms();
+        if self.reuses {
+            for (tidx, term) in terms {
+                writeln!(out, "{tidx:?} at {}: {}", term.pos, tidx.with(&self.ctxt))?;
+            }
+            return out.flush();
+        }
+        // Merge the terms which share an identifier into the newest one, which
+        // is the term that the identifier refers to at the end of the log.
+        let mut by_id: FxHashMap<_, Vec<_>> = FxHashMap::default();
+        let mut ids = Vec::new();
+        for (tidx, term) in terms {
+            let id = (term.id.namespace, term.id.id);
+            let reuses = by_id.entry(id).or_default();
+            if reuses.is_empty() {
+                ids.push(id);
+            }
+            reuses.push((tidx, term.pos));
+        }
+        for id in ids {
+            let (&(tidx, pos), earlier) = by_id[&id].split_last().unwrap();
+            write!(out, "{tidx:?} at {pos}")?;
+            for (_, pos) in earlier {
+                write!(out, ", reused from {pos}")?;
+            }
+            writeln!(out, ": {}", tidx.with(&self.ctxt))?;
+        }
+        out.flush()
+    }
+
+    fn write_quantifiers(&self, out: &mut impl Write) -> io::Result<()> {
+        let parser = self.ctxt.parser;
+        let (quant_count, _) = parser.quant_count_incl_theory_solving();
+        let mut quants: Vec<_> = (0..quant_count).map(QuantIdx::from).collect();
+        // Quantifiers only have a cost, the other orders apply to
+        // instantiations.
+        if self.sort_by != SortBy::Line {
+            quants.sort_by(|&a, &b| parser[b].cost.total_cmp(&parser[a].cost));
+        }
+        for qidx in quants {
+            let quant = &parser[qidx];
+            write!(
+                out,
+                "{qidx:?} {}: cost {}, useful in {} conflicts",
+                quant.kind.name(&parser.strings),
+                quant.cost,
+                quant.useful
+            )?;
+            match quant.term {
+                Some(term) => writeln!(out, ", {}", term.with(&self.ctxt))?,
+                None => writeln!(out)?,
+            }
+        }
+        out.flush()
+    }
+
+    fn write_insts(&self, out: &mut impl Write) -> io::Result<()> {
+        let parser = self.ctxt.parser;
+        let mut insts: Vec<_> = (0..parser.inst_count()).map(InstIdx::from).collect();
+        let graph = self.graph.as_ref();
+        match self.sort_by {
+            SortBy::Line => (),
+            SortBy::Cost => insts.sort_by(|&a, &b| parser[b].cost.total_cmp(&parser[a].cost)),
+            SortBy::Depth => {
+                insts.sort_by_key(|&i| Reverse(graph.unwrap().node_data(i).max_depth()))
+            }
+            SortBy::Branching => {
+                insts.sort_by_key(|&i| Reverse(graph.unwrap().node_data(i).child_count()))
+            }
+            // Each size is a search of the graph, compute them only once.
+            SortBy::SubgraphSize => {
+                insts.sort_by_cached_key(|&i| Reverse(graph.unwrap().subgraph_size(i)))
+            }
+        }
+        for iidx in insts {
+            let inst = &parser[iidx];
+            let match_ = &parser[inst.match_];
+            write!(out, "{iidx:?} at {}: cost {}", inst.pos, inst.cost)?;
+            if let Some(graph) = graph {
+                let node = graph.node_data(iidx);
+                write!(
+                    out,
+                    ", depth {}, {} children, subgraph size {}",
+                    node.max_depth(),
+                    node.child_count(),
+                    graph.subgraph_size(iidx)
+                )?;
+            }
+            writeln!(out, ", {}", match_.kind.with(&self.ctxt))?;
+        }
+        out.flush()
+    }
+
+    fn write_equalities(&self, out: &mut impl Write) -> io::Result<()> {
+        let parser = self.ctxt.parser;
+        for (_, enode) in parser.enodes() {
+            for eq in enode.equalities() {
+                let kind = match &eq.expl {
+                    EqualityExpl::Root { .. } => continue,
+                    EqualityExpl::Literal { eq, .. } => {
+                        Cow::Owned(format!("literal {}", eq.with(&self.ctxt)))
+                    }
+                    EqualityExpl::Congruence { .. } => Cow::Borrowed("congruence"),
+                    EqualityExpl::Theory { theory, .. } => {
+                        Cow::Owned(format!("theory {}", &parser.strings[*theory]))
+                    }
+                    EqualityExpl::Axiom { .. } => Cow::Borrowed("axiom"),
+                    EqualityExpl::Unknown { kind, .. } => {
+                        Cow::Owned(format!("unknown {}", &parser.strings[*kind]))
+                    }
+                };
+                writeln!(
+                    out,
+                    "{}: {} = {} by {kind}",
+                    eq.pos,
+                    eq.expl.from().with(&self.ctxt),
+                    eq.to.with(&self.ctxt)
+                )?;
+            }
+        }
+        out.flush()
+    }
+}
+
+/// The order in which items are printed with `verbose` and saved with
+/// `save_all_data`. The orders other than `Line` put the largest first.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    /// The order in which items appear in the log.
+    #[default]
+    #[serde(alias = "line_number")]
+    Line,
+    /// The cost of instantiations and quantifiers.
+    Cost,
+    /// The length of the longest chain of instantiations leading to an
+    /// instantiation.
+    Depth,
+    /// The number of instantiations which directly depend on an instantiation.
+    Branching,
+    /// The number of instantiations which transitively depend on an
+    /// instantiation.
+    SubgraphSize,
+}
+
+/// When to use a cache of the parsed log, see
+/// [`cache_path`](smt_log_parser::parsers::cache::cache_path).
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// Never load or save a cache.
+    Off,
+    /// Load a cache if there is one and replace it if it is stale, but never
+    /// create a new one.
+    #[default]
+    Existing,
+    /// Load a cache if there is one, otherwise save one after parsing.
+    Save,
 }
 
 /// Parsing settings.
 #[derive(Default, Clone, Debug, Deserialize)]
+#[serde(default)]
 pub struct Settings {
-    // not all settings currently work
     /// Name of file to parse.
     pub file: String,
-    /// Whether to consider terms with reused IDs as separate from previous terms with the same ID. Does not work yet.
+    /// Whether to keep terms which reuse the identifier of an earlier term
+    /// separate when printing and saving terms, otherwise they are merged
+    /// into the newest term.
     pub reuses: bool,
     /// Print contents of parser to standard output item by item.
     pub verbose: bool,
-    /// Whether to print all text/json files (Dot and SVG will always be generated regardless of this setting)
+    /// Whether to save the terms, quantifiers, instantiations and equalities
+    /// to text files next to the log.
     pub save_all_data: bool,
-    /// Select a sort type (by line number, cost, depth, etc.)
-    /// Does not work yet.
-    /// ## TODO
-    /// Replace with an enum
-    pub sort_by: String,
-    /// Stop parsing after a certain amount of time. Does not work yet.
+    /// The order in which items are printed and saved.
+    pub sort_by: SortBy,
+    /// Stop parsing after this many seconds, `0` to parse the entire log.
     pub timeout: f32,
-    /// Parse only up to a certain number of lines. Does not work yet.
+    /// Parse only up to this many lines, `0` to parse the entire log.
     pub line_limit: usize,
+    /// How to compute the cost of instantiations and quantifiers.
+    pub cost_model: CostModelKind,
+    /// Whether to load and save a cache of the parsed log next to it. Caches
+    /// hold the entire parsed log and so can be larger than the log itself.
+    pub cache: CacheMode,
     // add settings for:
     // - number of instantiations to display in final visualization.
 }
 
+impl Settings {
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout > 0.0).then(|| Duration::from_secs_f32(self.timeout))
+    }
+}
+
 /// settings file
 const SETTINGS: &str = "settings.json";
 
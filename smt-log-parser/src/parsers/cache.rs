@@ -0,0 +1,128 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Bump this whenever the layout of any cached type changes (e.g. a field is
+/// added to a parser struct) or the parser starts filling in a field it left
+/// empty before, so that old caches are rejected rather than misread.
+pub const CACHE_VERSION: u32 = 17;
+
+const MAGIC: [u8; 8] = *b"SLPCACHE";
+/// How much of the start and end of the log file is hashed to detect changes.
+/// Hashing the entire (multi-GB) file would defeat the purpose of the cache.
+const HASHED_BYTES: u64 = 1024 * 1024;
+
+/// The path of the cache file for a given log: the log path with `.cache`
+/// appended, e.g. `foo.log` is cached in `foo.log.cache`.
+pub fn cache_path(log: &Path) -> PathBuf {
+    let mut path = log.as_os_str().to_owned();
+    path.push(".cache");
+    PathBuf::from(path)
+}
+
+/// Identifies the exact version of a log file that a cache was created from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub size: u64,
+    /// Modification time in nanoseconds since the unix epoch.
+    pub mtime: Option<u128>,
+    /// Hash of the first and last [`HASHED_BYTES`] of the file.
+    pub hash: u64,
+}
+
+impl CacheKey {
+    pub fn new(log: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(log)?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos());
+        let mut data = Vec::new();
+        (&mut file).take(HASHED_BYTES).read_to_end(&mut data)?;
+        let tail_start = size.saturating_sub(HASHED_BYTES).max(data.len() as u64);
+        file.seek(SeekFrom::Start(tail_start))?;
+        file.read_to_end(&mut data)?;
+        let hash = fxhash::hash64(&data);
+        Ok(Self { size, mtime, hash })
+    }
+}
+
+/// Written at the start of each cache file, checked before the (large) rest of
+/// the file is deserialized.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheHeader {
+    magic: [u8; 8],
+    version: u32,
+    key: CacheKey,
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    /// The file is not a cache file, or was written by an incompatible version
+    /// of this library.
+    IncompatibleVersion,
+    /// The log file has changed since the cache was created.
+    Stale,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "cache io error: {err}"),
+            Self::Encoding(err) => write!(f, "cache encoding error: {err}"),
+            Self::IncompatibleVersion => write!(f, "cache has an incompatible version"),
+            Self::Stale => write!(f, "cache is stale, the log has changed"),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<std::io::Error> for CacheError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+impl From<bincode::Error> for CacheError {
+    fn from(err: bincode::Error) -> Self {
+        Self::Encoding(err)
+    }
+}
+
+/// Write `data` to the cache file of `log` (see [`cache_path`]). The data
+/// should be the result of fully parsing `log`.
+pub fn save<T: Serialize>(log: &Path, data: &T) -> Result<(), CacheError> {
+    let header = CacheHeader {
+        magic: MAGIC,
+        version: CACHE_VERSION,
+        key: CacheKey::new(log)?,
+    };
+    let mut writer = BufWriter::new(File::create(cache_path(log))?);
+    bincode::serialize_into(&mut writer, &header)?;
+    bincode::serialize_into(&mut writer, data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the data previously cached by [`save`] for `log`. Fails if there is
+/// no cache, or if it is from a different version or of a different log.
+pub fn load<T: DeserializeOwned>(log: &Path) -> Result<T, CacheError> {
+    let mut reader = BufReader::new(File::open(cache_path(log))?);
+    let header: CacheHeader =
+        bincode::deserialize_from(&mut reader).map_err(|_| CacheError::IncompatibleVersion)?;
+    if header.magic != MAGIC || header.version != CACHE_VERSION {
+        return Err(CacheError::IncompatibleVersion);
+    }
+    if header.key != CacheKey::new(log)? {
+        return Err(CacheError::Stale);
+    }
+    Ok(bincode::deserialize_from(reader)?)
+}
@@ -0,0 +1,291 @@
+use futures::{AsyncBufRead, AsyncRead};
+use std::cell::Cell;
+use std::io::{self, BufRead, Read, Write};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{ready, Context, Poll};
+
+/// A compression format which logs may be stored in, these are detected from
+/// the first few bytes of the input and decompressed on the fly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The number of bytes needed by [`detect`](Self::detect).
+    pub const MAGIC_LEN: usize = 4;
+
+    /// Detect the compression format from the magic bytes at the start of
+    /// the input, `None` if the input is not (known to be) compressed.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Compressed input is buffered until there is at least this much, which
+/// ensures that the zstd decoder always has at least one full block (of at
+/// most 128KiB plus headers) available.
+const MIN_INPUT: usize = 128 * 1024 + 64;
+
+/// Decompresses an input stream, implements both [`BufRead`] and
+/// [`AsyncBufRead`] depending on the underlying reader.
+pub struct Decompress<R> {
+    inner: R,
+    state: DecodeState,
+}
+
+impl<R> Decompress<R> {
+    /// Decompress `inner` with the given format. The number of compressed
+    /// bytes consumed so far is kept up to date in `compressed_read`.
+    pub fn new(inner: R, compression: Compression, compressed_read: Rc<Cell<usize>>) -> Self {
+        let decoder = match compression {
+            Compression::Gzip => {
+                Decoder::Gzip(Box::new(flate2::write::MultiGzDecoder::new(Vec::new())))
+            }
+            Compression::Zstd => Decoder::Zstd(ZstdDecoder::default()),
+        };
+        let state = DecodeState {
+            decoder,
+            compressed_read,
+            pending: Vec::new(),
+            output: Vec::new(),
+            out_pos: 0,
+            done: false,
+            error: None,
+        };
+        Self { inner, state }
+    }
+}
+
+impl<R: BufRead> Read for Decompress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decompress<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.state.needs_input() {
+            let chunk = self.inner.fill_buf()?;
+            let n = chunk.len();
+            self.state.push(chunk)?;
+            self.inner.consume(n);
+        }
+        self.state.available()
+    }
+    fn consume(&mut self, amt: usize) {
+        self.state.consume(amt)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for Decompress<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for Decompress<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        while this.state.needs_input() {
+            let chunk = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+            let n = chunk.len();
+            this.state.push(chunk)?;
+            Pin::new(&mut this.inner).consume(n);
+        }
+        Poll::Ready(this.state.available())
+    }
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().state.consume(amt)
+    }
+}
+
+/// The reader independent part of [`Decompress`].
+struct DecodeState {
+    decoder: Decoder,
+    compressed_read: Rc<Cell<usize>>,
+    /// Compressed input not yet consumed by the decoder.
+    pending: Vec<u8>,
+    /// Decompressed output, of which `out_pos` bytes have been consumed.
+    output: Vec<u8>,
+    out_pos: usize,
+    /// Has the end of the compressed input been reached?
+    done: bool,
+    /// Why the input ended early, returned once all output decoded before
+    /// that point has been consumed.
+    error: Option<io::Error>,
+}
+
+impl DecodeState {
+    fn needs_input(&self) -> bool {
+        self.out_pos == self.output.len() && !self.done
+    }
+    fn available(&mut self) -> io::Result<&[u8]> {
+        if self.out_pos == self.output.len() {
+            if let Some(error) = self.error.take() {
+                return Err(error);
+            }
+        }
+        Ok(&self.output[self.out_pos..])
+    }
+    fn consume(&mut self, amt: usize) {
+        self.out_pos = (self.out_pos + amt).min(self.output.len());
+    }
+
+    /// Add a chunk of compressed input, an empty chunk signals the end of the
+    /// input. Should only be called once all output has been consumed. If the
+    /// input cannot be decompressed any further, the error is kept until the
+    /// output decompressed before it has been consumed.
+    fn push(&mut self, chunk: &[u8]) -> io::Result<()> {
+        let eof = chunk.is_empty();
+        self.compressed_read
+            .set(self.compressed_read.get() + chunk.len());
+        self.pending.extend_from_slice(chunk);
+        if self.pending.len() < MIN_INPUT && !eof {
+            return Ok(());
+        }
+        self.output.clear();
+        self.out_pos = 0;
+        let consumed = match self.decoder.decode(&self.pending, &mut self.output) {
+            Ok(consumed) => consumed,
+            Err(error) => {
+                self.done = true;
+                self.error = Some(error);
+                return Ok(());
+            }
+        };
+        self.pending.drain(..consumed);
+        if eof {
+            self.done = true;
+            let finished = self.decoder.finish(&mut self.output);
+            self.error = finished.err();
+            if self.error.is_none() && !self.pending.is_empty() {
+                self.error = Some(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "compressed log ended part way through",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Decoder {
+    Gzip(Box<flate2::write::MultiGzDecoder<Vec<u8>>>),
+    Zstd(ZstdDecoder),
+}
+
+impl Decoder {
+    /// Decompress as much of `input` as possible, appending to `output`.
+    /// Returns the number of bytes of `input` consumed. On an error `output`
+    /// still holds everything decompressed before it.
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<usize> {
+        match self {
+            Self::Gzip(gzip) => {
+                let mut consumed = 0;
+                let mut result = Ok(());
+                while consumed < input.len() {
+                    match gzip.write(&input[consumed..]) {
+                        Ok(0) => break,
+                        Ok(n) => consumed += n,
+                        Err(error) => {
+                            result = Err(error);
+                            break;
+                        }
+                    }
+                }
+                output.append(gzip.get_mut());
+                result.map(|()| consumed)
+            }
+            Self::Zstd(zstd) => zstd.decode(input, output),
+        }
+    }
+    /// Flush any remaining output once all input has been decoded.
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Self::Gzip(gzip) => {
+                let finished = gzip.try_finish();
+                output.append(gzip.get_mut());
+                finished?;
+            }
+            Self::Zstd(zstd) => {
+                zstd.decode(&[], output)?;
+                if zstd.in_frame {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "compressed log ended part way through a frame",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ZstdDecoder {
+    frame: Box<ruzstd::FrameDecoder>,
+    /// Has the header of the current frame been read (and the frame not yet
+    /// fully drained)?
+    in_frame: bool,
+    scratch: Box<[u8]>,
+}
+
+impl Default for ZstdDecoder {
+    fn default() -> Self {
+        Self {
+            frame: Box::new(ruzstd::FrameDecoder::new()),
+            in_frame: false,
+            scratch: vec![0; 64 * 1024].into_boxed_slice(),
+        }
+    }
+}
+
+impl ZstdDecoder {
+    fn decode(&mut self, mut input: &[u8], output: &mut Vec<u8>) -> io::Result<usize> {
+        fn invalid(err: ruzstd::frame_decoder::FrameDecoderError) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        }
+        let len = input.len();
+        loop {
+            if !self.in_frame {
+                if input.is_empty() {
+                    break;
+                }
+                // A file may contain multiple frames one after another.
+                self.frame.reset(&mut input).map_err(invalid)?;
+                self.in_frame = true;
+            }
+            let (read, written) = self
+                .frame
+                .decode_from_to(input, &mut self.scratch)
+                .map_err(invalid)?;
+            input = &input[read.min(input.len())..];
+            output.extend_from_slice(&self.scratch[..written]);
+            if self.frame.is_finished() && self.frame.can_collect() == 0 {
+                self.in_frame = false;
+            } else if read == 0 && written == 0 {
+                // Need more input to make progress.
+                break;
+            }
+        }
+        Ok(len - input.len())
+    }
+}
@@ -0,0 +1,289 @@
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use typed_index_collections::TiVec;
+
+use crate::{
+    items::*,
+    parsers::{
+        diagnostics::{DiagnosticKind, Diagnostics},
+        observer::{EventQueue, ParseEvent},
+    },
+};
+
+use super::sexpr::SExpr;
+
+type ParseResult = Result<(), DiagnosticKind>;
+
+/// A parser for the instantiation output of cvc5. Use one of the various
+/// `Cvc5Parser::from_*` methods to construct this parser.
+///
+/// The following entries are understood:
+/// - `sat`, `unsat` and `unknown`: the result of a `check-sat`, starts a new
+///   [`Query`] to which all following instantiations belong.
+/// - `(instantiations <quant> ( <term>* )* )`: all instantiations of a
+///   quantifier with the terms bound to each variable, printed with
+///   `--dump-instantiations`. The quantifier is either its `:qid` or the full
+///   `(forall ...)` term.
+/// - `(num-instantiations <quant> <n>)`: the number of instantiations of a
+///   quantifier, printed with `-o inst`.
+///
+/// Unlike Z3, cvc5 does not report which e-graph terms triggered an
+/// instantiation, so each instantiation has a [`MatchKind::Untriggered`]
+/// match.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cvc5Parser {
+    terms: TiVec<TermIdx, Term>,
+    /// Identical terms share a single index.
+    term_map: FxHashMap<(IString, Box<[TermIdx]>), TermIdx>,
+
+    quantifiers: TiVec<QuantIdx, Quantifier>,
+    quant_map: FxHashMap<IString, QuantIdx>,
+    /// The most recent counts printed with `-o inst`.
+    reported_counts: FxHashMap<QuantIdx, usize>,
+
+    matches: TiVec<MatchIdx, Match>,
+    insts: TiVec<InstIdx, Instantiation>,
+    queries: TiVec<QueryIdx, Query>,
+
+    pub(super) diagnostics: Diagnostics,
+    #[serde(skip)]
+    pub(super) events: EventQueue,
+
+    pub strings: StringTable,
+}
+
+impl Default for Cvc5Parser {
+    fn default() -> Self {
+        Self {
+            terms: TiVec::new(),
+            term_map: FxHashMap::default(),
+            quantifiers: TiVec::new(),
+            quant_map: FxHashMap::default(),
+            reported_counts: FxHashMap::default(),
+            matches: TiVec::new(),
+            insts: TiVec::new(),
+            queries: TiVec::new(),
+            diagnostics: Diagnostics::default(),
+            events: EventQueue::default(),
+            strings: StringTable::with_hasher(fxhash::FxBuildHasher::default()),
+        }
+    }
+}
+
+impl Cvc5Parser {
+    pub fn terms(&self) -> &TiVec<TermIdx, Term> {
+        &self.terms
+    }
+    pub fn quantifiers(&self) -> &TiVec<QuantIdx, Quantifier> {
+        &self.quantifiers
+    }
+    pub fn matches(&self) -> &TiVec<MatchIdx, Match> {
+        &self.matches
+    }
+    pub fn insts(&self) -> &TiVec<InstIdx, Instantiation> {
+        &self.insts
+    }
+    /// The `check-sat` results in the output, in order.
+    pub fn queries(&self) -> &TiVec<QueryIdx, Query> {
+        &self.queries
+    }
+    /// Find a quantifier by its `:qid` (or full term if it has no name).
+    pub fn quantifier(&self, name: &str) -> Option<QuantIdx> {
+        let name = self.strings.get(name)?;
+        self.quant_map.get(&name).copied()
+    }
+    /// The number of instantiations of `qidx` cvc5 reported with `-o inst`.
+    /// This may be larger than the number of dumped instantiations.
+    pub fn reported_inst_count(&self, qidx: QuantIdx) -> Option<usize> {
+        self.reported_counts.get(&qidx).copied()
+    }
+    /// Problems encountered so far while parsing the log.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub(super) fn entry(&mut self, line: &str, pos: LinePos) -> ParseResult {
+        let sexpr = SExpr::parse(line)?;
+        if let Some(result) = sexpr.atom() {
+            return self.check_sat_result(result, pos);
+        }
+        let list = sexpr.list().unwrap();
+        let (tag, rest) = list.split_first().ok_or(DiagnosticKind::MalformedLine)?;
+        match tag.atom() {
+            Some("instantiations") => self.instantiations(rest, pos),
+            Some("num-instantiations") => self.num_instantiations(rest, pos),
+            _ => Err(DiagnosticKind::UnknownTag),
+        }
+    }
+
+    fn check_sat_result(&mut self, result: &str, pos: LinePos) -> ParseResult {
+        let result = match result {
+            "sat" | "unsat" | "unknown" => QueryResult::parse(&mut self.strings, result),
+            _ => return Err(DiagnosticKind::UnknownTag),
+        };
+        let next_inst = self.insts.next_key();
+        self.queries.push(Query {
+            level: 0,
+            begin: pos,
+            end: Some(pos),
+            result: Some(result),
+            insts: next_inst..next_inst,
+        });
+        Ok(())
+    }
+
+    fn instantiations(&mut self, l: &[SExpr], pos: LinePos) -> ParseResult {
+        let (quant, tuples) = l.split_first().ok_or(DiagnosticKind::MalformedLine)?;
+        // Check all tuples before creating anything.
+        let tuples = tuples
+            .iter()
+            .map(|tuple| tuple.list().ok_or(DiagnosticKind::MalformedLine))
+            .collect::<Result<Vec<_>, _>>()?;
+        let num_vars = self.known_num_vars(quant);
+        let num_vars = num_vars.or(tuples.first().map(|t| t.len())).unwrap_or_default();
+        if tuples.iter().any(|t| t.len() != num_vars) {
+            return Err(DiagnosticKind::MalformedLine);
+        }
+        let qidx = self.quantifier_of(quant, num_vars, pos);
+        for tuple in tuples {
+            let bound_terms = tuple.iter().map(|t| self.term(t, pos)).collect();
+            let match_ = self.matches.push_and_get_key(Match {
+                kind: MatchKind::Untriggered {
+                    quant: qidx,
+                    bound_terms,
+                },
+                pos,
+                blamed: Box::default(),
+            });
+            self.events.push(ParseEvent::NewMatch(match_));
+            let iidx = self.insts.push_and_get_key(Instantiation {
+                match_,
+                pos,
+                fingerprint: Fingerprint(0),
+                proof_id: None,
+                z3_generation: None,
+                cost: 1.0,
+                useful: 0,
+                yields_terms: Box::default(),
+            });
+            let quant = &mut self.quantifiers[qidx];
+            quant.instances.push(iidx);
+            quant.cost += 1.0;
+            // There are no terms created by cvc5 instantiations.
+            self.events.push(ParseEvent::InstStart(iidx));
+            self.events.push(ParseEvent::InstEnd(iidx));
+        }
+        if let Some(query) = self.queries.last_mut() {
+            query.insts.end = self.insts.next_key();
+        }
+        Ok(())
+    }
+
+    fn num_instantiations(&mut self, l: &[SExpr], pos: LinePos) -> ParseResult {
+        let [quant, count] = l else {
+            return Err(DiagnosticKind::MalformedLine);
+        };
+        let count = count
+            .atom()
+            .and_then(|c| c.parse().ok())
+            .ok_or(DiagnosticKind::MalformedLine)?;
+        let qidx = self.quantifier_of(quant, 0, pos);
+        self.reported_counts.insert(qidx, count);
+        Ok(())
+    }
+
+    /// The number of variables of the quantifier named by `quant`, if it is
+    /// already known from earlier instantiations.
+    fn known_num_vars(&self, quant: &SExpr) -> Option<usize> {
+        let name = match quant {
+            SExpr::Atom(name) => self.strings.get(name),
+            list => self.strings.get(list.to_string()),
+        };
+        let quant = &self.quantifiers[*self.quant_map.get(&name?)?];
+        (!quant.instances.is_empty()).then_some(quant.num_vars)
+    }
+
+    /// Looks up the quantifier named by `quant` (creating it if this is the
+    /// first time it is seen).
+    fn quantifier_of(&mut self, quant: &SExpr, num_vars: usize, pos: LinePos) -> QuantIdx {
+        let name = match quant {
+            SExpr::Atom(name) => self.strings.get_or_intern(name),
+            list => self.strings.get_or_intern(list.to_string()),
+        };
+        let qidx = *self.quant_map.entry(name).or_insert_with(|| {
+            let qidx = self.quantifiers.push_and_get_key(Quantifier {
+                kind: QuantKind::NamedQuant(name),
+                pos,
+                num_vars,
+                term: None,
+                cost: 0.0,
+                useful: 0,
+                instances: Vec::new(),
+                vars: None,
+            });
+            self.events.push(ParseEvent::NewQuantifier(qidx));
+            qidx
+        });
+        // Quantifiers first seen in a `num-instantiations` do not know how
+        // many variables they have.
+        let quant = &mut self.quantifiers[qidx];
+        if quant.instances.is_empty() {
+            quant.num_vars = quant.num_vars.max(num_vars);
+        }
+        qidx
+    }
+
+    fn term(&mut self, sexpr: &SExpr, pos: LinePos) -> TermIdx {
+        let (name, child_ids) = match sexpr {
+            SExpr::Atom(name) => (*name, Box::default()),
+            SExpr::List(list) => match list.split_first() {
+                Some((SExpr::Atom(name), children)) => {
+                    let children = children.iter().map(|c| self.term(c, pos)).collect();
+                    (*name, children)
+                }
+                // E.g. the bound variable list `((x Int))` of a binder or an
+                // indexed function `((_ extract 7 0) x)`.
+                _ => ("", list.iter().map(|c| self.term(c, pos)).collect()),
+            },
+        };
+        let name = self.strings.get_or_intern(name);
+        let key = (name, child_ids);
+        if let Some(&tidx) = self.term_map.get(&key) {
+            return tidx;
+        }
+        let tidx = self.terms.push_and_get_key(Term {
+            id: TermId::default(),
+            pos,
+            kind: TermKind::parse_proof_app(false, name),
+            child_ids: key.1.clone(),
+        });
+        self.term_map.insert(key, tidx);
+        self.events.push(ParseEvent::NewTerm(tidx));
+        tidx
+    }
+}
+
+impl std::ops::Index<TermIdx> for Cvc5Parser {
+    type Output = Term;
+    fn index(&self, idx: TermIdx) -> &Self::Output {
+        &self.terms[idx]
+    }
+}
+impl std::ops::Index<QuantIdx> for Cvc5Parser {
+    type Output = Quantifier;
+    fn index(&self, idx: QuantIdx) -> &Self::Output {
+        &self.quantifiers[idx]
+    }
+}
+impl std::ops::Index<InstIdx> for Cvc5Parser {
+    type Output = Instantiation;
+    fn index(&self, idx: InstIdx) -> &Self::Output {
+        &self.insts[idx]
+    }
+}
+impl std::ops::Index<MatchIdx> for Cvc5Parser {
+    type Output = Match;
+    fn index(&self, idx: MatchIdx) -> &Self::Output {
+        &self.matches[idx]
+    }
+}
@@ -0,0 +1,43 @@
+use crate::items::LinePos;
+
+use super::diagnostics::DiagnosticKind;
+
+use super::{observer::ParseEvent, LogParser};
+
+/// Parser for the quantifier instantiation output of cvc5, i.e. the output of
+/// running with `--dump-instantiations` and/or `-o inst`.
+pub mod cvc5parser;
+mod sexpr;
+
+use cvc5parser::Cvc5Parser;
+
+impl LogParser for Cvc5Parser {
+    fn is_line_start(&mut self, first_byte: u8) -> bool {
+        // Entries such as `(instantiations ...)` span multiple lines, where
+        // the continuation lines are indented or close the entry.
+        !matches!(first_byte, b' ' | b'\t' | b')')
+    }
+
+    fn process_line(&mut self, line: &str, pos: LinePos) -> bool {
+        if line.trim().is_empty() {
+            return true;
+        }
+        let parse = self.entry(line, pos);
+        self.diagnostics.end_line(pos, line, parse);
+        true
+    }
+
+    fn end_of_file(&mut self) {}
+
+    fn read_error(&mut self, pos: LinePos, error: &std::io::Error) {
+        let result = Err(DiagnosticKind::ReadError);
+        self.diagnostics.end_line(pos, &error.to_string(), result);
+    }
+
+    fn record_events(&mut self, record: bool) {
+        self.events.set_recording(record);
+    }
+    fn take_events(&mut self, events: &mut Vec<ParseEvent>) {
+        self.events.take_into(events);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::parsers::diagnostics::DiagnosticKind;
+
+/// A parsed SMT-LIB s-expression, atoms borrow from the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SExpr<'a> {
+    Atom(&'a str),
+    List(Vec<SExpr<'a>>),
+}
+
+impl<'a> SExpr<'a> {
+    pub fn atom(&self) -> Option<&'a str> {
+        match self {
+            Self::Atom(atom) => Some(atom),
+            Self::List(_) => None,
+        }
+    }
+    pub fn list(&self) -> Option<&[SExpr<'a>]> {
+        match self {
+            Self::Atom(_) => None,
+            Self::List(list) => Some(list),
+        }
+    }
+
+    /// Parses a single s-expression spanning all of `s`.
+    pub fn parse(s: &'a str) -> Result<Self, DiagnosticKind> {
+        let mut tokens = Tokens { s: s.trim_start() };
+        let sexpr = tokens.sexpr()?;
+        if tokens.next().is_some() {
+            return Err(DiagnosticKind::MalformedLine);
+        }
+        Ok(sexpr)
+    }
+}
+
+/// Renders the s-expression on a single line with normalised whitespace.
+impl std::fmt::Display for SExpr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Atom(atom) => write!(f, "{atom}"),
+            Self::List(list) => {
+                write!(f, "(")?;
+                for (idx, sexpr) in list.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{sexpr}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Open,
+    Close,
+    Atom(&'a str),
+}
+
+struct Tokens<'a> {
+    s: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn sexpr(&mut self) -> Result<SExpr<'a>, DiagnosticKind> {
+        match self.next().ok_or(DiagnosticKind::MalformedLine)?? {
+            Token::Atom(atom) => Ok(SExpr::Atom(atom)),
+            Token::Close => Err(DiagnosticKind::MalformedLine),
+            Token::Open => {
+                let mut list = Vec::new();
+                loop {
+                    let rest = self.s;
+                    match self.next().ok_or(DiagnosticKind::MalformedLine)?? {
+                        Token::Close => return Ok(SExpr::List(list)),
+                        _ => {
+                            self.s = rest;
+                            list.push(self.sexpr()?);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Splits off the next `len` bytes as an atom.
+    fn atom(&mut self, len: usize) -> Token<'a> {
+        let (atom, rest) = self.s.split_at(len);
+        self.s = rest;
+        Token::Atom(atom)
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>, DiagnosticKind>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.s = self.s.trim_start();
+        let first = *self.s.as_bytes().first()?;
+        let token = match first {
+            b'(' | b')' => {
+                self.s = &self.s[1..];
+                if first == b'(' {
+                    Token::Open
+                } else {
+                    Token::Close
+                }
+            }
+            // Quoted symbols and string literals may contain whitespace and
+            // brackets.
+            b'|' | b'"' => {
+                let Some(end) = self.s[1..].find(first as char) else {
+                    return Some(Err(DiagnosticKind::MalformedLine));
+                };
+                self.atom(end + 2)
+            }
+            _ => {
+                let end = self
+                    .s
+                    .find(|c: char| c.is_ascii_whitespace() || matches!(c, '(' | ')' | '|' | '"'))
+                    .unwrap_or(self.s.len());
+                self.atom(end)
+            }
+        };
+        Some(Ok(token))
+    }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::items::LinePos;
+
+/// The kind of problem encountered while parsing a single line of a log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiagnosticKind {
+    /// The line starts with a tag (e.g. `[foo]`) which we do not understand.
+    UnknownTag,
+    /// The line ended early, contained unexpected trailing data or a value
+    /// (e.g. a number) which could not be parsed.
+    MalformedLine,
+    /// A term id (e.g. `#12`), fingerprint or similar could not be parsed.
+    MalformedId,
+    /// A term id was used before it was defined.
+    UnknownId,
+    /// A term was referenced which is not of the expected kind (e.g. a
+    /// quantifier was expected).
+    UnexpectedTerm,
+    /// A term was referenced as an enode but was never attached to the e-graph
+    /// (or its enode was popped off the stack).
+    MissingEnode,
+    /// An `[instance]` with no prior `[new-match]` or `[inst-discovered]`, or
+    /// an `[end-of-instance]` outside of an instance.
+    UnknownMatch,
+    /// Two terms which should be equal have different roots in the e-graph.
+    EqualityMismatch,
+    /// A `[push]` or `[pop]` which does not agree with the current stack.
+    StackMismatch,
+    /// Something which may only be given once was given again, e.g. the
+    /// variable names of a quantifier or the instance of a match.
+    Redefinition,
+    /// The input could not be read any further, e.g. a compressed log which
+    /// ends part way through. Everything before the last complete line was
+    /// still parsed.
+    ReadError,
+    /// A known bug in the logging of a specific solver version which we worked
+    /// around. These are not errors; the line was still processed.
+    VersionQuirk,
+}
+
+impl DiagnosticKind {
+    /// Is this an error (i.e. the line was not, or only partially, processed)
+    /// or just a warning?
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::VersionQuirk)
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::UnknownTag => "unknown line tag",
+            Self::MalformedLine => "malformed line",
+            Self::MalformedId => "malformed id",
+            Self::UnknownId => "unknown id",
+            Self::UnexpectedTerm => "unexpected kind of term",
+            Self::MissingEnode => "missing enode",
+            Self::UnknownMatch => "instance without a match",
+            Self::EqualityMismatch => "equality between different e-classes",
+            Self::StackMismatch => "stack mismatch",
+            Self::Redefinition => "redefinition",
+            Self::ReadError => "input could not be read",
+            Self::VersionQuirk => "solver version quirk",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+/// A problem encountered while parsing, along with where it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseDiagnostic {
+    pub kind: DiagnosticKind,
+    pub pos: LinePos,
+    /// The raw text of the line (without the trailing newline).
+    pub raw: String,
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = if self.kind.is_error() {
+            "Error"
+        } else {
+            "Warning"
+        };
+        write!(
+            f,
+            "{level} ({}) on {}: {:?}",
+            self.kind, self.pos, self.raw
+        )
+    }
+}
+
+/// All diagnostics collected so far by a parser.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Diagnostics {
+    list: Vec<ParseDiagnostic>,
+    /// Warnings raised while processing the current line, these are turned
+    /// into full diagnostics once the line is done.
+    #[serde(skip)]
+    pending: Vec<DiagnosticKind>,
+}
+
+impl Diagnostics {
+    /// Record a non-fatal problem with the line currently being processed.
+    pub(crate) fn warn(&mut self, kind: DiagnosticKind) {
+        self.pending.push(kind);
+    }
+    /// Called after the line at `pos` has been processed, records any warnings
+    /// raised during processing as well as the error in `result`.
+    pub(crate) fn end_line(&mut self, pos: LinePos, raw: &str, result: Result<(), DiagnosticKind>) {
+        let error = result.err();
+        for kind in self.pending.drain(..).chain(error) {
+            self.list.push(ParseDiagnostic {
+                kind,
+                pos,
+                raw: raw.to_string(),
+            });
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ParseDiagnostic> + '_ {
+        self.list.iter()
+    }
+    pub fn errors(&self) -> impl Iterator<Item = &ParseDiagnostic> + '_ {
+        self.iter().filter(|d| d.kind.is_error())
+    }
+    pub fn warnings(&self) -> impl Iterator<Item = &ParseDiagnostic> + '_ {
+        self.iter().filter(|d| !d.kind.is_error())
+    }
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
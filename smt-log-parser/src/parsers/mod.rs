@@ -1,13 +1,24 @@
 pub use self::wrapper_async_parser::*;
 pub use self::wrapper_stream_parser::*;
-use futures::{AsyncBufRead, AsyncBufReadExt, AsyncRead};
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncSeek, AsyncSeekExt};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt::Debug;
 use std::fs::{File, Metadata};
-use std::io::{BufRead, BufReader, Result};
+use std::io::{BufRead, BufReader, Result, Seek, SeekFrom};
 use std::path::Path;
+use std::rc::Rc;
 use std::time::Duration;
 use wasm_timer::Instant;
 
+use crate::items::LinePos;
+use observer::{ParseEvent, ParseObserver};
+
+pub mod cache;
+pub mod compression;
+pub mod cvc5;
+pub mod diagnostics;
+pub mod observer;
 pub mod z3;
 
 /// Trait for a generic SMT solver trace parser. Intended to support different
@@ -18,12 +29,23 @@ pub trait LogParser: Default {
         true
     }
 
-    /// Process a single line of the log file. Return `true` if parsing should
-    /// continue, or `false` if parsing should stop.
-    fn process_line(&mut self, line: &str, line_no: usize) -> bool;
+    /// Process a single line of the log file found at `pos`. Return `true` if
+    /// parsing should continue, or `false` if parsing should stop.
+    fn process_line(&mut self, line: &str, pos: LinePos) -> bool;
 
     fn end_of_file(&mut self);
 
+    /// Called when the input could not be read any further (e.g. a compressed
+    /// log which was cut off), just before [`end_of_file`](Self::end_of_file).
+    /// The incomplete line at `pos` is not processed.
+    fn read_error(&mut self, pos: LinePos, error: &std::io::Error);
+
+    /// Start or stop recording [`ParseEvent`]s, these are only needed if a
+    /// [`ParseObserver`] is attached.
+    fn record_events(&mut self, _record: bool) {}
+    /// Move any events recorded since the last call to the end of `events`.
+    fn take_events(&mut self, _events: &mut Vec<ParseEvent>) {}
+
     /// Creates a new parser. Only use this if you cannot use the following
     /// convenience methods:
     /// - [`new_file`] for creating a streaming parser from a file path
@@ -63,6 +85,18 @@ pub trait LogParser: Default {
         let (meta, reader) = p.read_open()?;
         Ok((meta, reader.into_parser()))
     }
+
+    /// Resumes parsing a file from a [`Checkpoint`] previously taken while
+    /// parsing the same file. The file is seeked to where the checkpoint was
+    /// taken, so nothing before that point is read again. Additionally
+    /// returns the file metadata, as with [`from_file`](Self::from_file).
+    fn resume_file<P: AsRef<Path>>(
+        p: P,
+        checkpoint: Checkpoint<Self>,
+    ) -> Result<(Metadata, StreamParser<'static, Self>)> {
+        let (meta, reader) = p.read_open()?;
+        Ok((meta, StreamParser::resume(reader, checkpoint)?))
+    }
 }
 
 ////////////////////
@@ -140,31 +174,71 @@ impl<T: AsRef<[u8]> + Unpin> AsyncCursorRead for T {}
 ////////////////////
 
 /// Progress information for a parser.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct ReaderState {
-    /// The number of bytes parsed so far.
+    /// The number of bytes parsed so far. For compressed logs this is the
+    /// number of decompressed bytes.
     pub bytes_read: usize,
-    /// The number of lines parsed so far.
+    /// The number of lines parsed so far. An entry continued over several
+    /// lines (see [`LogParser::is_line_start`]) counts as all of its lines, so
+    /// that this is the line number of the next entry.
     pub lines_read: usize,
+    /// The number of bytes of compressed input read so far, or `None` if the
+    /// log is not compressed (see [`Compression`](compression::Compression)).
+    pub compressed_bytes_read: Option<usize>,
+}
+
+impl ReaderState {
+    /// The number of bytes read from the underlying input, compare this with
+    /// the size of the file to calculate progress.
+    pub fn input_bytes_read(&self) -> usize {
+        self.compressed_bytes_read.unwrap_or(self.bytes_read)
+    }
+}
+
+/// A snapshot of a parser part way through a log, from which parsing can be
+/// resumed with [`StreamParser::resume`] or [`AsyncParser::resume`]. The
+/// `parser` is generic so that a checkpoint can be serialized from a borrowed
+/// parser (see [`StreamParser::checkpoint`]) without cloning it, and then
+/// deserialized into an owned one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint<Parser> {
+    /// How far into the log the parser had got. Resuming will seek to
+    /// `reader_state.bytes_read`.
+    pub reader_state: ReaderState,
+    /// Had the end of the log already been reached (and
+    /// [`LogParser::end_of_file`] called)?
+    pub done: bool,
+    pub parser: Parser,
 }
 
 #[duplicate::duplicate_item(
-    EitherParser   ReadBound                   async   add_await(code);
-    [StreamParser] [BufRead + 'r]              []      [code];
-    [AsyncParser]  [AsyncBufRead + Unpin + 'r] [async] [code.await];
+    EitherParser   ReadBound                   SeekBound           async   add_await(code);
+    [StreamParser] [BufRead + 'r]              [Seek]              []      [code];
+    [AsyncParser]  [AsyncBufRead + Unpin + 'r] [AsyncSeek + Unpin] [async] [code.await];
 )]
 mod wrapper {
     use super::*;
+    use compression::{Compression, Decompress};
 
     /// Struct which contains both a parser state as well as the stream of lines
     /// which are being parsed. Always use this instead of the raw underlying
     /// parser. Feeds the parser line-by-line with a callback to indicate if or
     /// when to pause. Supports any parser as long as it implements
-    /// [`LogParser`].
+    /// [`LogParser`]. Logs compressed with gzip or zstd are detected and
+    /// decompressed on the fly.
     pub struct EitherParser<'r, Parser: LogParser> {
         reader: Option<Box<dyn ReadBound>>,
+        /// Should we check the start of the input for compression before
+        /// reading the first line?
+        detect_compression: bool,
+        /// Shared with the [`Decompress`] reader if the input is compressed.
+        compressed_read: Option<Rc<Cell<usize>>>,
         reader_state: ReaderState,
         parser: Parser,
+        observers: Vec<Box<dyn ParseObserver<Parser> + 'r>>,
+        /// Buffer for events being delivered to the `observers`.
+        events: Vec<ParseEvent>,
     }
     impl<'r, Parser: LogParser, R: ReadBound> From<R> for EitherParser<'r, Parser> {
         fn from(reader: R) -> Self {
@@ -175,8 +249,86 @@ mod wrapper {
         pub(super) fn new(reader: impl ReadBound) -> Self {
             Self {
                 reader: Some(Box::new(reader)),
+                detect_compression: true,
+                compressed_read: None,
                 reader_state: ReaderState::default(),
                 parser: Parser::default(),
+                observers: Vec::new(),
+                events: Vec::new(),
+            }
+        }
+
+        /// Resume parsing from a checkpoint. The `reader` must read the same
+        /// log as the one the checkpoint was taken from, it is seeked to the
+        /// position at which the checkpoint was taken. Parsing of compressed
+        /// logs cannot be resumed.
+        pub async fn resume(
+            mut reader: impl ReadBound + SeekBound,
+            checkpoint: Checkpoint<Parser>,
+        ) -> Result<Self> {
+            let Checkpoint { reader_state, done, parser } = checkpoint;
+            if reader_state.compressed_bytes_read.is_some() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "cannot resume parsing a compressed log",
+                ));
+            }
+            let reader: Option<Box<dyn ReadBound>> = if done {
+                None
+            } else {
+                let offset = reader_state.bytes_read as u64;
+                add_await([reader.seek(SeekFrom::Start(offset))])?;
+                Some(Box::new(reader))
+            };
+            Ok(Self {
+                reader,
+                detect_compression: false,
+                compressed_read: None,
+                reader_state,
+                parser,
+                observers: Vec::new(),
+                events: Vec::new(),
+            })
+        }
+        /// Take a snapshot of the current state which can be serialized and
+        /// later passed to [`resume`](Self::resume). Should only be called
+        /// between calls to the `process_*` methods.
+        pub fn checkpoint(&self) -> Checkpoint<&Parser> {
+            Checkpoint {
+                reader_state: self.reader_state,
+                done: self.is_done(),
+                parser: &self.parser,
+            }
+        }
+        /// As [`checkpoint`](Self::checkpoint) but consumes the parser.
+        pub fn into_checkpoint(self) -> Checkpoint<Parser> {
+            Checkpoint {
+                reader_state: self.reader_state,
+                done: self.is_done(),
+                parser: self.parser,
+            }
+        }
+
+        /// Attach an observer which is notified of every [`ParseEvent`] from
+        /// now on. Events are delivered after the line which created them has
+        /// been processed.
+        pub fn add_observer(&mut self, observer: impl ParseObserver<Parser> + 'r) {
+            self.parser.record_events(true);
+            self.observers.push(Box::new(observer));
+        }
+        fn notify_observers(
+            parser: &mut Parser,
+            events: &mut Vec<ParseEvent>,
+            observers: &mut [Box<dyn ParseObserver<Parser> + 'r>],
+        ) {
+            if observers.is_empty() {
+                return;
+            }
+            parser.take_events(events);
+            for event in events.drain(..) {
+                for observer in observers.iter_mut() {
+                    observer.on_event(parser, event);
+                }
             }
         }
 
@@ -211,22 +363,59 @@ mod wrapper {
             &mut self,
             mut predicate: impl FnMut(&Parser, ReaderState) -> bool,
         ) -> Option<ReaderState> {
-            let Some(reader) = self.reader.as_mut() else {
-                return None;
-            };
+            if self.detect_compression {
+                self.detect_compression = false;
+                let mut reader = self.reader.take()?;
+                let magic = match add_await([reader.fill_buf()]) {
+                    Ok(magic) => &magic[..magic.len().min(Compression::MAGIC_LEN)],
+                    Err(err) => {
+                        self.parser.read_error(LinePos::default(), &err);
+                        self.finish();
+                        return None;
+                    }
+                };
+                if let Some(compression) = Compression::detect(magic) {
+                    let compressed_read = Rc::default();
+                    let decompress =
+                        Decompress::new(reader, compression, Rc::clone(&compressed_read));
+                    reader = Box::new(decompress);
+                    self.compressed_read = Some(compressed_read);
+                }
+                self.reader = Some(reader);
+            }
+            self.reader.as_ref()?;
             let mut buf = String::new();
             while predicate(&self.parser, self.reader_state) {
+                let reader = self.reader.as_mut().unwrap();
                 buf.clear();
                 // Read line
-                let mut bytes_read = 0;
-
+                let mut error = None;
                 loop {
-                    bytes_read += add_await([reader.read_line(&mut buf)]).unwrap();
-                    let peek = add_await([reader.fill_buf()]).unwrap();
-                    // Stop reading if this is the end or we don't have a multiline.
-                    if peek.is_empty() || self.parser.is_line_start(peek[0]) {
+                    if let Err(err) = add_await([reader.read_line(&mut buf)]) {
+                        error = Some(err);
                         break;
                     }
+                    match add_await([reader.fill_buf()]) {
+                        // Stop reading if this is the end or we don't have a multiline.
+                        Ok(peek) if peek.is_empty() || self.parser.is_line_start(peek[0]) => break,
+                        Ok(_) => (),
+                        Err(err) => {
+                            error = Some(err);
+                            break;
+                        }
+                    }
+                }
+                if error.is_some() {
+                    // Only keep complete lines, the rest may have been cut off
+                    // part way through.
+                    buf.truncate(buf.rfind('\n').map_or(0, |end| end + 1));
+                }
+                let bytes_read = buf.len();
+                // Count physical lines, the last line of the log may not end in
+                // a newline.
+                let mut lines = buf.bytes().filter(|&b| b == b'\n').count();
+                if !buf.is_empty() && !buf.ends_with('\n') {
+                    lines += 1;
                 }
                 // Remove newline from end
                 if buf.ends_with('\n') {
@@ -236,17 +425,44 @@ mod wrapper {
                     }
                 }
                 // Parse line
-                if bytes_read == 0 || !self.parser.process_line(&buf, self.reader_state.lines_read)
-                {
-                    self.parser.end_of_file();
-                    self.reader.take(); // Release file handle/free up memory
+                let pos = LinePos {
+                    line_no: self.reader_state.lines_read,
+                    byte_offset: self.reader_state.bytes_read,
+                };
+                let more = bytes_read != 0 && self.parser.process_line(&buf, pos);
+                Self::notify_observers(&mut self.parser, &mut self.events, &mut self.observers);
+                if let Some(err) = &error {
+                    self.update_reader_state(bytes_read, lines);
+                    let pos = LinePos {
+                        line_no: self.reader_state.lines_read,
+                        byte_offset: self.reader_state.bytes_read,
+                    };
+                    self.parser.read_error(pos, err);
+                }
+                if !more || error.is_some() {
+                    self.finish();
                     return None;
                 }
-                self.reader_state.bytes_read += bytes_read;
-                self.reader_state.lines_read += 1;
+                self.update_reader_state(bytes_read, lines);
             }
             Some(self.reader_state)
         }
+        fn update_reader_state(&mut self, bytes_read: usize, lines: usize) {
+            self.reader_state.bytes_read += bytes_read;
+            self.reader_state.lines_read += lines;
+            self.reader_state.compressed_bytes_read =
+                self.compressed_read.as_ref().map(|read| read.get());
+        }
+        /// Tell the parser and observers that the end of the input has been
+        /// reached.
+        fn finish(&mut self) {
+            self.parser.end_of_file();
+            Self::notify_observers(&mut self.parser, &mut self.events, &mut self.observers);
+            for observer in self.observers.iter_mut() {
+                observer.on_eof(&self.parser);
+            }
+            self.reader.take(); // Release file handle/free up memory
+        }
         /// Parse the the input while calling the `predicate` callback every
         /// `delta` time. Keep parsing until the callback returns `false` or we
         /// reach the end of the input. If we stopped due to the callback,
@@ -271,7 +487,7 @@ mod wrapper {
             let mut lines_per_check = initial_lines_per_check;
             // How many lines until the next time check?
             let mut next_check = lines_per_check;
-            let max_lpc = lines_per_check.checked_mul(2).unwrap_or(usize::MAX);
+            let max_lpc = lines_per_check.saturating_mul(2);
             let mut start = Instant::now();
             let mut last_check_time = start;
             add_await([self.process_until(move |p, rs| {
@@ -286,9 +502,8 @@ mod wrapper {
                             if check_delta < MAX_LINES_PER_TIME_VARIATION {
                                 lines_per_check = 1;
                             } else {
-                                let check_delta = check_delta
-                                    .checked_mul(MAX_LINES_PER_TIME_VARIATION)
-                                    .unwrap_or(u128::MAX);
+                                let check_delta =
+                                    check_delta.saturating_mul(MAX_LINES_PER_TIME_VARIATION);
                                 // How much smaller is `lines_per_check` than it
                                 // should be?
                                 let under_approx = (time_left / check_delta)
@@ -298,7 +513,7 @@ mod wrapper {
                                 // Do rounding up division to make sure
                                 // `over_approx > 1` as soon as `check_delta >
                                 // time_left`.
-                                let over_approx = (check_delta + time_left - 1) / time_left;
+                                let over_approx = check_delta.div_ceil(time_left);
                                 // How much larger is `lines_per_check` than it
                                 // should be?
                                 let over_approx =
@@ -337,7 +552,9 @@ mod wrapper {
         ///
         /// Parsing cannot be resumed if the timeout is reached. If you need
         /// support for resuming, use [`process_check_every`] or
-        /// [`process_until`] instead.
+        /// [`process_until`] instead, these also allow taking a
+        /// [`checkpoint`](Self::checkpoint) to later [`resume`](Self::resume)
+        /// from.
         pub async fn process_all_timeout(
             mut self,
             timeout: Duration,
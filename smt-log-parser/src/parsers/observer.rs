@@ -0,0 +1,73 @@
+use crate::items::{ENodeIdx, InstIdx, MatchIdx, QuantIdx, TermIdx};
+
+/// Something which was created (or happened) while parsing a log. The indices
+/// refer to items in the parser, which already contains the item when the
+/// event is delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseEvent {
+    NewTerm(TermIdx),
+    NewQuantifier(QuantIdx),
+    NewENode(ENodeIdx),
+    /// An equality between two enodes was explained (`[eq-expl]`).
+    NewEquality {
+        from: ENodeIdx,
+        to: ENodeIdx,
+    },
+    NewMatch(MatchIdx),
+    /// The start of an instantiation, any terms and enodes created until the
+    /// corresponding [`InstEnd`](Self::InstEnd) were created by it.
+    InstStart(InstIdx),
+    InstEnd(InstIdx),
+    /// A new stack frame at the given scope.
+    Push {
+        scope: usize,
+    },
+    /// `num` stack frames were popped from the given scope.
+    Pop {
+        num: usize,
+        scope: usize,
+    },
+}
+
+/// Receives [`ParseEvent`]s while a log is being parsed, attach with
+/// [`StreamParser::add_observer`](super::StreamParser::add_observer) or
+/// [`AsyncParser::add_observer`](super::AsyncParser::add_observer). Any
+/// closure taking the parser and an event is an observer.
+pub trait ParseObserver<Parser> {
+    /// Called for each event after the line which caused it was processed.
+    fn on_event(&mut self, parser: &Parser, event: ParseEvent);
+    /// Called once the end of the log has been reached.
+    fn on_eof(&mut self, _parser: &Parser) {}
+}
+
+impl<Parser, F: FnMut(&Parser, ParseEvent)> ParseObserver<Parser> for F {
+    fn on_event(&mut self, parser: &Parser, event: ParseEvent) {
+        self(parser, event)
+    }
+}
+
+/// The events created by a parser which have not yet been delivered to
+/// observers. Events are only recorded if there are any observers.
+#[derive(Debug, Default)]
+pub struct EventQueue {
+    events: Option<Vec<ParseEvent>>,
+}
+
+impl EventQueue {
+    pub(crate) fn push(&mut self, event: ParseEvent) {
+        if let Some(events) = &mut self.events {
+            events.push(event);
+        }
+    }
+    pub(crate) fn set_recording(&mut self, record: bool) {
+        if record != self.events.is_some() {
+            self.events = record.then(Vec::new);
+        }
+    }
+    /// Moves all recorded events to the end of `events`.
+    pub(crate) fn take_into(&mut self, events: &mut Vec<ParseEvent>) {
+        if let Some(recorded) = &mut self.events {
+            events.append(recorded);
+        }
+    }
+}
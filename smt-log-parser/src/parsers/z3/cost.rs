@@ -0,0 +1,223 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use typed_index_collections::TiVec;
+
+use crate::items::{InstIdx, Instantiation, QuantIdx};
+
+use super::{inst_graph::blamed_creators, z3parser::Z3Parser};
+
+/// A way of assigning a cost to each instantiation (and quantifier), used to
+/// find the instantiations responsible for the most work. Applied with
+/// [`Z3Parser::compute_costs`] or [`InstGraph::set_costs`](super::inst_graph::InstGraph::set_costs).
+pub trait CostModel {
+    fn costs(&self, parser: &Z3Parser) -> Costs;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Costs {
+    pub insts: TiVec<InstIdx, f32>,
+    pub quants: TiVec<QuantIdx, f32>,
+}
+
+/// Each instantiation costs 1 and passes its total cost on to the
+/// instantiations it depends on, split evenly between them. A quantifier costs
+/// the total passed on by its instances.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvenSplit;
+
+/// As [`EvenSplit`], but each instantiation costs the number of enodes it
+/// yielded rather than 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YieldWeighted;
+
+/// As [`EvenSplit`], but each instantiation costs one more than its Z3
+/// generation rather than 1, so that deep chains of instantiations stand out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenerationWeighted;
+
+/// The cost of an instantiation is the number of instantiations which
+/// transitively depend on it (including itself), each counted once however
+/// many paths lead to it. A quantifier costs the number of instantiations which
+/// depend on any of its instances. Unlike the other models the costs of all
+/// instantiations do not add up to the number of instantiations.
+///
+/// Descendants are counted with a search from each instantiation (and
+/// quantifier), which takes memory linear in the number of instantiations.
+/// To bound the time taken, counts stop at [`MAX_DESCENDANTS`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Descendants;
+
+/// The highest cost given by [`Descendants`], instantiations and quantifiers
+/// with more descendants all cost this much.
+pub const MAX_DESCENDANTS: usize = 10_000;
+
+impl CostModel for EvenSplit {
+    fn costs(&self, parser: &Z3Parser) -> Costs {
+        split_among_parents(parser, |_| 1.0)
+    }
+}
+
+impl CostModel for YieldWeighted {
+    fn costs(&self, parser: &Z3Parser) -> Costs {
+        split_among_parents(parser, |inst| inst.yields_terms.len() as f32)
+    }
+}
+
+impl CostModel for GenerationWeighted {
+    fn costs(&self, parser: &Z3Parser) -> Costs {
+        split_among_parents(parser, |inst| {
+            inst.z3_generation.map(|gen| gen as f32).unwrap_or_default() + 1.0
+        })
+    }
+}
+
+impl CostModel for Descendants {
+    fn costs(&self, parser: &Z3Parser) -> Costs {
+        let (quant_count, _) = parser.quant_count_incl_theory_solving();
+        let mut children = vec![Vec::new(); parser.inst_count()];
+        let mut instances = vec![Vec::new(); quant_count];
+        for idx in (0..parser.inst_count()).map(InstIdx::from) {
+            let mut parents: Vec<_> = parents(parser, idx).collect();
+            parents.sort_unstable();
+            parents.dedup();
+            for parent in parents {
+                children[usize::from(parent)].push(idx);
+            }
+            if let Some(qidx) = parser[parser[idx].match_].kind.quant_idx() {
+                instances[usize::from(qidx)].push(idx);
+            }
+        }
+        let mut search = DescendantSearch {
+            children,
+            seen: vec![usize::MAX; parser.inst_count()],
+            stack: Vec::new(),
+        };
+        let insts = (0..parser.inst_count())
+            .map(|idx| search.count(idx, [InstIdx::from(idx)]) as f32)
+            .collect();
+        let quants = instances
+            .into_iter()
+            .enumerate()
+            .map(|(idx, instances)| search.count(parser.inst_count() + idx, instances) as f32)
+            .collect();
+        Costs { insts, quants }
+    }
+}
+
+/// A depth-first search through the instantiation graph, see [`Descendants`].
+struct DescendantSearch {
+    children: Vec<Vec<InstIdx>>,
+    /// The last search which reached each instantiation, so that it need not
+    /// be cleared between searches.
+    seen: Vec<usize>,
+    stack: Vec<InstIdx>,
+}
+
+impl DescendantSearch {
+    /// The number of instantiations reachable from `roots`, up to
+    /// [`MAX_DESCENDANTS`]. Each search needs a different `id`.
+    fn count(&mut self, id: usize, roots: impl IntoIterator<Item = InstIdx>) -> usize {
+        self.stack.clear();
+        for root in roots {
+            if std::mem::replace(&mut self.seen[usize::from(root)], id) != id {
+                self.stack.push(root);
+            }
+        }
+        let mut count = 0;
+        while let Some(inst) = self.stack.pop() {
+            count += 1;
+            if count == MAX_DESCENDANTS {
+                break;
+            }
+            for &child in &self.children[usize::from(inst)] {
+                if std::mem::replace(&mut self.seen[usize::from(child)], id) != id {
+                    self.stack.push(child);
+                }
+            }
+        }
+        count
+    }
+}
+
+/// See [`blamed_creators`]. Only earlier instantiations are considered, a
+/// blame on a later one would make a cycle.
+fn parents(parser: &Z3Parser, iidx: InstIdx) -> impl Iterator<Item = InstIdx> + '_ {
+    blamed_creators(parser, &parser[parser[iidx].match_])
+        .into_iter()
+        .map(|(_, _, creator)| creator)
+        .filter(move |&creator| creator < iidx)
+}
+
+/// Give each instantiation an `own` cost and pass the total on to its parents,
+/// last instantiation first.
+fn split_among_parents(parser: &Z3Parser, own: impl Fn(&Instantiation) -> f32) -> Costs {
+    let (quant_count, _) = parser.quant_count_incl_theory_solving();
+    let mut insts: TiVec<InstIdx, f32> = (0..parser.inst_count())
+        .map(|idx| own(&parser[InstIdx::from(idx)]))
+        .collect();
+    let mut quants: TiVec<QuantIdx, f32> = vec![0.0; quant_count].into();
+    for idx in (0..parser.inst_count()).rev().map(InstIdx::from) {
+        let deps: Vec<_> = parents(parser, idx).collect();
+        let cost = insts[idx] / deps.len() as f32;
+        for blamed in deps {
+            insts[blamed] += cost;
+            if let Some(qidx) = parser[parser[idx].match_].kind.quant_idx() {
+                quants[qidx] += cost;
+            }
+        }
+    }
+    Costs { insts, quants }
+}
+
+/// The built-in cost models, e.g. to choose one in a UI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostModelKind {
+    #[default]
+    EvenSplit,
+    Yields,
+    Generation,
+    Descendants,
+}
+
+impl CostModelKind {
+    pub const ALL: [Self; 4] = [Self::EvenSplit, Self::Yields, Self::Generation, Self::Descendants];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::EvenSplit => "even-split",
+            Self::Yields => "yields",
+            Self::Generation => "generation",
+            Self::Descendants => "descendants",
+        }
+    }
+}
+
+impl CostModel for CostModelKind {
+    fn costs(&self, parser: &Z3Parser) -> Costs {
+        match self {
+            Self::EvenSplit => EvenSplit.costs(parser),
+            Self::Yields => YieldWeighted.costs(parser),
+            Self::Generation => GenerationWeighted.costs(parser),
+            Self::Descendants => Descendants.costs(parser),
+        }
+    }
+}
+
+impl fmt::Display for CostModelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for CostModelKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|kind| kind.name() == s).ok_or_else(|| {
+            let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+            format!("unknown cost model `{s}`, expected one of {}", names.join(", "))
+        })
+    }
+}
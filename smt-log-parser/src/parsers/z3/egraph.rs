@@ -1,14 +1,19 @@
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
 use typed_index_collections::TiVec;
 
-use crate::items::{ENodeIdx, EqualityExpl, InstIdx, StackIdx, TermIdx, BlameKind};
+use crate::items::{BlameKind, ENodeIdx, EqualityDependency, EqualityExpl, InstIdx, LinePos, StackIdx, TermIdx};
+use crate::parsers::diagnostics::DiagnosticKind;
 
 use super::stack::Stack;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct EGraph {
     term_to_enode: FxHashMap<TermIdx, ENodeIdx>,
     enodes: TiVec<ENodeIdx, ENode>,
+    /// The enodes of `(= a b)` terms, keyed by the enodes of `a` and `b` (in
+    /// ascending order).
+    equality_terms: FxHashMap<(ENodeIdx, ENodeIdx), ENodeIdx>,
 }
 
 impl EGraph {
@@ -17,6 +22,7 @@ impl EGraph {
         created_by: Option<InstIdx>,
         term: TermIdx,
         z3_generation: Option<u32>,
+        pos: LinePos,
         stack: &Stack,
     ) -> ENodeIdx {
         // TODO: why does this happen sometimes?
@@ -29,6 +35,7 @@ impl EGraph {
         // }
         let enode = self.enodes.push_and_get_key(ENode {
             frame: stack.active_frame(),
+            pos,
             created_by,
             owner: term,
             z3_generation,
@@ -43,26 +50,45 @@ impl EGraph {
         enode
     }
 
+    /// Record that `eq` is the enode of the equality term `lhs = rhs`.
+    pub fn new_equality_term(&mut self, eq: ENodeIdx, lhs: ENodeIdx, rhs: ENodeIdx) {
+        self.equality_terms.insert((lhs.min(rhs), lhs.max(rhs)), eq);
+    }
+
+    /// The enode of the equality term `lhs = rhs` (or `rhs = lhs`), if there
+    /// is one which is still the current enode of its term.
+    pub fn equality_term(&self, lhs: ENodeIdx, rhs: ENodeIdx, stack: &Stack) -> Option<ENodeIdx> {
+        let eq = *self.equality_terms.get(&(lhs.min(rhs), lhs.max(rhs)))?;
+        // The enode is gone if its stack frame was popped.
+        let current = self.get_enode(self.enodes[eq].owner, stack);
+        (current == Some(eq)).then_some(eq)
+    }
+
     pub fn get_enode(&self, term: TermIdx, stack: &Stack) -> Option<ENodeIdx> {
         let enode = *self.term_to_enode.get(&term)?;
         let frame = self.enodes[enode].frame;
         // This cannot be an enode if it points to a popped stack frame
-        if frame.is_some_and(|f| !stack.stack_frames[f].active) {
+        if !stack.is_active(frame) {
             None
         } else {
             Some(enode)
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (ENodeIdx, &ENode)> + '_ {
+        self.enodes.iter_enumerated()
+    }
+
     pub fn get_owner(&self, enode: ENodeIdx) -> TermIdx {
         self.enodes[enode].owner
     }
 
-    pub fn new_equality(&mut self, from: ENodeIdx, expl: EqualityExpl, stack: &Stack) {
+    pub fn new_equality(&mut self, from: ENodeIdx, expl: EqualityExpl, pos: LinePos, stack: &Stack) {
         let enode = &mut self.enodes[from];
         let to = expl.to();
         let eq = Equality {
-            _frame: stack.active_frame(),
+            frame: stack.active_frame(),
+            pos,
             to,
             expl,
         };
@@ -89,29 +115,78 @@ impl EGraph {
         // }
     }
 
-    pub fn path_to_root(&self, from: ENodeIdx, stack: &Stack, depth: usize) -> Vec<ENodeIdx> {
-        if let Some(eq) = &self.enodes[from].get_equality(stack) {
-            if eq.to != from {
-                let mut path = self.path_to_root(eq.to, stack, depth + 1);
-                path.push(from);
-                return path;
+    /// The path from the root of the e-class of `from` to `from`, following
+    /// the equalities which are current in the `view`.
+    fn path_to_root(&self, from: ENodeIdx, view: View) -> Vec<ENodeIdx> {
+        let mut path = vec![from];
+        // Mixing equalities from different frames could form a cycle, the
+        // other views only follow frames which were on the stack together.
+        let mut seen = matches!(view, View::Latest).then(|| FxHashSet::from_iter([from]));
+        let mut current = from;
+        while let Some(eq) = view.equality(&self.enodes[current]) {
+            let cycle = match &mut seen {
+                Some(seen) => !seen.insert(eq.to),
+                // Still make sure to stop should Z3 log a cycle.
+                None => path.len() > self.enodes.len(),
+            };
+            if eq.to == current || cycle {
+                break;
             }
+            path.push(eq.to);
+            current = eq.to;
         }
-        let mut path = Vec::with_capacity(depth + 1);
-        path.push(from);
+        path.reverse();
         path
     }
 
-    #[must_use]
-    pub fn get_equalities<'a: 'b, 'b>(&'a self, from: ENodeIdx, to: ENodeIdx, stack: &'b Stack, can_mismatch: impl Fn() -> bool) -> Option<impl Iterator<Item = &'a EqualityExpl> + 'b> {
-        let f_path = self.path_to_root(from, stack, 0);
-        let t_path = self.path_to_root(to, stack, 0);
+    /// The root of the e-class of `enode` just after the line at `pos` was
+    /// parsed, ignoring equalities from stack frames which were popped by then.
+    pub fn root_at(&self, enode: ENodeIdx, stack: &Stack, pos: LinePos) -> ENodeIdx {
+        self.path_to_root(enode, View::At(stack, pos))[0]
+    }
+
+    /// The enodes in the e-class of `enode` just after the line at `pos` was
+    /// parsed, see [`root_at`](Self::root_at).
+    pub fn eclass_at(&self, enode: ENodeIdx, stack: &Stack, pos: LinePos) -> Vec<ENodeIdx> {
+        let view = View::At(stack, pos);
+        // The enodes whose equality points directly at each enode.
+        let mut children: FxHashMap<ENodeIdx, Vec<ENodeIdx>> = FxHashMap::default();
+        for (idx, e) in self.enodes.iter_enumerated() {
+            if e.pos > pos || !stack.is_active_at(e.frame, pos) {
+                continue;
+            }
+            if let Some(eq) = view.equality(e).filter(|eq| eq.to != idx) {
+                children.entry(eq.to).or_default().push(idx);
+            }
+        }
+        let mut eclass = vec![self.root_at(enode, stack, pos)];
+        let mut next = 0;
+        while let Some(&idx) = eclass.get(next) {
+            next += 1;
+            eclass.extend(children.remove(&idx).into_iter().flatten());
+        }
+        eclass.sort_unstable();
+        eclass.dedup();
+        eclass
+    }
+
+    pub fn get_equalities<'a: 'b, 'b>(&'a self, from: ENodeIdx, to: ENodeIdx, stack: &'b Stack, can_mismatch: impl Fn() -> bool) -> Result<impl Iterator<Item = &'a EqualityExpl> + 'b, DiagnosticKind> {
+        let mut view = View::Active(stack);
+        let mut f_path = self.path_to_root(from, view);
+        let mut t_path = self.path_to_root(to, view);
+        if f_path[0] != t_path[0] {
+            // Z3 does not always repeat an `[eq-expl]` after the frame it was
+            // logged in is popped, so fall back to the latest explanations.
+            view = View::Latest;
+            f_path = self.path_to_root(from, view);
+            t_path = self.path_to_root(to, view);
+        }
         let mut shared = 1;
         if f_path[0] != t_path[0] {
             // Root may not always be the same from v4.12.3 onwards if `to` is an `ite` expression. See:
             // https://github.com/Z3Prover/z3/commit/faf14012ba18d21c1fcddbdc321ac127f019fa03#diff-0a9ec50ded668e51578edc67ecfe32380336b9cbf12c5d297e2d3759a7a39847R2417-R2419
             if !can_mismatch() {
-                return None;
+                return Err(DiagnosticKind::EqualityMismatch);
             }
             // Return an empty iterator if the roots are different.
             shared = f_path.len().max(t_path.len());
@@ -120,30 +195,37 @@ impl EGraph {
             shared += 1;
         }
         let all = f_path.into_iter().skip(shared).rev().chain(t_path.into_iter().skip(shared));
-        Some(all.map(|idx| &self.enodes[idx].get_equality(stack).unwrap().expl))
+        Ok(all.map(move |idx| &view.equality(&self.enodes[idx]).unwrap().expl))
     }
 
-    #[must_use]
-    pub fn blame_equalities(&self, from: ENodeIdx, to: ENodeIdx, stack: &Stack, blamed: &mut Vec<BlameKind>, can_mismatch: impl Fn() -> bool) -> Option<()> {
-        for eq in self.get_equalities(from, to, stack, can_mismatch)? {
-            // TODO: figure out if this is all the blames we need.
-            match eq {
-                EqualityExpl::Root { .. } => unreachable!(),
-                &EqualityExpl::Literal { eq, .. } => {
-                    blamed.push(BlameKind::Equality { eq });
-                }
-                EqualityExpl::Congruence { arg_eqs, .. } => {
-                    for (from, to) in arg_eqs.iter() {
+    /// Blame the equalities which explain `from = to`. If the e-graph cannot
+    /// explain it (the two are in different e-classes) then a
+    /// [`BlameKind::UnknownEquality`] is blamed instead. The same goes for
+    /// each theory or axiom step for which no equality term was known.
+    pub fn blame_equalities(&self, from: ENodeIdx, to: ENodeIdx, stack: &Stack, blamed: &mut Vec<BlameKind>, can_mismatch: impl Fn() -> bool) {
+        let Ok(equalities) = self.get_equalities(from, to, stack, can_mismatch) else {
+            blamed.push(BlameKind::UnknownEquality { from, to, proof: None });
+            return;
+        };
+        for eq in equalities {
+            debug_assert!(!matches!(eq, EqualityExpl::Root { .. }));
+            if let EqualityExpl::Theory { from, eq: None, to, .. }
+            | EqualityExpl::Axiom { from, eq: None, to }
+            | EqualityExpl::Unknown { from, to, .. } = *eq
+            {
+                blamed.push(BlameKind::UnknownEquality { from, to, proof: None });
+                continue;
+            }
+            for dependency in eq.dependency_on() {
+                match dependency {
+                    EqualityDependency::Term(eq) => blamed.push(BlameKind::Equality { eq }),
+                    EqualityDependency::Equality { from, to } => {
                         fn cannot_mismatch() -> bool { false }
-                        self.blame_equalities(*from, *to, stack, blamed, cannot_mismatch)?;
+                        self.blame_equalities(from, to, stack, blamed, cannot_mismatch);
                     }
                 }
-                EqualityExpl::Theory { .. } => (),
-                EqualityExpl::Axiom { .. } => (),
-                EqualityExpl::Unknown { .. } => (),
             }
         }
-        Some(())
     }
 }
 
@@ -154,9 +236,11 @@ impl std::ops::Index<ENodeIdx> for EGraph {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ENode {
     frame: Option<StackIdx>,
+    /// The `[attach-enode]` line which created this enode.
+    pub pos: LinePos,
     pub created_by: Option<InstIdx>,
     pub owner: TermIdx,
     pub z3_generation: Option<u32>,
@@ -165,16 +249,53 @@ pub struct ENode {
 }
 
 impl ENode {
-    pub fn get_equality(&self, _stack: &Stack) -> Option<&Equality> {
-        // TODO: why are we allowed to use equalities from popped stack frames?
-        // self.equalities.iter().rev().find(|eq| eq.frame.map(|f| stack.stack_frames[f].active).unwrap_or(true))
-        self.equalities.last()
+    /// The latest equality explained in a stack frame which is still active.
+    pub fn get_equality(&self, stack: &Stack) -> Option<&Equality> {
+        self.equalities.iter().rev().find(|eq| stack.is_active(eq.frame))
+    }
+    /// The latest equality explained up to the line at `pos`, in a stack frame
+    /// which was still active then.
+    pub fn equality_at(&self, stack: &Stack, pos: LinePos) -> Option<&Equality> {
+        self.equalities
+            .iter()
+            .rev()
+            .find(|eq| eq.pos <= pos && stack.is_active_at(eq.frame, pos))
+    }
+    /// All equalities explained for this enode, in the order of their
+    /// `[eq-expl]` lines. Which one is current depends on the stack, see
+    /// [`get_equality`](Self::get_equality).
+    pub fn equalities(&self) -> &[Equality] {
+        &self.equalities
+    }
+}
+
+/// Which of the equalities of an enode are considered current.
+#[derive(Clone, Copy)]
+enum View<'a> {
+    /// See [`ENode::get_equality`].
+    Active(&'a Stack),
+    /// The latest equality, regardless of stack frames.
+    Latest,
+    /// See [`ENode::equality_at`].
+    At(&'a Stack, LinePos),
+}
+
+impl View<'_> {
+    fn equality(self, enode: &ENode) -> Option<&Equality> {
+        match self {
+            View::Active(stack) => enode.get_equality(stack),
+            View::Latest => enode.equalities.last(),
+            View::At(stack, pos) => enode.equality_at(stack, pos),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Equality {
-    _frame: Option<StackIdx>,
+    /// The stack frame active when this equality was explained.
+    pub frame: Option<StackIdx>,
+    /// The `[eq-expl]` line which explained this equality.
+    pub pos: LinePos,
     pub to: ENodeIdx,
     pub expl: EqualityExpl,
 }
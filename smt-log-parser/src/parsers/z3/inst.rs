@@ -1,13 +1,18 @@
 use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
 use typed_index_collections::TiVec;
 
 use crate::items::{Fingerprint, InstIdx, Instantiation, Match, MatchIdx};
+use crate::parsers::diagnostics::DiagnosticKind;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Insts {
     // `theory-solving` fingerprints are always 0, others rarely repeat.
-    fingerprint_to_match: FxHashMap<Fingerprint, (MatchIdx, Option<InstIdx>)>,
+    fingerprint_to_match: FxHashMap<Fingerprint, MatchIdx>,
     pub(super) matches: TiVec<MatchIdx, Match>,
+    /// The instance of each match, `None` for pending matches which were never
+    /// instantiated.
+    match_insts: TiVec<MatchIdx, Option<InstIdx>>,
     pub(super) insts: TiVec<InstIdx, Instantiation>,
 
     has_theory_solving_inst: bool,
@@ -17,21 +22,34 @@ impl Insts {
     pub fn new_match(&mut self, fingerprint: Fingerprint, match_: Match) -> MatchIdx {
         self.has_theory_solving_inst |= match_.kind.quant_idx().is_none();
         let idx = self.matches.push_and_get_key(match_);
-        // Can remove a duplicate fingerprint if that one was never instantiated.
-        self.fingerprint_to_match.insert(fingerprint, (idx, None));
+        self.match_insts.push(None);
+        // An `[instance]` refers to the latest match with its fingerprint, any
+        // earlier one which was never instantiated stays pending.
+        self.fingerprint_to_match.insert(fingerprint, idx);
         idx
     }
 
-    pub fn new_inst(&mut self, fingerprint: Fingerprint, mut inst: Instantiation) -> InstIdx {
-        let (match_idx, inst_idx) = self
+    pub fn new_inst(
+        &mut self,
+        fingerprint: Fingerprint,
+        mut inst: Instantiation,
+    ) -> Result<InstIdx, DiagnosticKind> {
+        let match_idx = *self
             .fingerprint_to_match
-            .get_mut(&fingerprint)
-            .expect(&format!("{:x}", fingerprint.0));
-        inst.match_ = *match_idx;
+            .get(&fingerprint)
+            .ok_or(DiagnosticKind::UnknownMatch)?;
+        if self.match_insts[match_idx].is_some() {
+            return Err(DiagnosticKind::Redefinition);
+        }
+        inst.match_ = match_idx;
         let idx = self.insts.push_and_get_key(inst);
-        debug_assert!(inst_idx.is_none(), "duplicate fingerprint");
-        *inst_idx = Some(idx);
-        idx
+        self.match_insts[match_idx] = Some(idx);
+        Ok(idx)
+    }
+
+    /// The instance of the match `idx`, `None` if it was never instantiated.
+    pub fn instance_of(&self, idx: MatchIdx) -> Option<InstIdx> {
+        self.match_insts[idx]
     }
 
     pub fn has_theory_solving_inst(&self) -> bool {
@@ -1,9 +1,7 @@
-use fxhash::{FxHashSet, FxHashMap};
-use gloo_console::log;
-use itertools::Itertools;
+use fxhash::FxHashMap;
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::StableGraph;
-use petgraph::visit::{Bfs, IntoEdgeReferences, Topo, IntoEdges};
+use petgraph::visit::Topo;
 use petgraph::{
     stable_graph::EdgeIndex,
     visit::{Dfs, EdgeRef},
@@ -11,15 +9,21 @@ use petgraph::{
 };
 use petgraph::{Direction, Graph};
 use roaring::bitmap::RoaringBitmap;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 use typed_index_collections::TiVec;
 
 use crate::display_with::{DisplayCtxt, DisplayWithCtxt};
-use crate::items::{BlameKind, ENodeIdx, Fingerprint, InstIdx, MatchKind};
-
-use super::z3parser::Z3Parser;
+use crate::items::{
+    BlameKind, ENodeIdx, Fingerprint, InstIdx, LinePos, Match, MatchIdx, MatchKind, QueryIdx,
+};
 
-const MIN_MATCHING_LOOP_LENGTH: usize = 3;
+use super::{
+    cost::CostModel,
+    reachability::{IntervalLabels, ReachabilityIndex},
+    z3parser::Z3Parser,
+};
 
 #[derive(Clone)]
 pub struct NodeData {
@@ -28,15 +32,27 @@ pub struct NodeData {
     cost: f32,
     pub inst_idx: InstIdx,
     pub mkind: MatchKind,
-    visible: bool,
+    /// The query during which this instantiation was made.
+    pub query: Option<QueryIdx>,
     child_count: usize,
     parent_count: usize,
     pub orig_graph_idx: NodeIndex,
-    cost_rank: usize,
-    branching_rank: usize,
     pub min_depth: Option<usize>,
     max_depth: usize,
-    topo_ord: usize,
+}
+
+impl NodeData {
+    pub fn cost(&self) -> f32 {
+        self.cost
+    }
+    /// The number of instantiations which directly depend on this one.
+    pub fn child_count(&self) -> usize {
+        self.child_count
+    }
+    /// The length of the longest dependency chain ending in this instantiation.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
 }
 
 impl fmt::Debug for NodeData {
@@ -111,9 +127,16 @@ impl fmt::Debug for EdgeType {
 pub struct InstInfo {
     pub fingerprint: Fingerprint,
     pub inst_idx: InstIdx,
+    /// Position of the `[instance]` line in the log.
+    pub inst_pos: LinePos,
+    /// The query during which this instantiation was made.
+    pub query: Option<QueryIdx>,
+    /// Position of the `[new-match]` or `[inst-discovered]` line in the log.
+    pub match_pos: LinePos,
     pub resulting_term: Option<String>,
     pub z3_gen: Option<u32>,
     pub cost: f32,
+    pub useful: u32,
     pub mkind: MatchKind,
     pub quant_discovered: bool,
     pub formula: String,
@@ -134,12 +157,27 @@ impl PartialEq for InstInfo {
 
 #[derive(Default, Clone)]
 pub struct InstGraph {
-    orig_graph: Graph<NodeData, BlameKind>,
+    pub(super) orig_graph: Graph<NodeData, BlameKind>,
     pub visible_graph: Graph<NodeData, EdgeType>,
     node_of_inst_idx: TiVec<InstIdx, NodeIndex>,
     cost_ranked_node_indices: Vec<NodeIndex>,
     branching_ranked_node_indices: Vec<NodeIndex>,
-    tr_closure: Vec<RoaringBitmap>,
+    reachability: IntervalLabels,
+    ghosts: Vec<GhostNode>,
+    /// The nodes of the original graph shown in the visible graph, see
+    /// [`set_visible_nodes`](Self::set_visible_nodes).
+    visible: RoaringBitmap,
+}
+
+/// A match which was never instantiated, shown next to the instantiations it
+/// would have depended on to spot near misses. See
+/// [`InstGraph::add_pending_matches`].
+#[derive(Debug, Clone)]
+struct GhostNode {
+    match_idx: MatchIdx,
+    /// The instantiations which created the terms it was triggered by, as
+    /// nodes of the original graph.
+    parents: Vec<NodeIndex>,
 }
 
 enum InstOrder {
@@ -157,24 +195,82 @@ pub struct VisibleGraphInfo {
 impl InstGraph {
     pub fn from(parser: &Z3Parser) -> Self {
         let mut inst_graph = Self::default();
-        inst_graph.compute_instantiation_graph(parser);
+        inst_graph.extend(parser);
         inst_graph
     }
 
-    pub fn retain_nodes(&mut self, retain: impl Fn(&NodeData) -> bool) {
-        for node in self.orig_graph.node_weights_mut() {
-            if !retain(node) {
-                node.visible = false;
-            }
-        }
+    pub fn node_data(&self, inst: InstIdx) -> &NodeData {
+        &self.orig_graph[self.node_of_inst_idx[inst]]
+    }
+
+    /// The number of instantiations which transitively depend on `inst`,
+    /// including `inst` itself.
+    pub fn subgraph_size(&self, inst: InstIdx) -> usize {
+        let mut dfs = Dfs::new(&self.orig_graph, self.node_of_inst_idx[inst]);
+        std::iter::from_fn(|| dfs.next(&self.orig_graph)).count()
+    }
+
+    /// Whether `to` transitively depends on `from`, or they are the same.
+    pub fn reaches(&self, from: InstIdx, to: InstIdx) -> bool {
+        let (from, to) = (self.node_of_inst_idx[from], self.node_of_inst_idx[to]);
+        self.tr_closure_contains_edge(from, to)
+    }
+
+    /// The nodes shown by the next
+    /// [`retain_visible_nodes_and_reconnect`](Self::retain_visible_nodes_and_reconnect),
+    /// as indices into the original graph.
+    pub fn visible_nodes(&self) -> &RoaringBitmap {
+        &self.visible
+    }
+
+    /// Show exactly `nodes` from the next
+    /// [`retain_visible_nodes_and_reconnect`](Self::retain_visible_nodes_and_reconnect)
+    /// on. Filters are computed as sets of nodes, e.g. with
+    /// [`nodes_where`](Self::nodes_where) or
+    /// [`descendants`](Self::descendants), and combined with the set
+    /// operations of [`RoaringBitmap`] before being set here.
+    pub fn set_visible_nodes(&mut self, nodes: RoaringBitmap) {
+        self.visible = nodes;
+    }
+
+    /// All nodes of the original graph.
+    pub fn all_nodes(&self) -> RoaringBitmap {
+        RoaringBitmap::from_sorted_iter(0..self.orig_graph.node_count() as u32).unwrap()
+    }
+
+    /// The nodes for which `pred` holds.
+    pub fn nodes_where(&self, pred: impl Fn(&NodeData) -> bool) -> RoaringBitmap {
+        let nodes = self.orig_graph.node_indices().filter(|&node| pred(&self.orig_graph[node]));
+        RoaringBitmap::from_sorted_iter(nodes.map(|node| node.index() as u32)).unwrap()
     }
 
+    /// Hide the nodes for which `retain` does not hold.
+    pub fn retain_nodes(&mut self, retain: impl Fn(&NodeData) -> bool) {
+        self.visible &= self.nodes_where(retain);
+    }
+
+    /// Rebuild the visible graph from the visible nodes of the original
+    /// graph. Nodes are connected by the original edges between them and by
+    /// an indirect edge wherever one depends on another only through hidden
+    /// nodes, as long as the dependency is not already implied by other
+    /// edges (i.e. indirect edges form a transitive reduction).
+    ///
+    /// Indirect edges are found from the hidden subgraph: going through the
+    /// nodes in reverse topological order, each hidden node collects the set
+    /// of visible nodes it reaches through hidden nodes only (its exits).
+    /// This takes one bitmap union per edge leaving a hidden node, and a
+    /// set is dropped once all parents of its node have used it. The
+    /// candidates of a visible node `u` are then its visible children and
+    /// the exits of its hidden children, of which a candidate is kept unless
+    /// reachable from an earlier kept one. In total this is `O(E)` bitmap
+    /// unions plus `O(sum(candidates(u) * kept(u)))` reachability queries,
+    /// rather than a query per pair of nodes with hidden neighbours.
     pub fn retain_visible_nodes_and_reconnect(&mut self) -> VisibleGraphInfo {
         let prev_node_count = self.visible_graph.node_count();
         let prev_edge_count = self.visible_graph.edge_count();
         // retain all visible nodes
         let mut new_inst_graph = self.orig_graph.filter_map(
-            |_, node| Some(node).filter(|node| node.visible).cloned(),
+            |nx, node| Some(node).filter(|_| self.visible.contains(nx.index() as u32)).cloned(),
             |orig_graph_idx, edge_data| {
                 Some(EdgeType::Direct {
                     kind: edge_data.clone(),
@@ -182,70 +278,59 @@ impl InstGraph {
                 })
             },
         );
-        // remember all direct edges (will be added to the graph in the end)
-        let direct_edges = new_inst_graph
-            .raw_edges()
-            .to_vec();
-        // nodes with missing children
-        let out_set: Vec<NodeIndex> = new_inst_graph
-            .node_indices()
-            .filter(|node| {
-                let new_child_count = new_inst_graph.neighbors_directed(*node, Outgoing).count();
-                let old_child_count = new_inst_graph.node_weight(*node).unwrap().child_count;
-                new_child_count < old_child_count
-            })
-            .collect();
-        // nodes with missing parents
-        let in_set: Vec<NodeIndex> = new_inst_graph
+        let mut new_idx = vec![None; self.orig_graph.node_count()];
+        for node in new_inst_graph.node_indices() {
+            new_idx[new_inst_graph[node].orig_graph_idx.index()] = Some(node);
+        }
+        // Nodes are in topological order, so going backwards all children of
+        // a node have been visited before it.
+        let mut exits: Vec<Option<RoaringBitmap>> = vec![None; self.orig_graph.node_count()];
+        let mut remaining_parents: Vec<usize> = self
+            .orig_graph
             .node_indices()
-            .filter(|node| {
-                new_inst_graph.neighbors_directed(*node, Incoming).count()
-                    < new_inst_graph.node_weight(*node).unwrap().parent_count
-            })
+            .map(|node| self.orig_graph.edges_directed(node, Incoming).count())
             .collect();
-        // add all edges (u,v) in out_set x in_set to the new_inst_graph where v is reachable from u in the original graph
-        // and (u,v) is not an edge in the original graph, i.e., all indirect edges
-        for &u in &out_set {
-            for &v in &in_set {
-                let old_u = new_inst_graph.node_weight(u).unwrap().orig_graph_idx;
-                let old_v = new_inst_graph.node_weight(v).unwrap().orig_graph_idx;
-                if old_u != old_v
-                    && !self.orig_graph.contains_edge(old_u, old_v)
-                    && self.tr_closure_contains_edge(old_u, old_v)
-                // && petgraph::algo::has_path_connecting(&self.orig_graph, old_u, old_v, None)
-                {
-                    new_inst_graph.add_edge(u, v, EdgeType::Indirect);
+        let mut indirect_edges = Vec::new();
+        for node in self.orig_graph.node_indices().rev() {
+            let mut direct = RoaringBitmap::new();
+            let mut indirect = RoaringBitmap::new();
+            for child in self.orig_graph.neighbors_directed(node, Outgoing) {
+                if new_idx[child.index()].is_some() {
+                    direct.insert(child.index() as u32);
+                    continue;
+                }
+                indirect |= exits[child.index()].as_ref().unwrap();
+                remaining_parents[child.index()] -= 1;
+                if remaining_parents[child.index()] == 0 {
+                    exits[child.index()] = None;
                 }
             }
-        }
-        // compute transitive reduction to minimize |E| and not clutter the graph
-        let toposorted_dag = petgraph::algo::toposort(&new_inst_graph, None).unwrap();
-        let (intermediate, _) = petgraph::algo::tred::dag_to_toposorted_adjacency_list::<_, u32>(
-            &new_inst_graph,
-            &toposorted_dag,
-        );
-        let (tred, _) = petgraph::algo::tred::dag_transitive_reduction_closure(&intermediate);
-        // remove all edges since we only want the direct edges and the indirect edges in the transitive reduction in the final graph
-        new_inst_graph.clear_edges();
-        // add all direct edges to new_inst_graph that we removed previously
-        for direct_edge in direct_edges {
-            new_inst_graph.add_edge(
-                direct_edge.source(),
-                direct_edge.target(),
-                direct_edge.weight,
-            );
-        }
-        // add all indirect edges from transitive reduction that are not direct edges
-        for indirect_edge in tred.edge_references() {
-            // in tred, the node indices are replaced by their topological rank
-            // hence we need to look up the index before adding to new_inst_graph
-            let source = toposorted_dag[indirect_edge.source() as usize];
-            let target = toposorted_dag[indirect_edge.target() as usize];
-            // we only want indirect edges
-            if !new_inst_graph.contains_edge(source, target) {
-                new_inst_graph.add_edge(source, target, EdgeType::Indirect);
+            let Some(source) = new_idx[node.index()] else {
+                indirect |= direct;
+                exits[node.index()] = Some(indirect);
+                continue;
+            };
+            if indirect.is_empty() {
+                continue;
+            }
+            // Candidates in topological order: a candidate reachable from
+            // another is either reachable from a kept one or is kept itself.
+            let mut kept: Vec<NodeIndex> = Vec::new();
+            for candidate in &(&direct | &indirect) {
+                let candidate = NodeIndex::new(candidate as usize);
+                if kept.iter().any(|&kept| self.tr_closure_contains_edge(kept, candidate)) {
+                    continue;
+                }
+                kept.push(candidate);
+                if !direct.contains(candidate.index() as u32) {
+                    indirect_edges.push((source, new_idx[candidate.index()].unwrap()));
+                }
             }
         }
+        indirect_edges.sort_unstable();
+        for (source, target) in indirect_edges {
+            new_inst_graph.add_edge(source, target, EdgeType::Indirect);
+        }
         self.visible_graph = new_inst_graph;
         let curr_node_count = self.visible_graph.node_count();
         let curr_edge_count = self.visible_graph.edge_count();
@@ -258,81 +343,75 @@ impl InstGraph {
     }
 
     fn tr_closure_contains_edge(&self, from: NodeIndex, to: NodeIndex) -> bool {
-        let topo_ord_from = self.orig_graph.node_weight(from).unwrap().topo_ord;
-        let from_bitset = &self.tr_closure[topo_ord_from];
-        from_bitset.contains(to.index() as u32)
+        self.reachability.reaches(&self.orig_graph, from, to)
     }
 
     pub fn keep_n_most_costly(&mut self, n: usize) {
-        self.keep_n_highest_ranked(n, InstOrder::Cost)
+        self.visible = self.n_most_costly(n, &self.visible);
     }
 
     pub fn keep_n_most_branching(&mut self, n: usize) {
-        self.keep_n_highest_ranked(n, InstOrder::Branching)
+        self.visible = self.n_most_branching(n, &self.visible);
+    }
+
+    /// The `n` nodes of `among` with the highest cost.
+    pub fn n_most_costly(&self, n: usize, among: &RoaringBitmap) -> RoaringBitmap {
+        self.n_highest_ranked(n, among, InstOrder::Cost)
     }
 
-    fn keep_n_highest_ranked(&mut self, n: usize, order: InstOrder) {
+    /// The `n` nodes of `among` with the most children.
+    pub fn n_most_branching(&self, n: usize, among: &RoaringBitmap) -> RoaringBitmap {
+        self.n_highest_ranked(n, among, InstOrder::Branching)
+    }
+
+    fn n_highest_ranked(&self, n: usize, among: &RoaringBitmap, order: InstOrder) -> RoaringBitmap {
         let ranked_node_indices = match order {
             InstOrder::Branching => &self.branching_ranked_node_indices,
             InstOrder::Cost => &self.cost_ranked_node_indices,
         };
-        let visible_nodes: Vec<NodeIndex> = self
-            .orig_graph
-            .node_indices()
-            .filter(|n| self.orig_graph.node_weight(*n).unwrap().visible)
-            .collect();
-        if let Some(nth_highest_ranked_visible_node) = ranked_node_indices
+        ranked_node_indices
             .iter()
-            .filter(|nidx| visible_nodes.contains(nidx))
+            .map(|nidx| nidx.index() as u32)
+            .filter(|nidx| among.contains(*nidx))
             .take(n)
-            .last()
-        {
-            let nth_largest_rank = self
-                .orig_graph
-                .node_weight(*nth_highest_ranked_visible_node)
-                .unwrap()
-                .clone();
-            // among the visible nodes keep those whose cost-rank
-            // is larger than the cost rank of the n-th costliest
-            match order {
-                InstOrder::Branching => self.retain_nodes(|node| {
-                    node.visible && node.branching_rank <= nth_largest_rank.branching_rank
-                }),
-                InstOrder::Cost => self.retain_nodes(|node| {
-                    node.visible && node.cost_rank <= nth_largest_rank.cost_rank
-                }),
-            }
-        }
+            .collect()
     }
 
-    pub fn visit_descendants(&mut self, root: NodeIndex, retain: bool) {
+    /// `root` and all nodes which transitively depend on it.
+    pub fn descendants(&self, root: NodeIndex) -> RoaringBitmap {
         let mut dfs = Dfs::new(&self.orig_graph, root);
-        while let Some(nx) = dfs.next(&self.orig_graph) {
-            self.orig_graph[nx].visible = retain;
-        }
+        std::iter::from_fn(|| dfs.next(&self.orig_graph))
+            .map(|nx| nx.index() as u32)
+            .collect()
     }
 
-    pub fn visit_ancestors(&mut self, node: NodeIndex, retain: bool) {
-        let mut dfs = Dfs::new(petgraph::visit::Reversed(&self.orig_graph), node);
-        while let Some(nx) = dfs.next(petgraph::visit::Reversed(&self.orig_graph)) {
-            self.orig_graph[nx].visible = retain;
-        }
+    /// `node` and all nodes which it transitively depends on.
+    pub fn ancestors(&self, node: NodeIndex) -> RoaringBitmap {
+        let reversed = petgraph::visit::Reversed(&self.orig_graph);
+        let mut dfs = Dfs::new(reversed, node);
+        std::iter::from_fn(|| dfs.next(reversed))
+            .map(|nx| nx.index() as u32)
+            .collect()
     }
 
-    pub fn show_longest_path_through(&mut self, node: NodeIndex) -> Vec<NodeIndex> {
+    /// The direct parents or children of `node`, not including `node`.
+    pub fn neighbours(&self, node: NodeIndex, direction: petgraph::Direction) -> RoaringBitmap {
+        self.orig_graph
+            .neighbors_directed(node, direction)
+            .map(|nx| nx.index() as u32)
+            .collect()
+    }
+
+    /// A longest dependency path through `node`, from a root of the original
+    /// graph to the furthest descendant of `node`.
+    pub fn longest_path_through(&self, node: NodeIndex) -> Vec<NodeIndex> {
         // construct subtree rooted at selected node
-        let mut subtree_rooted_at_node: StableGraph<NodeData, BlameKind> =
+        let descendants = self.descendants(node);
+        let subtree_rooted_at_node: StableGraph<NodeData, BlameKind> =
             StableGraph::from(self.orig_graph.clone());
-        for node in subtree_rooted_at_node.node_weights_mut() {
-            node.visible = false;
-        }
-        let mut dfs = Dfs::new(&subtree_rooted_at_node, node);
-        while let Some(nx) = dfs.next(&subtree_rooted_at_node) {
-            subtree_rooted_at_node[nx].visible = true;
-        }
-        subtree_rooted_at_node = subtree_rooted_at_node.filter_map(
-            |_, node_data| {
-                if node_data.visible {
+        let mut subtree_rooted_at_node = subtree_rooted_at_node.filter_map(
+            |nx, node_data| {
+                if descendants.contains(nx.index() as u32) {
                     Some(node_data.clone())
                 } else {
                     None
@@ -366,16 +445,14 @@ impl InstGraph {
         }
         while let Some(curr) = visitor.pop() {
             longest_path.push(curr);
-            self.orig_graph[curr].visible = true;
             let curr_distance = subtree_rooted_at_node.node_weight(curr).unwrap().max_depth;
             let pred = subtree_rooted_at_node
                 .neighbors_directed(curr, Incoming)
-                .filter(|pred| {
+                .find(|pred| {
                     let pred_distance =
                         subtree_rooted_at_node.node_weight(*pred).unwrap().max_depth;
                     pred_distance == curr_distance - 1
-                })
-                .next();
+                });
             if let Some(node) = pred {
                 visitor.push(node);
             }
@@ -384,25 +461,22 @@ impl InstGraph {
         visitor.push(node);
         while let Some(curr) = visitor.pop() {
             longest_path.push(curr);
-            self.orig_graph[curr].visible = true;
             let curr_distance = self.orig_graph.node_weight(curr).unwrap().max_depth;
             let pred = self
                 .orig_graph
                 .neighbors_directed(curr, Incoming)
-                .filter(|pred| {
+                .find(|pred| {
                     let pred_distance = self.orig_graph.node_weight(*pred).unwrap().max_depth;
                     pred_distance == curr_distance - 1
-                })
-                .next();
+                });
             if let Some(node) = pred {
                 visitor.push(node);
             }
         }
+        // both backtracks include `node`
+        longest_path.reverse();
+        longest_path.dedup();
         longest_path
-            .iter()
-            .cloned()
-            .rev()
-            .collect::<Vec<NodeIndex>>()
     }
 
     // fn backtrack<T>(&mut self, graph: Option<T>, node: NodeIndex) where
@@ -427,95 +501,12 @@ impl InstGraph {
     //     }
     // }
 
-    pub fn show_matching_loops(&mut self) {
-        let quants: FxHashSet<_> = self
-            .orig_graph
-            .node_weights()
-            .flat_map(|node| node.mkind.quant_idx())
-            .collect();
-        let mut matching_loop_nodes_per_quant: Vec<FxHashSet<NodeIndex>> = Vec::new();
-        log!(format!("Start processing quants"));
-        for quant in quants {
-            log!(format!("Processing quant {}", quant));
-            self.reset_visibility_to(true);
-            self.retain_nodes(|node| {
-                node.mkind
-                    .quant_idx()
-                    .map(|q| q == quant)
-                    .unwrap_or_default()
-            });
-            self.retain_visible_nodes_and_reconnect();
-            let matching_loops = Self::find_longest_paths(&mut self.visible_graph);
-            matching_loop_nodes_per_quant.push(matching_loops);
-        }
-        log!(format!("Done processing quants"));
-        self.reset_visibility_to(false);
-        for matching_loop in matching_loop_nodes_per_quant {
-            for node in matching_loop {
-                self.orig_graph[node].visible = true;
-            }
-        }
-    }
-
-    fn find_longest_paths(graph: &mut Graph<NodeData, EdgeType>) -> FxHashSet<NodeIndex> {
-        // traverse this subtree in topological order to compute longest distances from node
-        let mut topo = Topo::new(&*graph);
-        while let Some(nx) = topo.next(&*graph) {
-            let parents = graph.neighbors_directed(nx, Incoming);
-            let max_parent_depth = parents
-                .map(|nx| graph.node_weight(nx).unwrap().max_depth)
-                .max();
-            if let Some(depth) = max_parent_depth {
-                graph[nx].max_depth = depth + 1;
-            } else {
-                graph[nx].max_depth = 0;
-            }
-        }
-        let furthest_away_nodes = graph
-            .node_indices()
-            // only want to show matching loops of length at least 3, hence only keep nodes with depth at least 2
-            .filter(|nx| graph.node_weight(*nx).unwrap().max_depth >= MIN_MATCHING_LOOP_LENGTH - 1)
-            .max_set_by(|node_a, node_b| {
-                graph
-                    .node_weight(*node_a)
-                    .unwrap()
-                    .max_depth
-                    .cmp(&graph.node_weight(*node_b).unwrap().max_depth)
-            });
-        // backtrack longest paths from furthest away nodes in subgraph until we reach a root
-        let mut matching_loop_nodes: FxHashSet<NodeIndex> = FxHashSet::default();
-        let mut visitor: Vec<NodeIndex> = furthest_away_nodes;
-        let mut visited: FxHashSet<_> = FxHashSet::default();
-        while let Some(curr) = visitor.pop() {
-            matching_loop_nodes.insert(graph.node_weight(curr).unwrap().orig_graph_idx);
-            let curr_distance = graph.node_weight(curr).unwrap().max_depth;
-            let preds = graph.neighbors_directed(curr, Incoming).filter(|pred| {
-                let pred_distance = graph.node_weight(*pred).unwrap().max_depth;
-                pred_distance == curr_distance - 1
-            });
-            for pred in preds {
-                if visited.insert(pred) {
-                    visitor.push(pred);
-                }
-            }
-        }
-        matching_loop_nodes
-    }
-
     pub fn reset_visibility_to(&mut self, visibility: bool) {
-        for node in self.orig_graph.node_weights_mut() {
-            node.visible = visibility;
-        }
-    }
-
-    pub fn show_neighbours(&mut self, node: NodeIndex, direction: petgraph::Direction) {
-        let neighbour_indices: Vec<NodeIndex> = self
-            .orig_graph
-            .neighbors_directed(node, direction)
-            .collect();
-        for neighbour in neighbour_indices {
-            self.orig_graph.node_weight_mut(neighbour).unwrap().visible = true;
-        }
+        self.visible = if visibility {
+            self.all_nodes()
+        } else {
+            RoaringBitmap::new()
+        };
     }
 
     pub fn node_has_filtered_children(&self, node_idx: NodeIndex) -> bool {
@@ -539,167 +530,190 @@ impl InstGraph {
                     Incoming => e.source(),
                 });
         let (visible_neighbours, hidden_neighbours): (Vec<NodeIndex>, Vec<NodeIndex>) =
-            neighbours.partition(|n| self.orig_graph.node_weight(*n).unwrap().visible);
+            neighbours.partition(|n| self.visible.contains(n.index() as u32));
         let nr_visible_neighbours = visible_neighbours.len();
         let nr_hidden_neighbours = hidden_neighbours.len();
         nr_visible_neighbours < nr_hidden_neighbours + nr_visible_neighbours
     }
 
-    fn compute_instantiation_graph(&mut self, parser: &Z3Parser) {
-        for (inst_idx, inst) in parser.insts.insts.iter_enumerated() {
-            let match_ = &parser.insts[inst.match_];
+    /// Add the instantiations which the parser made since the graph was
+    /// created or last extended, e.g. to show a log while it is still being
+    /// parsed. Only the new nodes and their parents are updated, nothing is
+    /// recomputed from scratch.
+    ///
+    /// The edges of a node are those known when it was added, they are not
+    /// updated if an equality is explained afterwards (see
+    /// [`BlameKind::UnknownEquality`]). An unfiltered visible graph is
+    /// extended as well, a filtered one only shows the new nodes after the
+    /// next [`retain_visible_nodes_and_reconnect`](Self::retain_visible_nodes_and_reconnect).
+    ///
+    /// The new nodes and their ancestors are costed with
+    /// [`EvenSplit`](super::cost::EvenSplit),
+    /// independently of the (possibly not yet computed) costs of the parser.
+    /// After [`set_costs`](Self::set_costs) with a different model it has to
+    /// be called again.
+    pub fn extend(&mut self, parser: &Z3Parser) {
+        let first_node = self.orig_graph.node_count();
+        let first_edge = self.orig_graph.edge_count();
+        let unfiltered = self.visible_graph.node_count() == first_node
+            && self.visible_graph.edge_count() == first_edge;
+        let mut parents = Vec::new();
+        let mut updated_parents = Vec::new();
+        for idx in self.node_of_inst_idx.len()..parser.inst_count() {
+            let inst_idx = InstIdx::from(idx);
+            let inst = &parser[inst_idx];
+            let match_ = &parser[inst.match_];
             // add new node to graph
-            self.add_node(NodeData {
+            let node = self.add_node(NodeData {
                 is_theory_inst: match_.kind.is_discovered(),
-                cost: inst.cost,
+                cost: 0.0,
                 inst_idx,
                 mkind: match_.kind.clone(),
-                visible: true,
+                query: parser.query_of_inst(inst_idx),
                 child_count: 0,
                 parent_count: 0,
                 orig_graph_idx: NodeIndex::default(),
-                cost_rank: 0,
-                branching_rank: 0,
-                min_depth: None,
+                min_depth: Some(0),
                 max_depth: 0,
-                topo_ord: 0,
             });
-            // then add all edges to previous nodes
-            for (kind, from) in match_
-                .due_to_enodes()
-                .filter_map(|(kind, e)| parser[e].created_by.map(|c| (kind, c)))
-            {
+            // new nodes are visible until filtered out
+            self.visible.insert(node.index() as u32);
+            // then add all edges to previous nodes, ignoring any blame on a
+            // later instantiation (see `check`) which would make a cycle
+            parents.clear();
+            let creators = blamed_creators(parser, match_).into_iter();
+            for (kind, _, from) in creators.filter(|&(_, _, from)| from < inst_idx) {
+                parents.push(self.node_of_inst_idx[from]);
                 self.add_edge(from, inst_idx, kind);
             }
-        }
-        // precompute number of children and parents of each node
-        for idx in self.orig_graph.node_indices() {
-            let child_count = self.orig_graph.neighbors_directed(idx, Outgoing).count();
-            let parent_count = self.orig_graph.neighbors_directed(idx, Incoming).count();
-            self.orig_graph.node_weight_mut(idx).unwrap().child_count = child_count;
-            self.orig_graph.node_weight_mut(idx).unwrap().parent_count = parent_count;
-        }
-        // precompute the cost-rank of all nodes by sorting the node_indices by our cost-order
-        // in descending order and then assigning the rank to each node
-        // Our cost-order is defined as follows:
-        // inst_b > inst_a iff (cost_b > cost_a or (cost_b = cost_a and line_nr_b < line_nr_a))
-        // This is a total order since the line numbers are always guaranteed to be distinct
-        // integers.
-        let mut cost_ranked_node_indices: Vec<NodeIndex> = self.orig_graph.node_indices().collect();
-        let cost_order = |node_a: &NodeIndex, node_b: &NodeIndex| {
-            let node_a_data = self.orig_graph.node_weight(*node_a).unwrap();
-            let node_b_data = self.orig_graph.node_weight(*node_b).unwrap();
-            if node_a_data.cost < node_b_data.cost || (node_a_data.cost == node_b_data.cost
-                && node_b_data.inst_idx < node_a_data.inst_idx) {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Less
+            // Instantiations only depend on earlier ones, so the depths of
+            // the parents are final and those of the new node follow
+            // directly from them.
+            self.reachability.add_node(&self.orig_graph, node);
+            let (mut min_depth, mut max_depth) = (None, None);
+            for &parent in &parents {
+                let parent_data = &mut self.orig_graph[parent];
+                parent_data.child_count += 1;
+                min_depth = min_depth.min(parent_data.min_depth).or(parent_data.min_depth);
+                max_depth = max_depth.max(Some(parent_data.max_depth));
+                updated_parents.push(parent);
             }
-        };
-        cost_ranked_node_indices.sort_unstable_by(cost_order);
-        for (i, nidx) in cost_ranked_node_indices.iter().enumerate() {
-            self.orig_graph.node_weight_mut(*nidx).unwrap().cost_rank = i;
-        }
-        self.cost_ranked_node_indices = cost_ranked_node_indices;
-        // precompute BFS depth such that we can filter the graph up to some specified depth
-        let roots: Vec<NodeIndex> = self
-            .orig_graph
-            .node_indices()
-            .filter(|nx| self.orig_graph.node_weight(*nx).unwrap().parent_count == 0)
-            .collect();
-        for root in roots {
-            let mut bfs = Bfs::new(&self.orig_graph, root);
-            while let Some(nx) = bfs.next(&self.orig_graph) {
-                let parents = self.orig_graph.neighbors_directed(nx, Incoming);
-                let min_parent_depth = parents
-                    .filter_map(|parent| self.orig_graph.node_weight(parent).unwrap().min_depth)
-                    .min();
-                if let Some(depth) = min_parent_depth {
-                    self.orig_graph[nx].min_depth = Some(depth + 1);
-                } else {
-                    // the min_depth is None iff the node at nx has no parents, hence we set the depth to 0
-                    self.orig_graph[nx].min_depth = Some(0);
-                }
+            let node_data = &mut self.orig_graph[node];
+            node_data.parent_count = parents.len();
+            node_data.min_depth = Some(min_depth.map_or(0, |depth| depth + 1));
+            node_data.max_depth = max_depth.map_or(0, |depth| depth + 1);
+        }
+        let new_nodes = (first_node..self.orig_graph.node_count()).map(NodeIndex::new);
+        let costed = self.split_costs(new_nodes.clone());
+        rerank(
+            &self.orig_graph,
+            &mut self.cost_ranked_node_indices,
+            costed.clone(),
+            cost_order,
+        );
+        updated_parents.retain(|parent| parent.index() < first_node);
+        updated_parents.extend(new_nodes.clone());
+        rerank(
+            &self.orig_graph,
+            &mut self.branching_ranked_node_indices,
+            updated_parents.clone(),
+            branching_order,
+        );
+        if unfiltered {
+            for node in new_nodes {
+                self.visible_graph.add_node(self.orig_graph[node].clone());
             }
-        }
-        // precompute the branching-rank of all nodes by sorting the node_indices by our branching-order
-        // in descending order and then assigning the rank to each node
-        // Our branching-order is defined as follows:
-        // inst_b > inst_a iff (child_count(b) > child_count(a) or (child_count(b) = child_count(a) and line_nr_b < line_nr_a))
-        // This is a total order since the line numbers are always guaranteed to be distinct
-        // integers.
-        let mut branching_ranked_node_indices: Vec<NodeIndex> =
-            self.orig_graph.node_indices().collect();
-        let branching_order = |node_a: &NodeIndex, node_b: &NodeIndex| {
-            let node_a_data = self.orig_graph.node_weight(*node_a).unwrap();
-            let node_b_data = self.orig_graph.node_weight(*node_b).unwrap();
-            if node_a_data.child_count < node_b_data.child_count || (node_a_data.child_count == node_b_data.child_count
-                && node_b_data.inst_idx < node_a_data.inst_idx) {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Less
+            for parent in updated_parents {
+                self.visible_graph[parent].child_count = self.orig_graph[parent].child_count;
             }
-        };
-        branching_ranked_node_indices.sort_unstable_by(branching_order);
-        for (i, nidx) in branching_ranked_node_indices.iter().enumerate() {
-            self.orig_graph
-                .node_weight_mut(*nidx)
-                .unwrap()
-                .branching_rank = i;
-        }
-        self.branching_ranked_node_indices = branching_ranked_node_indices;
-        // compute the longest distances from root nodes by traversing the graph in topological order
-        // and taking max distance among parents + 1. Needed to compute longest paths through selected
-        // nodes
-        // root nodes (i.e., nodes without parents) have distance 0
-        let mut topo = Topo::new(&self.orig_graph);
-        while let Some(node) = topo.next(&self.orig_graph) {
-            let max_parent_depth = self
-                .orig_graph
-                .neighbors_directed(node, Incoming)
-                .map(|nx| self.orig_graph.node_weight(nx).unwrap().max_depth)
-                .max();
-            if let Some(depth) = max_parent_depth {
-                let node_weight = self.orig_graph.node_weight_mut(node).unwrap();
-                node_weight.max_depth = depth + 1;
+            for node in costed {
+                self.visible_graph[node].cost = self.orig_graph[node].cost;
+            }
+            for edge in (first_edge..self.orig_graph.edge_count()).map(EdgeIndex::new) {
+                let (from, to) = self.orig_graph.edge_endpoints(edge).unwrap();
+                let kind = self.orig_graph[edge].clone();
+                self.visible_graph.add_edge(from, to, EdgeType::Direct { kind, orig_graph_idx: edge });
             }
         }
-        // efficiently compute transitive closure with a vector of FixedBitSet's
-        let mut topo = Topo::new(petgraph::visit::Reversed(&self.orig_graph));
-        // assign topological orders to each node
-        let mut topo_ord = self.orig_graph.node_count() - 1;
-        while let Some(nx) = topo.next(petgraph::visit::Reversed(&self.orig_graph)) {
-            self.orig_graph[nx].topo_ord = topo_ord;
-            topo_ord = topo_ord.saturating_sub(1);
-        }
-        self.tr_closure = vec![RoaringBitmap::new(); self.orig_graph.node_count()];
-        // note that we are storing the bitsets's of each node index in topological order!
-        let mut topo = Topo::new(petgraph::visit::Reversed(&self.orig_graph));
-        let mut bitsets = self.tr_closure.as_mut_slice();
-        let mut ord = self.orig_graph.node_count() - 1;
-        while let Some((last, others)) = bitsets.split_last_mut() {
-            if let Some(nx) = topo.next(petgraph::visit::Reversed(&self.orig_graph)) {
-                last.insert(nx.index() as u32);
-                for pred in self.orig_graph.neighbors_directed(nx, Incoming) {
-                    let pred_topo_ord = self.orig_graph.node_weight(pred).unwrap().topo_ord;
-                    let pred_bitset = others.get_mut(pred_topo_ord).unwrap();
-                    *pred_bitset |= &*last;
-                }
+    }
+
+    /// Give each of `new_nodes` a cost of 1 and pass it on to its parents as
+    /// [`EvenSplit`](super::cost::EvenSplit) does. Instantiations only depend on earlier ones, so
+    /// going through the nodes last first, each has received everything from
+    /// its children before passing it on. Returns the nodes whose cost
+    /// changed.
+    fn split_costs(&mut self, new_nodes: impl Iterator<Item = NodeIndex>) -> Vec<NodeIndex> {
+        let mut passed_on: BTreeMap<NodeIndex, f32> = new_nodes.map(|node| (node, 1.0)).collect();
+        let mut costed = Vec::new();
+        while let Some((node, cost)) = passed_on.pop_last() {
+            self.orig_graph[node].cost += cost;
+            costed.push(node);
+            // One share per edge, i.e. per term the node was triggered by.
+            let parents: Vec<_> = self.orig_graph.neighbors_directed(node, Incoming).collect();
+            let share = cost / parents.len() as f32;
+            for parent in parents {
+                *passed_on.entry(parent).or_default() += share;
             }
-            bitsets = others;
-            ord = ord.saturating_sub(1);
         }
-        log!("Finished computing transitive closure");
-        self.visible_graph = self.orig_graph.map(
-            |_, n| n.clone(),
-            |orig_graph_idx, e| EdgeType::Direct {
-                kind: e.clone(),
-                orig_graph_idx,
-            },
-        )
+        costed
+    }
+
+    /// Replace the cost of every node with the cost given by `model` and
+    /// recompute the cost ranks used by
+    /// [`keep_n_most_costly`](Self::keep_n_most_costly).
+    pub fn set_costs(&mut self, parser: &Z3Parser, model: &impl CostModel) {
+        let costs = model.costs(parser);
+        for (node, cost) in self.node_of_inst_idx.iter().zip(costs.insts) {
+            self.orig_graph[*node].cost = cost;
+        }
+        let all_nodes = self.orig_graph.node_indices().collect();
+        rerank(&self.orig_graph, &mut self.cost_ranked_node_indices, all_nodes, cost_order);
+        for node in self.visible_graph.node_weights_mut() {
+            node.cost = self.orig_graph[node.orig_graph_idx].cost;
+        }
+    }
+
+    /// Keep track of the pending matches (see
+    /// [`Z3Parser::pending_matches`]) such that they can be shown as ghost
+    /// nodes with [`visible_ghosts`](Self::visible_ghosts).
+    pub fn add_pending_matches(&mut self, parser: &Z3Parser) {
+        self.ghosts = parser
+            .pending_matches()
+            .map(|(match_idx, match_)| {
+                let mut parents: Vec<_> = blamed_creators(parser, match_)
+                    .into_iter()
+                    .map(|(_, _, inst)| self.node_of_inst_idx[inst])
+                    .collect();
+                parents.sort_unstable();
+                parents.dedup();
+                GhostNode { match_idx, parents }
+            })
+            .collect();
+    }
+
+    /// The ghost nodes which depend on at least one node of the visible
+    /// graph, with those nodes as indices into the visible graph. Pending
+    /// matches triggered only by terms which no instantiation created are not
+    /// included.
+    pub fn visible_ghosts(&self) -> Vec<(MatchIdx, Vec<NodeIndex>)> {
+        let mut visible = vec![None; self.orig_graph.node_count()];
+        for node in self.visible_graph.node_indices() {
+            visible[self.visible_graph[node].orig_graph_idx.index()] = Some(node);
+        }
+        self.ghosts
+            .iter()
+            .filter_map(|ghost| {
+                let parents: Vec<_> = ghost
+                    .parents
+                    .iter()
+                    .filter_map(|parent| visible[parent.index()])
+                    .collect();
+                (!parents.is_empty()).then_some((ghost.match_idx, parents))
+            })
+            .collect()
     }
 
-    fn add_node(&mut self, node_data: NodeData) {
+    fn add_node(&mut self, node_data: NodeData) -> NodeIndex {
         let inst_idx = node_data.inst_idx;
         let node = self.orig_graph.add_node(node_data);
         let ins_idx = self.node_of_inst_idx.push_and_get_key(node);
@@ -709,10 +723,8 @@ impl InstGraph {
         // store original node-idx such that when we compute reachability, we
         // can use the old indices.
         // this is necessary since filtering out nodes will changes node-indices
-        // Also, using StableGraph where node-indices stay stable across removals
-        // is not viable here since StableGraph does not implement NodeCompactIndexable
-        // which is needed for petgraph::algo::tred::dag_to_toposorted_adjacency_list
         self.orig_graph[node].orig_graph_idx = node;
+        node
     }
 
     fn add_edge(&mut self, from: InstIdx, to: InstIdx, blame: &BlameKind) {
@@ -723,8 +735,8 @@ impl InstGraph {
     pub fn get_node_info_map(&self) -> NodeInfoMap {
         let mut node_info_map = FxHashMap::default();
         for node_index in self.orig_graph.node_indices() {
-            let NodeData { inst_idx, .. } = self.orig_graph[node_index];
-            node_info_map.insert(node_index, inst_idx);
+            let NodeData { inst_idx, cost, .. } = self.orig_graph[node_index];
+            node_info_map.insert(node_index, (inst_idx, cost));
         }
         NodeInfoMap::from(node_info_map)
     }
@@ -740,11 +752,76 @@ impl InstGraph {
     }
 }
 
-pub struct NodeInfoMap(FxHashMap<NodeIndex, InstIdx>); 
+/// Instantiations with a higher cost first, ties are broken by the order of
+/// their `[instance]` lines.
+fn cost_order(a: &NodeData, b: &NodeData) -> Ordering {
+    b.cost.total_cmp(&a.cost).then(a.inst_idx.cmp(&b.inst_idx))
+}
+
+/// Instantiations with more children first, ties are broken by the order of
+/// their `[instance]` lines.
+fn branching_order(a: &NodeData, b: &NodeData) -> Ordering {
+    b.child_count.cmp(&a.child_count).then(a.inst_idx.cmp(&b.inst_idx))
+}
+
+/// Move the `changed` nodes, which are new or ranked by a different key than
+/// before, to their place in `ranked` without sorting it again.
+fn rerank(
+    graph: &Graph<NodeData, BlameKind>,
+    ranked: &mut Vec<NodeIndex>,
+    mut changed: Vec<NodeIndex>,
+    order: fn(&NodeData, &NodeData) -> Ordering,
+) {
+    changed.sort_unstable();
+    changed.dedup();
+    let is_changed: RoaringBitmap = changed.iter().map(|node| node.index() as u32).collect();
+    ranked.retain(|node| !is_changed.contains(node.index() as u32));
+    changed.sort_unstable_by(|&a, &b| order(&graph[a], &graph[b]));
+    let unchanged = std::mem::take(ranked);
+    ranked.reserve(unchanged.len() + changed.len());
+    let mut unchanged = unchanged.into_iter().peekable();
+    let mut changed = changed.into_iter().peekable();
+    while let (Some(&a), Some(&b)) = (unchanged.peek(), changed.peek()) {
+        if order(&graph[b], &graph[a]).is_lt() {
+            ranked.push(b);
+            changed.next();
+        } else {
+            ranked.push(a);
+            unchanged.next();
+        }
+    }
+    ranked.extend(unchanged.chain(changed));
+}
+
+/// The instantiations which created the terms that `match_` was triggered by,
+/// with the blame and the term (enode) each is blamed for. These are the
+/// parents of its instantiation, once for each such term.
+pub fn blamed_creators<'a>(parser: &Z3Parser, match_: &'a Match) -> Vec<(&'a BlameKind, ENodeIdx, InstIdx)> {
+    let mut creators: Vec<_> = match_
+        .due_to_enodes()
+        .filter_map(|(kind, e)| parser[e].created_by.map(|c| (kind, e, c)))
+        .collect();
+    // for equalities we could not explain, the best we can do is to depend on
+    // the creators of both sides
+    for kind in match_.blamed.iter() {
+        if let &BlameKind::UnknownEquality { from, to, .. } = kind {
+            let mut sides: Vec<_> = [from, to]
+                .into_iter()
+                .filter_map(|e| parser[e].created_by.map(|c| (e, c)))
+                .collect();
+            sides.dedup_by_key(|&mut (_, creator)| creator);
+            creators.extend(sides.into_iter().map(|(e, creator)| (kind, e, creator)));
+        }
+    }
+    creators
+}
+
+/// The instantiation and cost (as given by the graph's cost model) of each node.
+pub struct NodeInfoMap(FxHashMap<NodeIndex, (InstIdx, f32)>); 
 
 impl NodeInfoMap {
 
-    fn from(map: FxHashMap<NodeIndex, InstIdx>) -> Self {
+    fn from(map: FxHashMap<NodeIndex, (InstIdx, f32)>) -> Self {
         NodeInfoMap(map) 
     }
 
@@ -754,7 +831,7 @@ impl NodeInfoMap {
         parser: &Z3Parser,
         ignore_ids: bool,
     ) -> InstInfo {
-        let inst_idx = self.0.get(&NodeIndex::new(node_index)).unwrap();
+        let (inst_idx, cost) = self.0.get(&NodeIndex::new(node_index)).unwrap();
         let ctxt = DisplayCtxt {
             parser,
 
@@ -772,11 +849,15 @@ impl NodeInfoMap {
         let inst_info = InstInfo {
             fingerprint: inst.fingerprint,
             inst_idx: *inst_idx,
+            inst_pos: inst.pos,
+            query: parser.query_of_inst(*inst_idx),
+            match_pos: match_.pos,
             resulting_term: inst
                 .get_resulting_term()
                 .map(|rt| rt.with(&ctxt).to_string()),
             z3_gen: inst.z3_generation,
-            cost: inst.cost,
+            cost: *cost,
+            useful: inst.useful,
             mkind: match_.kind.clone(),
             quant_discovered: match_.kind.is_discovered(),
             formula: match_.kind.with(&ctxt).to_string(),
@@ -823,8 +904,12 @@ impl EdgeInfoMap {
             display_quantifier_name: false,
             use_mathematical_symbols: true,
         };
-        let blame_term_idx = edge_data.get_blame_node().unwrap();
-        let blame_term = blame_term_idx.with(&ctxt).to_string();
+        let blame_term = match *edge_data {
+            BlameKind::UnknownEquality { from, to, .. } => {
+                format!("{} = {}", from.with(&ctxt), to.with(&ctxt))
+            }
+            _ => edge_data.get_blame_node().unwrap().with(&ctxt).to_string(),
+        };
         EdgeInfo {
             edge_data: edge_data.clone(),
             orig_graph_idx: edge_index,
@@ -0,0 +1,403 @@
+use std::fmt;
+
+use fxhash::{FxHashMap, FxHashSet};
+use petgraph::{
+    graph::NodeIndex,
+    visit::EdgeRef,
+    Direction::{Incoming, Outgoing},
+};
+
+use crate::display_with::{DisplayCtxt, DisplayWithCtxt};
+use crate::items::{IString, InstIdx, QuantIdx, TermIdx};
+
+use super::{
+    inst_graph::{blamed_creators, InstGraph},
+    z3parser::Z3Parser,
+};
+
+/// The minimum number of times a sequence of quantifiers has to repeat along
+/// a dependency path to be reported as a matching loop.
+pub const MIN_MATCHING_LOOP_LENGTH: usize = 3;
+/// The maximum number of distinct quantifiers taking part in a single
+/// iteration of a matching loop.
+pub const MAX_MATCHING_LOOP_QUANTS: usize = 4;
+
+/// A sequence of quantifiers which repeatedly trigger each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchingLoop {
+    /// The quantifiers instantiated in one iteration of the loop, in order.
+    pub quants: Vec<QuantIdx>,
+    /// All instantiations on a path through the loop with at least
+    /// [`MIN_MATCHING_LOOP_LENGTH`] iterations, sorted.
+    pub nodes: Vec<NodeIndex>,
+    /// A longest path through the loop, each instantiation depends on the
+    /// previous one.
+    pub chain: Vec<InstIdx>,
+}
+
+impl MatchingLoop {
+    /// The number of complete iterations along the [`chain`](Self::chain).
+    pub fn iterations(&self) -> usize {
+        self.chain.len() / self.quants.len()
+    }
+
+    /// Generalise the terms of each iteration of the [`chain`](Self::chain)
+    /// into a symbolic explanation of the loop.
+    pub fn explain(&self, parser: &Z3Parser) -> LoopExplanation {
+        let len = self.quants.len();
+        let bound_terms = |iidx: InstIdx| {
+            let kind = &parser[parser[iidx].match_].kind;
+            kind.bound_terms(|enode| parser[enode].owner, |term| term)
+        };
+        // Only use complete transitions so that the same iteration of every
+        // step is generalised together.
+        let iterations = (self.chain.len() - 1) / len;
+        let mut vars = FxHashMap::default();
+        let mut generalise = |terms: &[TermIdx]| Generalised::new(parser, terms, &mut vars);
+        let steps = (0..len)
+            .map(|step| {
+                let transitions = self
+                    .chain
+                    .windows(2)
+                    .skip(step)
+                    .step_by(len)
+                    .take(iterations);
+                let mut bound = Vec::new();
+                let mut produces = Vec::new();
+                let mut next_bound = Vec::new();
+                for pair in transitions {
+                    let (from, to) = (pair[0], pair[1]);
+                    bound.push(bound_terms(from));
+                    // The chain only follows edges of the instantiation graph,
+                    // so `to` must have been triggered by a term of `from`
+                    // (possibly one side of an unexplained equality).
+                    let (_, enode, _) = blamed_creators(parser, &parser[parser[to].match_])
+                        .into_iter()
+                        .find(|&(_, _, creator)| creator == from)
+                        .expect("consecutive instantiations in chain are not dependent");
+                    produces.push(parser[enode].owner);
+                    next_bound.push(bound_terms(to));
+                }
+                let mut columns = |terms: Vec<Vec<TermIdx>>| {
+                    let count = terms.iter().map(Vec::len).min().unwrap_or_default();
+                    (0..count)
+                        .map(|idx| generalise(&terms.iter().map(|t| t[idx]).collect::<Vec<_>>()))
+                        .collect::<Vec<_>>()
+                };
+                let bound_terms = columns(bound);
+                let next_bound_terms = columns(next_bound);
+                LoopStep {
+                    quant: self.quants[step],
+                    bound_terms,
+                    produces: generalise(&produces),
+                    next_bound_terms,
+                }
+            })
+            .collect();
+        LoopExplanation { steps }
+    }
+}
+
+/// A term generalised over the iterations of a matching loop
+/// (anti-unification): the parts which differ between iterations are replaced
+/// by variables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generalised {
+    /// The same term in every iteration.
+    Term(TermIdx),
+    /// The same function applied in every iteration, though to different
+    /// arguments.
+    App {
+        name: IString,
+        children: Vec<Generalised>,
+    },
+    /// Terms which differ between iterations. Each distinct sequence of terms
+    /// gets its own variable, shared between all terms of the loop.
+    Var(usize),
+}
+
+impl Generalised {
+    /// Anti-unify `terms`, which must not be empty.
+    fn new(
+        parser: &Z3Parser,
+        terms: &[TermIdx],
+        vars: &mut FxHashMap<Vec<TermIdx>, usize>,
+    ) -> Self {
+        let first = terms[0];
+        if terms.iter().all(|&term| term == first) {
+            return Self::Term(first);
+        }
+        let head = |term: TermIdx| {
+            let name = parser[term].kind.app_name()?;
+            // Interpreted constants with different values are different terms.
+            parser
+                .meaning(term)
+                .is_none()
+                .then_some((name, parser[term].child_ids.len()))
+        };
+        if let Some((name, arity)) =
+            head(first).filter(|&h| terms.iter().all(|&t| head(t) == Some(h)))
+        {
+            let children = (0..arity)
+                .map(|idx| {
+                    let column: Vec<_> = terms.iter().map(|&t| parser[t].child_ids[idx]).collect();
+                    Self::new(parser, &column, vars)
+                })
+                .collect();
+            return Self::App { name, children };
+        }
+        let next = vars.len();
+        Self::Var(*vars.entry(terms.to_vec()).or_insert(next))
+    }
+}
+
+/// One quantifier of a matching loop, with the terms of its instances
+/// generalised over all iterations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopStep {
+    pub quant: QuantIdx,
+    pub bound_terms: Vec<Generalised>,
+    /// The term produced by this step which triggers the next one.
+    pub produces: Generalised,
+    /// The terms bound by the next step, in the following iteration if this
+    /// is the last step.
+    pub next_bound_terms: Vec<Generalised>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopExplanation {
+    pub steps: Vec<LoopStep>,
+}
+
+impl InstGraph {
+    /// Find sequences of (at most [`MAX_MATCHING_LOOP_QUANTS`]) quantifiers
+    /// which repeat at least [`MIN_MATCHING_LOOP_LENGTH`] times along a
+    /// dependency path. Disconnected occurrences of the same sequence are
+    /// reported as separate loops, longest chain first.
+    pub fn matching_loops(&self) -> Vec<MatchingLoop> {
+        let graph = &self.orig_graph;
+        let quant = |node: NodeIndex| graph[node].mkind.quant_idx();
+        let mut nodes_of_quant = FxHashMap::<QuantIdx, Vec<NodeIndex>>::default();
+        for node in graph.node_indices() {
+            if let Some(q) = quant(node) {
+                nodes_of_quant.entry(q).or_default().push(node);
+            }
+        }
+        // Which quantifiers directly trigger which others.
+        let mut triggers = FxHashMap::<QuantIdx, FxHashSet<QuantIdx>>::default();
+        for edge in graph.edge_references() {
+            if let (Some(from), Some(to)) = (quant(edge.source()), quant(edge.target())) {
+                triggers.entry(from).or_default().insert(to);
+            }
+        }
+        let mut loops = Vec::new();
+        for cycle in quant_cycles(&triggers) {
+            let position: FxHashMap<_, _> =
+                cycle.iter().enumerate().map(|(i, &q)| (q, i)).collect();
+            // Whether `from -> to` follows the order of the cycle.
+            let follows = |from: NodeIndex, to: NodeIndex| {
+                let (Some(from), Some(to)) = (quant(from), quant(to)) else {
+                    return false;
+                };
+                position
+                    .get(&from)
+                    .is_some_and(|&p| cycle[(p + 1) % cycle.len()] == to)
+            };
+            // Instantiations are indexed in the order of their `[instance]`
+            // lines and only depend on earlier ones, so sorting the nodes
+            // gives a topological order.
+            let mut nodes: Vec<_> = cycle
+                .iter()
+                .flat_map(|q| nodes_of_quant[q].iter().copied())
+                .collect();
+            nodes.sort_unstable();
+            // The longest path following the cycle ending in (`depth`) and
+            // starting from (`height`) each node.
+            let mut depth = FxHashMap::<NodeIndex, usize>::default();
+            for &node in &nodes {
+                let d = graph
+                    .neighbors_directed(node, Incoming)
+                    .filter(|&parent| follows(parent, node))
+                    .map(|parent| depth[&parent] + 1)
+                    .max();
+                depth.insert(node, d.unwrap_or_default());
+            }
+            let mut height = FxHashMap::<NodeIndex, usize>::default();
+            for &node in nodes.iter().rev() {
+                let h = graph
+                    .neighbors_directed(node, Outgoing)
+                    .filter(|&child| follows(node, child))
+                    .map(|child| height[&child] + 1)
+                    .max();
+                height.insert(node, h.unwrap_or_default());
+            }
+            let min_len = cycle.len() * MIN_MATCHING_LOOP_LENGTH;
+            let in_loop = |node: &NodeIndex| depth[node] + height[node] + 1 >= min_len;
+            let mut unvisited: FxHashSet<_> = nodes.iter().copied().filter(in_loop).collect();
+            for &start in &nodes {
+                if !unvisited.remove(&start) {
+                    continue;
+                }
+                // Collect the connected component of loop nodes around `start`.
+                let mut component = vec![start];
+                let mut stack = vec![start];
+                while let Some(node) = stack.pop() {
+                    let children = graph
+                        .neighbors_directed(node, Outgoing)
+                        .filter(|&c| follows(node, c));
+                    let parents = graph
+                        .neighbors_directed(node, Incoming)
+                        .filter(|&p| follows(p, node));
+                    for next in children.chain(parents).collect::<Vec<_>>() {
+                        if unvisited.remove(&next) {
+                            component.push(next);
+                            stack.push(next);
+                        }
+                    }
+                }
+                component.sort_unstable();
+                let chain = longest_chain(
+                    &component,
+                    &depth,
+                    |p, n| follows(p, n) && component.binary_search(&p).is_ok(),
+                    graph,
+                );
+                let first = quant(chain[0]).unwrap();
+                let mut quants = cycle.clone();
+                quants.rotate_left(position[&first]);
+                loops.push(MatchingLoop {
+                    quants,
+                    chain: chain.into_iter().map(|node| graph[node].inst_idx).collect(),
+                    nodes: component,
+                });
+            }
+        }
+        loops.sort_by(|a, b| {
+            b.chain
+                .len()
+                .cmp(&a.chain.len())
+                .then_with(|| a.nodes.cmp(&b.nodes))
+        });
+        loops
+    }
+}
+
+/// Backtrack a longest path ending in the deepest node of `component`.
+fn longest_chain<N, E>(
+    component: &[NodeIndex],
+    depth: &FxHashMap<NodeIndex, usize>,
+    follows: impl Fn(NodeIndex, NodeIndex) -> bool,
+    graph: &petgraph::Graph<N, E>,
+) -> Vec<NodeIndex> {
+    let mut curr = *component
+        .iter()
+        .max_by_key(|&n| (depth[n], std::cmp::Reverse(*n)))
+        .unwrap();
+    let mut chain = vec![curr];
+    while let Some(pred) = graph
+        .neighbors_directed(curr, Incoming)
+        .filter(|&p| follows(p, curr) && depth[&p] + 1 == depth[&curr])
+        .min()
+    {
+        chain.push(pred);
+        curr = pred;
+    }
+    chain.reverse();
+    chain
+}
+
+/// All simple cycles of at most [`MAX_MATCHING_LOOP_QUANTS`] quantifiers in
+/// the `triggers` graph, each starting from its smallest quantifier.
+fn quant_cycles(triggers: &FxHashMap<QuantIdx, FxHashSet<QuantIdx>>) -> Vec<Vec<QuantIdx>> {
+    fn extend(
+        triggers: &FxHashMap<QuantIdx, FxHashSet<QuantIdx>>,
+        path: &mut Vec<QuantIdx>,
+        cycles: &mut Vec<Vec<QuantIdx>>,
+    ) {
+        let Some(next) = triggers.get(path.last().unwrap()) else {
+            return;
+        };
+        let mut next: Vec<_> = next.iter().copied().collect();
+        next.sort_unstable();
+        for q in next {
+            if q == path[0] {
+                cycles.push(path.clone());
+            } else if q > path[0] && !path.contains(&q) && path.len() < MAX_MATCHING_LOOP_QUANTS {
+                path.push(q);
+                extend(triggers, path, cycles);
+                path.pop();
+            }
+        }
+    }
+    let mut starts: Vec<_> = triggers.keys().copied().collect();
+    starts.sort_unstable();
+    let mut cycles = Vec::new();
+    for start in starts {
+        extend(triggers, &mut vec![start], &mut cycles);
+    }
+    cycles
+}
+
+////////////
+// Display
+////////////
+
+impl DisplayWithCtxt<DisplayCtxt<'_>, ()> for &Generalised {
+    fn fmt_with(
+        self,
+        f: &mut fmt::Formatter<'_>,
+        ctxt: &DisplayCtxt<'_>,
+        data: &mut (),
+    ) -> fmt::Result {
+        match self {
+            Generalised::Term(term) => term.fmt_with(f, ctxt, data),
+            Generalised::App { name, children } => {
+                write!(f, "{}", &ctxt.parser.strings[*name])?;
+                if children.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "(")?;
+                for (idx, child) in children.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ", ")?;
+                    }
+                    child.fmt_with(f, ctxt, data)?;
+                }
+                write!(f, ")")
+            }
+            Generalised::Var(idx) => write!(f, "_x{idx}"),
+        }
+    }
+}
+
+/// One line per step, e.g. `q1 produces f(g(_x0)), which re-triggers q1 on
+/// g(_x0)`.
+impl DisplayWithCtxt<DisplayCtxt<'_>, ()> for &LoopExplanation {
+    fn fmt_with(
+        self,
+        f: &mut fmt::Formatter<'_>,
+        ctxt: &DisplayCtxt<'_>,
+        data: &mut (),
+    ) -> fmt::Result {
+        let name = |quant: QuantIdx| ctxt.parser[quant].kind.name(&ctxt.parser.strings);
+        for (idx, step) in self.steps.iter().enumerate() {
+            if idx != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} produces ", name(step.quant))?;
+            step.produces.fmt_with(f, ctxt, data)?;
+            let (verb, next) = match self.steps.get(idx + 1) {
+                Some(next) => ("triggers", next.quant),
+                None => ("re-triggers", self.steps[0].quant),
+            };
+            write!(f, ", which {verb} {} on ", name(next))?;
+            for (idx, term) in step.next_bound_terms.iter().enumerate() {
+                if idx != 0 {
+                    write!(f, ", ")?;
+                }
+                term.fmt_with(f, ctxt, data)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,24 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
-use super::LogParser;
+use crate::items::LinePos;
 
+use super::{
+    diagnostics::{DiagnosticKind, Diagnostics},
+    observer::{EventQueue, ParseEvent},
+    LogParser,
+};
+
+pub mod cost;
 pub mod egraph;
 pub mod inst;
 pub mod inst_graph;
+pub mod matching_loop;
+pub mod pattern_stats;
+pub mod proof;
+pub mod quant_stats;
+pub mod reachability;
+pub mod search;
 pub mod stack;
 pub mod terms;
 /// Original Z3 log parser. Works with Z3 v.4.12.1, should work with other versions
@@ -17,13 +31,14 @@ impl<T: Z3LogParser + Default> LogParser for T {
         first_byte == b'['
     }
 
-    fn process_line(&mut self, line: &str, line_no: usize) -> bool {
+    fn process_line(&mut self, line: &str, pos: LinePos) -> bool {
         // Much faster than `split_whitespace` or `split(' ')` since it works on
         // [u8] instead of [char] and so doesn't need to convert to UTF-8.
         let mut split = line.split_ascii_whitespace();
         let Some(first) = split.next() else {
             return true;
         };
+        self.begin_line(pos);
         let parse = match first {
             // match the line case
             "[tool-version]" => self.version_info(split),
@@ -50,74 +65,92 @@ impl<T: Z3LogParser + Default> LogParser for T {
             "[resolve-process]" => self.resolve_process(split),
             "[resolve-lit]" => self.resolve_lit(split),
             "[conflict]" => self.conflict(split),
-            _ => None,
+            _ => Err(DiagnosticKind::UnknownTag),
         };
-        parse.unwrap_or_else(|| {
-            if std::env::var("SLP_TEST_MODE").is_ok() {
-                panic!("Error parsing line {line_no}: {line:?}");
-            } else {
-                eprintln!("Error parsing line {line_no}: {line:?}")
-            }
-        });
+        self.diagnostics_mut().end_line(pos, line, parse);
         true
     }
 
     fn end_of_file(&mut self) {
         self.eof();
     }
+
+    fn read_error(&mut self, pos: LinePos, error: &std::io::Error) {
+        let result = Err(DiagnosticKind::ReadError);
+        self.diagnostics_mut().end_line(pos, &error.to_string(), result);
+    }
+
+    fn record_events(&mut self, record: bool) {
+        self.events_mut().set_recording(record);
+    }
+    fn take_events(&mut self, events: &mut Vec<ParseEvent>) {
+        self.events_mut().take_into(events);
+    }
 }
 
-const DEFAULT: Option<()> = Some(());
+/// The result of processing a single line, the error is recorded as a
+/// [`ParseDiagnostic`](super::diagnostics::ParseDiagnostic).
+pub type ParseResult = Result<(), DiagnosticKind>;
+
+const DEFAULT: ParseResult = Ok(());
 pub trait Z3LogParser {
+    /// Where to record problems encountered while parsing.
+    fn diagnostics_mut(&mut self) -> &mut Diagnostics;
+    /// Where to record events for any attached observers.
+    fn events_mut(&mut self) -> &mut EventQueue;
+    /// Called before each line is processed with the position of that line,
+    /// any items created while processing it should record this position.
+    fn begin_line(&mut self, pos: LinePos);
+
     /* Methods to handle each line case of Z3 logs.
      `l` is a line split with spaces as delimiters,
      and `l0` is the raw line (used only when )
     */
-    fn version_info<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn mk_quant<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn mk_var<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn mk_proof_app<'a>(&mut self, l: impl Iterator<Item = &'a str>, is_proof: bool) -> Option<()>;
-    fn attach_meaning<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn attach_var_names<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn attach_enode<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn eq_expl<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn new_match<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn inst_discovered<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn instance<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn end_of_instance<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn push<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()>;
-    fn pop<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()>;
+    fn version_info<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn mk_quant<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn mk_var<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn mk_proof_app<'a>(&mut self, l: impl Iterator<Item = &'a str>, is_proof: bool) -> ParseResult;
+    fn attach_meaning<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn attach_var_names<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn attach_enode<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn eq_expl<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn new_match<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn inst_discovered<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn instance<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn end_of_instance<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn push<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult;
+    fn pop<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult;
     fn eof(&mut self);
 
     // unused in original parser
-    fn decide_and_or<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn decide_and_or<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn decide<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn decide<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn assign<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn assign<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn begin_check<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn begin_check<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn query_done<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn query_done<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn resolve_process<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn resolve_process<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn resolve_lit<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn resolve_lit<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
-    fn conflict<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> Option<()> {
+    fn conflict<'a>(&mut self, _l: impl Iterator<Item = &'a str>) -> ParseResult {
         DEFAULT
     }
 }
 
 /// Type of solver and version number
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct VersionInfo {
     solver: String,
     version: semver::Version,
@@ -0,0 +1,142 @@
+use std::cmp::Reverse;
+
+use fxhash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+
+use crate::display_with::{DisplayCtxt, DisplayWithCtxt};
+use crate::items::{InstIdx, QuantIdx, QueryIdx, TermIdx};
+
+use super::{inst_graph::blamed_creators, z3parser::Z3Parser};
+
+/// A summary of the matches of a single quantifier pattern, to spot triggers
+/// which are too liberal.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PatternStats {
+    pub quant: QuantIdx,
+    pub name: String,
+    pub pattern: TermIdx,
+    /// The pattern as it would be printed by the CLI.
+    pub pattern_text: String,
+    pub matches: usize,
+    /// How many of the `matches` were instantiated.
+    pub instantiations: usize,
+    /// How many of the `matches` were never instantiated, see
+    /// [`Z3Parser::pending_matches`].
+    pub pending_matches: usize,
+    /// The number of distinct tuples of bound terms over all matches.
+    pub distinct_bindings: usize,
+    /// As `distinct_bindings`, but counting tuples whose terms were equal in
+    /// the e-graph at the time of the match only once.
+    pub distinct_bindings_modulo_eq: usize,
+    /// Instantiations which at least one other instantiation directly depends
+    /// on.
+    pub productive_instantiations: usize,
+    /// The number of instantiations which directly depend on an instance.
+    pub children: usize,
+    /// Matches which did not lead anywhere: they were never instantiated,
+    /// bound terms equal modulo the e-graph to an earlier match, or no other
+    /// instantiation depends on their instance. Patterns are ranked by this.
+    pub permissiveness: usize,
+}
+
+impl PatternStats {
+    /// Compute the statistics of every pattern which was matched in the log,
+    /// most permissive first.
+    pub fn compute(parser: &Z3Parser) -> Vec<Self> {
+        Self::compute_in(parser, None)
+    }
+
+    /// As [`compute`](Self::compute), but only for the matches found while
+    /// checking the query `qidx`. Only instantiations of the query count as
+    /// children.
+    pub fn compute_for_query(parser: &Z3Parser, qidx: QueryIdx) -> Vec<Self> {
+        Self::compute_in(parser, Some(qidx))
+    }
+
+    fn compute_in(parser: &Z3Parser, query: Option<QueryIdx>) -> Vec<Self> {
+        let in_scope = |iidx| query.is_none_or(|qidx| parser.query_of_inst(iidx) == Some(qidx));
+        let mut children: Vec<usize> = vec![0; parser.inst_count()];
+        let mut parents = Vec::new();
+        for idx in 0..parser.inst_count() {
+            let iidx = InstIdx::from(idx);
+            if !in_scope(iidx) {
+                continue;
+            }
+            let match_ = parser[iidx].match_;
+            parents.clear();
+            parents.extend(
+                blamed_creators(parser, &parser[match_])
+                    .into_iter()
+                    .map(|(_, _, parent)| parent)
+                    .filter(|&parent| parent < iidx && in_scope(parent)),
+            );
+            parents.sort_unstable();
+            parents.dedup();
+            for &parent in &parents {
+                children[usize::from(parent)] += 1;
+            }
+        }
+
+        let ctxt = DisplayCtxt {
+            parser,
+            display_term_ids: false,
+            display_quantifier_name: false,
+            use_mathematical_symbols: false,
+        };
+        let mut stats: Vec<Self> = Vec::new();
+        let mut by_pattern: FxHashMap<(QuantIdx, TermIdx), usize> = FxHashMap::default();
+        let mut bindings: Vec<(Bindings, Bindings)> = Vec::new();
+        for (midx, match_) in parser.matches() {
+            if query.is_some_and(|qidx| parser.query_of_match(midx) != Some(qidx)) {
+                continue;
+            }
+            let (Some(quant), Some(pattern)) = (match_.kind.quant_idx(), match_.kind.pattern())
+            else {
+                continue;
+            };
+            let idx = *by_pattern.entry((quant, pattern)).or_insert_with(|| {
+                stats.push(Self {
+                    quant,
+                    name: parser[quant].kind.name(&parser.strings).into_owned(),
+                    pattern,
+                    pattern_text: pattern.with(&ctxt).to_string(),
+                    ..Self::default()
+                });
+                bindings.push(Default::default());
+                stats.len() - 1
+            });
+            let (distinct, modulo_eq) = &mut bindings[idx];
+            let stats = &mut stats[idx];
+            stats.matches += 1;
+
+            let terms = match_.kind.bound_terms(|enode| parser[enode].owner, |term| term);
+            distinct.insert(terms);
+            let roots = match_.kind.bound_terms(
+                |enode| parser[parser.root_at(enode, match_.pos)].owner,
+                |term| term,
+            );
+            let redundant = !modulo_eq.insert(roots);
+
+            let inst = parser.instance_of(midx);
+            let productive = inst.is_some_and(|inst| children[usize::from(inst)] != 0);
+            if let Some(inst) = inst {
+                stats.instantiations += 1;
+                stats.children += children[usize::from(inst)];
+            } else {
+                stats.pending_matches += 1;
+            }
+            stats.productive_instantiations += usize::from(productive);
+            stats.permissiveness += usize::from(redundant || !productive);
+        }
+        for (stats, (distinct, modulo_eq)) in stats.iter_mut().zip(bindings) {
+            stats.distinct_bindings = distinct.len();
+            stats.distinct_bindings_modulo_eq = modulo_eq.len();
+        }
+        stats.sort_by_key(|stats| (Reverse(stats.permissiveness), Reverse(stats.matches)));
+        stats
+    }
+}
+
+/// The distinct tuples of bound terms of a pattern's matches.
+type Bindings = FxHashSet<Vec<TermIdx>>;
+
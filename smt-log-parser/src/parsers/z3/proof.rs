@@ -0,0 +1,236 @@
+use fxhash::FxHashMap;
+use petgraph::{
+    graph::{Graph, NodeIndex},
+    visit::{Dfs, Reversed},
+    Direction::Incoming,
+};
+use std::fmt;
+use typed_index_collections::TiVec;
+
+use crate::items::{InstIdx, ProofOrApp, TermIdx, TermKind};
+
+use super::z3parser::Z3Parser;
+
+/// The inference rule of a `[mk-proof]` step, see `design-docs/mk-proof.md`
+/// and Z3's `basic_decl_plugin.cpp` for the names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProofRule {
+    TrueAxiom,
+    Asserted,
+    Goal,
+    Mp,
+    MpTilde,
+    Refl,
+    Symm,
+    Trans,
+    TransStar,
+    Monotonicity,
+    QuantIntro,
+    Bind,
+    Distributivity,
+    AndElim,
+    NotOrElim,
+    Rewrite,
+    RewriteStar,
+    PullQuant,
+    PushQuant,
+    ElimUnused,
+    DerElim,
+    QuantInst,
+    Hypothesis,
+    Lemma,
+    UnitResolution,
+    IffTrue,
+    IffFalse,
+    Commutativity,
+    DefAxiom,
+    AssumptionAdd,
+    LemmaAdd,
+    RedundantDel,
+    ClauseTrail,
+    DefIntro,
+    ApplyDef,
+    IffOeq,
+    NnfPos,
+    NnfNeg,
+    SkNnf,
+    ThLemma,
+    HyperRes,
+    /// A rule we do not know about.
+    Other,
+}
+
+impl ProofRule {
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "true-axiom" => Self::TrueAxiom,
+            "asserted" => Self::Asserted,
+            "goal" => Self::Goal,
+            "mp" => Self::Mp,
+            "mp~" => Self::MpTilde,
+            "refl" => Self::Refl,
+            "symm" => Self::Symm,
+            "trans" => Self::Trans,
+            "trans*" => Self::TransStar,
+            "monotonicity" => Self::Monotonicity,
+            "quant-intro" => Self::QuantIntro,
+            "proof-bind" => Self::Bind,
+            "distributivity" => Self::Distributivity,
+            "and-elim" => Self::AndElim,
+            "not-or-elim" => Self::NotOrElim,
+            "rewrite" => Self::Rewrite,
+            "rewrite*" => Self::RewriteStar,
+            "pull-quant" => Self::PullQuant,
+            "push-quant" => Self::PushQuant,
+            "elim-unused" => Self::ElimUnused,
+            "der" => Self::DerElim,
+            "quant-inst" => Self::QuantInst,
+            "hypothesis" => Self::Hypothesis,
+            "lemma" => Self::Lemma,
+            "unit-resolution" => Self::UnitResolution,
+            "iff-true" => Self::IffTrue,
+            "iff-false" => Self::IffFalse,
+            "commutativity" => Self::Commutativity,
+            "def-axiom" => Self::DefAxiom,
+            "add-assume" => Self::AssumptionAdd,
+            "add-lemma" => Self::LemmaAdd,
+            "del-redundant" => Self::RedundantDel,
+            "proof-trail" => Self::ClauseTrail,
+            "intro-def" => Self::DefIntro,
+            "apply-def" => Self::ApplyDef,
+            "iff~" => Self::IffOeq,
+            "nnf-pos" => Self::NnfPos,
+            "nnf-neg" => Self::NnfNeg,
+            "sk" => Self::SkNnf,
+            "th-lemma" => Self::ThLemma,
+            "hyper-res" => Self::HyperRes,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for ProofRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A single proof step.
+#[derive(Debug, Clone)]
+pub struct ProofStep {
+    /// The `[mk-proof]` term of this step.
+    pub term: TermIdx,
+    pub rule: ProofRule,
+    /// The (non-proof) term proven by this step.
+    pub conclusion: Option<TermIdx>,
+}
+
+/// The DAG of proof steps logged with `[mk-proof]`. Edges go from each premise
+/// to the steps which use it.
+#[derive(Debug, Default)]
+pub struct ProofGraph {
+    pub graph: Graph<ProofStep, ()>,
+    node_of_term: FxHashMap<TermIdx, NodeIndex>,
+    /// The `quant-inst` step of each instantiation (if any).
+    inst_steps: TiVec<InstIdx, Option<NodeIndex>>,
+    /// Steps which conclude `false`, i.e. the ends of final proofs.
+    roots: Vec<NodeIndex>,
+}
+
+impl ProofGraph {
+    pub fn from(parser: &Z3Parser) -> Self {
+        let mut proof_graph = Self::default();
+        for (tidx, term) in parser.terms.iter() {
+            let TermKind::ProofOrApp(ProofOrApp {
+                is_proof: true,
+                name,
+            }) = term.kind
+            else {
+                continue;
+            };
+            // The last child is the conclusion, any other children are
+            // premises (i.e. proof terms themselves).
+            let conclusion = term
+                .child_ids
+                .last()
+                .copied()
+                .filter(|&c| !is_proof(parser, c));
+            let rule = ProofRule::parse(&parser.strings[name]);
+            let node = proof_graph.graph.add_node(ProofStep {
+                term: tidx,
+                rule,
+                conclusion,
+            });
+            proof_graph.node_of_term.insert(tidx, node);
+            for premise in term.child_ids.iter() {
+                // Premises must have been created before the step.
+                if let Some(&premise) = proof_graph.node_of_term.get(premise) {
+                    proof_graph.graph.add_edge(premise, node, ());
+                }
+            }
+            let is_false = conclusion
+                .and_then(|c| parser[c].kind.app_name())
+                .is_some_and(|name| &parser.strings[name] == "false");
+            if is_false {
+                proof_graph.roots.push(node);
+            }
+        }
+        proof_graph.inst_steps = parser
+            .insts
+            .insts
+            .iter()
+            .map(|inst| {
+                let step = proof_graph.node_of_term.get(&inst.get_resulting_term()?)?;
+                (proof_graph.graph[*step].rule == ProofRule::QuantInst).then_some(*step)
+            })
+            .collect();
+        proof_graph
+    }
+
+    /// The proof step for a `[mk-proof]` term.
+    pub fn step_of_term(&self, term: TermIdx) -> Option<NodeIndex> {
+        self.node_of_term.get(&term).copied()
+    }
+    /// The `quant-inst` proof step of the instantiation `iidx`.
+    pub fn inst_step(&self, iidx: InstIdx) -> Option<NodeIndex> {
+        self.inst_steps.get(iidx).copied().flatten()
+    }
+    /// The steps which conclude `false`, these are the final steps of a proof
+    /// of unsatisfiability.
+    pub fn roots(&self) -> &[NodeIndex] {
+        &self.roots
+    }
+    /// All steps which are (transitively) premises of one of the
+    /// [`roots`](Self::roots), i.e. which are used in a final proof.
+    pub fn used_steps(&self) -> Vec<bool> {
+        let mut used = vec![false; self.graph.node_count()];
+        let reversed = Reversed(&self.graph);
+        let mut dfs = Dfs::empty(reversed);
+        for &root in &self.roots {
+            dfs.move_to(root);
+            while let Some(node) = dfs.next(reversed) {
+                used[node.index()] = true;
+            }
+        }
+        used
+    }
+    /// The instantiations whose `quant-inst` step is used in a final proof.
+    pub fn used_insts(&self) -> Vec<InstIdx> {
+        let used = self.used_steps();
+        self.inst_steps
+            .iter_enumerated()
+            .filter_map(|(iidx, step)| step.filter(|s| used[s.index()]).map(|_| iidx))
+            .collect()
+    }
+    /// The premises of a proof step.
+    pub fn premises(&self, step: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.graph.neighbors_directed(step, Incoming)
+    }
+}
+
+fn is_proof(parser: &Z3Parser, term: TermIdx) -> bool {
+    matches!(
+        parser[term].kind,
+        TermKind::ProofOrApp(ProofOrApp { is_proof: true, .. })
+    )
+}
@@ -0,0 +1,151 @@
+use serde::Serialize;
+use typed_index_collections::TiVec;
+
+use crate::items::{InstIdx, QuantIdx, QueryIdx};
+use crate::parsers::cvc5::cvc5parser::Cvc5Parser;
+
+use super::{inst_graph::blamed_creators, z3parser::Z3Parser};
+
+/// A summary of the matches and instantiations of a single quantifier.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QuantifierStats {
+    pub quant: QuantIdx,
+    pub name: String,
+    pub matches: usize,
+    pub instantiations: usize,
+    /// Matches which were never instantiated.
+    pub unused_matches: usize,
+    /// See [`Quantifier::cost`](crate::items::Quantifier::cost). For a single
+    /// query this is the total cost of the instances made during it instead.
+    pub cost: f32,
+    pub max_generation: Option<u32>,
+    pub mean_generation: Option<f32>,
+    /// The longest chain of instantiations ending in an instance of this
+    /// quantifier, as [`NodeData::max_depth`](super::inst_graph::NodeData::max_depth)
+    /// in the instantiation graph.
+    pub max_depth: usize,
+    /// The number of enodes created by instances.
+    pub yielded_enodes: usize,
+    /// The number of instantiations which directly depend on an instance.
+    pub children: usize,
+    /// How many of the `children` are not instances of this quantifier.
+    pub foreign_children: usize,
+}
+
+impl QuantifierStats {
+    /// Compute the statistics of every quantifier in the log.
+    pub fn compute(parser: &Z3Parser) -> TiVec<QuantIdx, Self> {
+        Self::compute_in(parser, None)
+    }
+
+    /// Compute the statistics of every quantifier, counting only the matches
+    /// and instantiations made while checking the query `qidx`. Depths and
+    /// children only follow dependencies between instantiations of the query.
+    pub fn compute_for_query(parser: &Z3Parser, qidx: QueryIdx) -> TiVec<QuantIdx, Self> {
+        Self::compute_in(parser, Some(qidx))
+    }
+
+    /// Compute the statistics of every quantifier in a cvc5 log, optionally
+    /// only for the instantiations of the query `query`. cvc5 does not report
+    /// what triggered an instantiation, so every match was instantiated and
+    /// there are no dependencies between instantiations.
+    pub fn compute_cvc5(parser: &Cvc5Parser, query: Option<QueryIdx>) -> TiVec<QuantIdx, Self> {
+        let insts = query.map(|qidx| parser.queries()[qidx].insts.clone());
+        let in_scope = |iidx: &&InstIdx| insts.as_ref().is_none_or(|insts| insts.contains(iidx));
+        parser
+            .quantifiers()
+            .iter_enumerated()
+            .map(|(quant, q)| {
+                let instances = q.instances.iter().filter(in_scope);
+                let cost = instances.clone().map(|&iidx| parser[iidx].cost).sum();
+                let instantiations = instances.count();
+                Self {
+                    quant,
+                    name: q.kind.name(&parser.strings).into_owned(),
+                    matches: instantiations,
+                    instantiations,
+                    cost,
+                    ..Self::default()
+                }
+            })
+            .collect()
+    }
+
+    fn compute_in(parser: &Z3Parser, query: Option<QueryIdx>) -> TiVec<QuantIdx, Self> {
+        let in_scope = |iidx| query.is_none_or(|qidx| parser.query_of_inst(iidx) == Some(qidx));
+        let (quant_count, _) = parser.quant_count_incl_theory_solving();
+        let mut stats: TiVec<QuantIdx, Self> = (0..quant_count)
+            .map(QuantIdx::from)
+            .map(|quant| Self {
+                quant,
+                name: parser[quant].kind.name(&parser.strings).into_owned(),
+                cost: if query.is_none() { parser[quant].cost } else { 0.0 },
+                ..Self::default()
+            })
+            .collect();
+        let mut generations: TiVec<QuantIdx, (u64, usize)> = vec![(0, 0); quant_count].into();
+        let mut depths: TiVec<InstIdx, usize> = TiVec::with_capacity(parser.inst_count());
+        let mut parents = Vec::new();
+        for idx in 0..parser.inst_count() {
+            let iidx = InstIdx::from(idx);
+            let inst = &parser[iidx];
+            let match_ = &parser[inst.match_];
+            let quant = match_.kind.quant_idx();
+            if !in_scope(iidx) {
+                depths.push(0);
+                continue;
+            }
+
+            parents.clear();
+            parents.extend(
+                blamed_creators(parser, match_)
+                    .into_iter()
+                    .map(|(_, _, parent)| parent)
+                    .filter(|&parent| parent < iidx && in_scope(parent)),
+            );
+            parents.sort_unstable();
+            parents.dedup();
+            let depth = parents.iter().map(|&parent| depths[parent] + 1).max();
+            depths.push(depth.unwrap_or_default());
+            for &parent in &parents {
+                let parent_quant = parser[parser[parent].match_].kind.quant_idx();
+                if let Some(parent_quant) = parent_quant {
+                    stats[parent_quant].children += 1;
+                    if quant != Some(parent_quant) {
+                        stats[parent_quant].foreign_children += 1;
+                    }
+                }
+            }
+
+            let Some(quant) = quant else {
+                continue;
+            };
+            let stats = &mut stats[quant];
+            stats.instantiations += 1;
+            if query.is_some() {
+                stats.cost += inst.cost;
+            }
+            stats.yielded_enodes += inst.yields_terms.len();
+            stats.max_depth = stats.max_depth.max(depths[iidx]);
+            if let Some(generation) = inst.z3_generation {
+                stats.max_generation = stats.max_generation.max(Some(generation));
+                let (sum, count) = &mut generations[quant];
+                *sum += u64::from(generation);
+                *count += 1;
+            }
+        }
+        for (midx, match_) in parser.matches() {
+            if query.is_some_and(|qidx| parser.query_of_match(midx) != Some(qidx)) {
+                continue;
+            }
+            if let Some(quant) = match_.kind.quant_idx() {
+                stats[quant].matches += 1;
+                stats[quant].unused_matches += usize::from(parser.instance_of(midx).is_none());
+            }
+        }
+        for (stats, (sum, count)) in stats.iter_mut().zip(generations) {
+            stats.mean_generation = (count != 0).then(|| sum as f32 / count as f32);
+        }
+        stats
+    }
+}
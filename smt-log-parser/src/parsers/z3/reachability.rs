@@ -0,0 +1,193 @@
+use fxhash::FxHashSet;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction::Incoming;
+use petgraph::Graph;
+use roaring::RoaringBitmap;
+
+/// Answers whether one node of a DAG can reach another. Nodes must be added
+/// in a topological order, with all of their parents already in the graph and
+/// index, as is the case for instantiations.
+pub trait ReachabilityIndex: Default {
+    /// Add `node`, which must be the node following the last one added.
+    fn add_node<N, E>(&mut self, graph: &Graph<N, E>, node: NodeIndex);
+    /// Whether there is a path from `from` to `to`. Every node reaches itself.
+    fn reaches<N, E>(&self, graph: &Graph<N, E>, from: NodeIndex, to: NodeIndex) -> bool;
+    /// An estimate of the memory used by the index, in bytes.
+    fn heap_size(&self) -> usize;
+}
+
+/// The full transitive closure, storing the ancestors of each node. Queries
+/// are a single lookup, but memory grows quadratically with long chains.
+#[derive(Debug, Clone, Default)]
+pub struct TransitiveClosure {
+    ancestors: Vec<RoaringBitmap>,
+}
+
+impl ReachabilityIndex for TransitiveClosure {
+    fn add_node<N, E>(&mut self, graph: &Graph<N, E>, node: NodeIndex) {
+        debug_assert_eq!(node.index(), self.ancestors.len());
+        let mut ancestors = RoaringBitmap::new();
+        ancestors.insert(node.index() as u32);
+        for parent in graph.neighbors_directed(node, Incoming) {
+            ancestors |= &self.ancestors[parent.index()];
+        }
+        self.ancestors.push(ancestors);
+    }
+    fn reaches<N, E>(&self, _graph: &Graph<N, E>, from: NodeIndex, to: NodeIndex) -> bool {
+        self.ancestors[to.index()].contains(from.index() as u32)
+    }
+    fn heap_size(&self) -> usize {
+        let bitmaps = self.ancestors.iter().map(|a| a.serialized_size()).sum::<usize>();
+        bitmaps + self.ancestors.capacity() * std::mem::size_of::<RoaringBitmap>()
+    }
+}
+
+/// The number of random orders used by [`IntervalLabels`].
+const ORDERS: usize = 4;
+
+/// GRAIL-style interval labels: each node is labelled with the smallest and
+/// largest key of its ancestors in a few random orders, along with its depth.
+/// If `from` reaches `to` its ancestors are a subset of those of `to`, so its
+/// intervals are contained in those of `to` and it is less deep. Labels only
+/// depend on the parents of a node and so never change once added.
+///
+/// Additionally each node keeps one of its parents, forming a spanning
+/// forest with skew-binary jump pointers, so that reachability along the
+/// forest (e.g. along a matching loop) is found in logarithmic time.
+///
+/// Queries which neither rules out nor confirms are answered by a search from
+/// `to` towards the roots, which skips any node the labels rule out. Memory
+/// is linear in the number of nodes.
+#[derive(Debug, Clone, Default)]
+pub struct IntervalLabels {
+    labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Label {
+    /// The length of the longest path from a root to the node.
+    depth: u32,
+    intervals: [(u32, u32); ORDERS],
+    /// The deepest parent in the spanning forest, `None` for roots.
+    tree_parent: Option<NodeIndex>,
+    /// An ancestor in the forest, see [`IntervalLabels::is_tree_ancestor`].
+    jump: NodeIndex,
+    tree_depth: u32,
+}
+
+impl Label {
+    fn new(node: NodeIndex) -> Self {
+        let intervals = std::array::from_fn(|order| {
+            let key = key(node, order);
+            (key, key)
+        });
+        Self {
+            depth: 0,
+            intervals,
+            tree_parent: None,
+            jump: node,
+            tree_depth: 0,
+        }
+    }
+    /// Whether the node labelled `self` could reach the node labelled `to`.
+    fn may_reach(&self, to: &Self) -> bool {
+        self.depth < to.depth
+            && self
+                .intervals
+                .iter()
+                .zip(&to.intervals)
+                .all(|(&(min, max), &(to_min, to_max))| to_min <= min && max <= to_max)
+    }
+}
+
+/// The position of `node` in the random order `order`.
+fn key(node: NodeIndex, order: usize) -> u32 {
+    // splitmix64, seeded by the order
+    let mut z = (node.index() as u64)
+        .wrapping_add((order as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)) as u32
+}
+
+impl IntervalLabels {
+    /// Whether `from` is `to` or one of its ancestors in the spanning forest.
+    /// The jump pointers skip to an ancestor such that any depth can be
+    /// reached in a logarithmic number of steps (Myers, 1983).
+    fn is_tree_ancestor(&self, from: NodeIndex, to: NodeIndex) -> bool {
+        let depth = self.labels[from.index()].tree_depth;
+        let mut node = to;
+        while self.labels[node.index()].tree_depth > depth {
+            let label = &self.labels[node.index()];
+            node = if self.labels[label.jump.index()].tree_depth >= depth {
+                label.jump
+            } else {
+                label.tree_parent.unwrap()
+            };
+        }
+        node == from
+    }
+}
+
+impl ReachabilityIndex for IntervalLabels {
+    fn add_node<N, E>(&mut self, graph: &Graph<N, E>, node: NodeIndex) {
+        debug_assert_eq!(node.index(), self.labels.len());
+        let mut label = Label::new(node);
+        for parent in graph.neighbors_directed(node, Incoming) {
+            let parent = &self.labels[parent.index()];
+            label.depth = label.depth.max(parent.depth + 1);
+            for (interval, parent) in label.intervals.iter_mut().zip(&parent.intervals) {
+                interval.0 = interval.0.min(parent.0);
+                interval.1 = interval.1.max(parent.1);
+            }
+        }
+        let tree_parent = graph
+            .neighbors_directed(node, Incoming)
+            .max_by_key(|parent| self.labels[parent.index()].tree_depth);
+        if let Some(parent) = tree_parent {
+            let depth = |node: NodeIndex| self.labels[node.index()].tree_depth;
+            let jump = self.labels[parent.index()].jump;
+            let jump_jump = self.labels[jump.index()].jump;
+            label.tree_parent = Some(parent);
+            label.tree_depth = depth(parent) + 1;
+            label.jump = if depth(parent) - depth(jump) == depth(jump) - depth(jump_jump) {
+                jump_jump
+            } else {
+                parent
+            };
+        }
+        self.labels.push(label);
+    }
+    fn reaches<N, E>(&self, graph: &Graph<N, E>, from: NodeIndex, to: NodeIndex) -> bool {
+        if from == to {
+            return true;
+        }
+        // Nodes are added in topological order, so only later nodes can be
+        // reached.
+        let may_reach = |node: NodeIndex| {
+            from < node && self.labels[from.index()].may_reach(&self.labels[node.index()])
+        };
+        if !may_reach(to) {
+            return false;
+        }
+        let mut visited = FxHashSet::default();
+        let mut stack = vec![to];
+        while let Some(node) = stack.pop() {
+            if self.is_tree_ancestor(from, node) {
+                return true;
+            }
+            for parent in graph.neighbors_directed(node, Incoming) {
+                if parent == from {
+                    return true;
+                }
+                if may_reach(parent) && visited.insert(parent) {
+                    stack.push(parent);
+                }
+            }
+        }
+        false
+    }
+    fn heap_size(&self) -> usize {
+        self.labels.capacity() * std::mem::size_of::<Label>()
+    }
+}
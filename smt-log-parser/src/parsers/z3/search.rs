@@ -0,0 +1,183 @@
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use typed_index_collections::TiVec;
+
+use crate::items::{EventIdx, IString, LinePos, StackIdx, TermIdx};
+
+use super::stack::Stack;
+
+/// A boolean literal of the SAT solver, either a constant or an assignment of
+/// a value to a (boolean) term. Logged as `true`, `false`, `#12` or
+/// `(not #12)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Literal {
+    /// `None` for the constants `true` and `false`.
+    pub term: Option<TermIdx>,
+    pub value: bool,
+}
+
+/// Why a literal was assigned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JustificationKind {
+    Axiom,
+    /// Unit propagation of a binary clause.
+    Binary,
+    /// Unit propagation of a clause.
+    Clause,
+    /// Propagated by a theory solver.
+    Theory,
+    Unknown(IString),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Justification {
+    pub kind: JustificationKind,
+    /// The other literals involved (e.g. the rest of the clause).
+    pub lits: Box<[Literal]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchEventKind {
+    /// `[decide-and-or]`: case split on a child of an `and`/`or` term.
+    DecideAndOr { parent: TermIdx, child: TermIdx },
+    /// `[decide]`: the solver chose a value for a literal.
+    Decide { lit: Literal },
+    /// `[assign]`: a literal was assigned, either as a decision or due to
+    /// propagation.
+    Assign {
+        lit: Literal,
+        decision: bool,
+        justification: Justification,
+    },
+    /// `[conflict]`: the current assignment is inconsistent with the given
+    /// literals. `backjump` is the number of stack frames subsequently popped
+    /// to resolve the conflict.
+    Conflict {
+        lits: Box<[Literal]>,
+        backjump: usize,
+    },
+    /// `[resolve-lit]`: a literal used in conflict resolution, `level_delta`
+    /// decision levels below the conflict.
+    ResolveLit { level_delta: usize, lit: Literal },
+    /// `[resolve-process]`: a literal whose justification is being resolved.
+    ResolveProcess { lit: Literal },
+}
+
+/// A single step of the CDCL search.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchEvent {
+    pub kind: SearchEventKind,
+    /// The stack frame active when this happened.
+    pub frame: Option<StackIdx>,
+    /// The height of the stack (i.e. the decision level) when this happened.
+    pub level: usize,
+    pub pos: LinePos,
+}
+
+/// The timeline of decisions, assignments and conflicts of the SAT-level
+/// search.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Search {
+    events: TiVec<EventIdx, SearchEvent>,
+    /// The most recent conflict, if no decisions have been made since. Any
+    /// pops are attributed to this conflict.
+    last_conflict: Option<EventIdx>,
+}
+
+impl Search {
+    pub(super) fn new_event(
+        &mut self,
+        kind: SearchEventKind,
+        pos: LinePos,
+        stack: &Stack,
+    ) -> EventIdx {
+        let is_conflict = matches!(kind, SearchEventKind::Conflict { .. });
+        let is_decision = matches!(
+            kind,
+            SearchEventKind::Decide { .. }
+                | SearchEventKind::DecideAndOr { .. }
+                | SearchEventKind::Assign { decision: true, .. }
+        );
+        let idx = self.events.push_and_get_key(SearchEvent {
+            kind,
+            frame: stack.active_frame(),
+            level: stack.height(),
+            pos,
+        });
+        if is_conflict {
+            self.last_conflict = Some(idx);
+        } else if is_decision {
+            self.last_conflict = None;
+        }
+        idx
+    }
+
+    /// Called when `count` stack frames are popped.
+    pub(super) fn pop(&mut self, count: usize) {
+        if let Some(conflict) = self.last_conflict {
+            if let SearchEventKind::Conflict { backjump, .. } = &mut self.events[conflict].kind {
+                *backjump += count;
+            }
+        }
+    }
+
+    pub fn events(&self) -> &TiVec<EventIdx, SearchEvent> {
+        &self.events
+    }
+    pub fn decisions(&self) -> impl Iterator<Item = &SearchEvent> + '_ {
+        self.events.iter().filter(|e| {
+            matches!(
+                e.kind,
+                SearchEventKind::Decide { .. } | SearchEventKind::DecideAndOr { .. }
+            )
+        })
+    }
+    pub fn conflicts(&self) -> impl Iterator<Item = &SearchEvent> + '_ {
+        self.events
+            .iter()
+            .filter(|e| matches!(e.kind, SearchEventKind::Conflict { .. }))
+    }
+    /// The total number of stack frames popped due to conflicts.
+    pub fn total_backjump(&self) -> usize {
+        self.conflicts()
+            .map(|e| match e.kind {
+                SearchEventKind::Conflict { backjump, .. } => backjump,
+                _ => unreachable!(),
+            })
+            .sum()
+    }
+    /// The deepest decision level reached.
+    pub fn max_level(&self) -> usize {
+        self.events
+            .iter()
+            .map(|e| e.level)
+            .max()
+            .unwrap_or_default()
+    }
+    /// How often each term appeared in a conflict or in its resolution, most
+    /// frequent first. These are the literals which drove the backtracking.
+    pub fn conflict_terms(&self) -> Vec<(TermIdx, usize)> {
+        let mut counts = FxHashMap::<TermIdx, usize>::default();
+        for event in self.events.iter() {
+            let lits = match &event.kind {
+                SearchEventKind::Conflict { lits, .. } => lits,
+                SearchEventKind::ResolveLit { lit, .. }
+                | SearchEventKind::ResolveProcess { lit } => std::slice::from_ref(lit),
+                _ => continue,
+            };
+            for term in lits.iter().filter_map(|lit| lit.term) {
+                *counts.entry(term).or_default() += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+}
+
+impl std::ops::Index<EventIdx> for Search {
+    type Output = SearchEvent;
+    fn index(&self, idx: EventIdx) -> &Self::Output {
+        &self.events[idx]
+    }
+}
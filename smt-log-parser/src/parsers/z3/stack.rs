@@ -1,31 +1,36 @@
+use serde::{Deserialize, Serialize};
 use typed_index_collections::TiVec;
 
-use crate::items::StackIdx;
+use crate::items::{LinePos, StackIdx};
+use crate::parsers::diagnostics::DiagnosticKind;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Stack {
     pub(super) stack: Vec<StackIdx>,
     pub(super) stack_frames: TiVec<StackIdx, StackFrame>,
 }
 
 impl Stack {
-    fn add_frame(&mut self) {
-        let idx = self.stack_frames.push_and_get_key(StackFrame::new());
+    fn add_frame(&mut self, pos: LinePos) {
+        let idx = self.stack_frames.push_and_get_key(StackFrame::new(pos));
         self.stack.push(idx);
     }
-    fn remove_frame(&mut self, active: bool) -> Option<StackIdx> {
+    fn remove_frame(&mut self, active: bool, pos: LinePos) -> Option<StackIdx> {
         let idx = self.stack.pop()?;
-        self.stack_frames[idx].active = active;
+        let frame = &mut self.stack_frames[idx];
+        frame.active = active;
+        if !active {
+            frame.popped = Some(pos);
+        }
         Some(idx)
     }
-    #[must_use]
-    fn ensure_height(&mut self, height: usize) -> Option<()> {
-        let mut res = Some(());
+    fn ensure_height(&mut self, height: usize, pos: LinePos) -> Result<(), DiagnosticKind> {
+        let mut res = Ok(());
         // Neither condition should hold, but handle it as best we can.
         while height > self.stack.len() {
             // Have not run into this case, so make tests fail if it happens.
-            res = None;
-            self.add_frame();
+            res = Err(DiagnosticKind::StackMismatch);
+            self.add_frame(pos);
         }
         while height < self.stack.len() {
             // This can happen when pushing a new frame in e.g. z3 v4.8.17 and
@@ -33,40 +38,74 @@ impl Stack {
             // It seems that there is a bug where the pop doesn't get emitted
             // and so we need to conservatively leak the frame and treat it as
             // always active.
-            self.remove_frame(true);
+            self.remove_frame(true, pos);
         }
         res
     }
 
-    #[must_use]
-    pub(super) fn new_frame(&mut self, idx: usize) -> Option<()> {
-        let res = self.ensure_height(idx);
-        self.add_frame();
+    pub(super) fn new_frame(&mut self, idx: usize, pos: LinePos) -> Result<(), DiagnosticKind> {
+        let res = self.ensure_height(idx, pos);
+        self.add_frame(pos);
         res
     }
 
-    #[must_use]
-    pub(super) fn pop_frames(&mut self, count: usize, idx: usize) -> Option<()> {
-        debug_assert!(0 < count && count <= idx);
-        let res = self.ensure_height(idx);
+    pub(super) fn pop_frames(&mut self, count: usize, idx: usize, pos: LinePos) -> Result<(), DiagnosticKind> {
+        if count == 0 || idx < count {
+            return Err(DiagnosticKind::StackMismatch);
+        }
+        let res = self.ensure_height(idx, pos);
         for _ in 0..count {
-            self.remove_frame(false)?;
+            self.remove_frame(false, pos)
+                .ok_or(DiagnosticKind::StackMismatch)?;
         }
         res
     }
 
+    /// Is the frame currently on the stack? `None` is the base frame.
+    pub fn is_active(&self, frame: Option<StackIdx>) -> bool {
+        frame.is_none_or(|f| self.stack_frames[f].active)
+    }
+
+    /// Was the frame on the stack just after the line at `pos` was parsed?
+    /// `None` is the base frame.
+    pub fn is_active_at(&self, frame: Option<StackIdx>, pos: LinePos) -> bool {
+        frame.is_none_or(|f| {
+            let frame = &self.stack_frames[f];
+            frame.pushed <= pos && frame.popped.is_none_or(|popped| pos < popped)
+        })
+    }
+
+    /// Would moving to a stack of the given height require leaking frames
+    /// (i.e. is there a missing `[pop]`)?
+    pub(super) fn is_above(&self, height: usize) -> bool {
+        height < self.stack.len()
+    }
+
+    pub(super) fn height(&self) -> usize {
+        self.stack.len()
+    }
+
     pub(super) fn active_frame(&self) -> Option<StackIdx> {
         self.stack.last().copied()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StackFrame {
     pub active: bool,
+    /// The `[push]` line which created this frame.
+    pub pushed: LinePos,
+    /// The `[pop]` line which removed this frame, `None` while it is active
+    /// (or if it was leaked, see `Stack::ensure_height`).
+    pub popped: Option<LinePos>,
 }
 
 impl StackFrame {
-    pub fn new() -> Self {
-        Self { active: true }
+    pub fn new(pushed: LinePos) -> Self {
+        Self {
+            active: true,
+            pushed,
+            popped: None,
+        }
     }
 }
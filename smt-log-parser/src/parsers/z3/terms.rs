@@ -1,13 +1,18 @@
 use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
 use typed_index_collections::TiVec;
 
 use crate::items::{Term, TermId, TermIdToIdxMap, TermIdx, StringTable, Meaning};
+use crate::parsers::diagnostics::DiagnosticKind;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Terms {
     term_id_map: TermIdToIdxMap,
     terms: TiVec<TermIdx, Term>,
-    meanings: FxHashMap<TermIdx, Meaning>
+    meanings: FxHashMap<TermIdx, Meaning>,
+    /// The `[mk-proof]` steps concluding `a = b`, keyed by `a` and `b` (in
+    /// ascending order).
+    proved_equalities: FxHashMap<(TermIdx, TermIdx), TermIdx>,
 }
 
 impl Terms {
@@ -16,6 +21,7 @@ impl Terms {
             term_id_map: TermIdToIdxMap::new(strings),
             terms: TiVec::new(),
             meanings: FxHashMap::default(),
+            proved_equalities: FxHashMap::default(),
         }
     }
 
@@ -26,32 +32,51 @@ impl Terms {
         idx
     }
 
-    #[must_use]
     pub(super) fn parse_id(
         &self,
         strings: &mut StringTable,
         id: &str,
-    ) -> Option<Result<TermIdx, TermId>> {
-        let term_id = TermId::parse(strings, id)?;
-        Some(self.term_id_map.get_term(&term_id).ok_or_else(|| term_id))
+    ) -> Result<Result<TermIdx, TermId>, DiagnosticKind> {
+        let term_id = TermId::parse(strings, id).ok_or(DiagnosticKind::MalformedId)?;
+        Ok(self.term_id_map.get_term(&term_id).ok_or(term_id))
     }
-    #[must_use]
-    pub(super) fn parse_existing_id(&self, strings: &mut StringTable, id: &str) -> Option<TermIdx> {
-        self.parse_id(strings, id).and_then(|r| r.ok())
+    pub(super) fn parse_existing_id(
+        &self,
+        strings: &mut StringTable,
+        id: &str,
+    ) -> Result<TermIdx, DiagnosticKind> {
+        self.parse_id(strings, id)?
+            .map_err(|_| DiagnosticKind::UnknownId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TermIdx, &Term)> + '_ {
+        self.terms.iter_enumerated()
     }
 
     pub fn meaning(&self, tidx: TermIdx) -> Option<&Meaning> {
         self.meanings.get(&tidx)
     }
 
-    pub(super) fn new_meaning(&mut self, term: TermIdx, meaning: Meaning) {
+    /// Record that the proof step `proof` concludes `lhs = rhs`.
+    pub(super) fn new_proved_equality(&mut self, proof: TermIdx, lhs: TermIdx, rhs: TermIdx) {
+        self.proved_equalities.insert((lhs.min(rhs), lhs.max(rhs)), proof);
+    }
+
+    /// A proof step concluding `lhs = rhs` (or `rhs = lhs`), if there is one.
+    pub fn proved_equality(&self, lhs: TermIdx, rhs: TermIdx) -> Option<TermIdx> {
+        self.proved_equalities.get(&(lhs.min(rhs), lhs.max(rhs))).copied()
+    }
+
+    pub(super) fn new_meaning(&mut self, term: TermIdx, meaning: Meaning) -> Result<(), DiagnosticKind> {
         use std::collections::hash_map::Entry;
         match self.meanings.entry(term) {
-            Entry::Occupied(old) => assert_eq!(old.get(), &meaning),
+            Entry::Occupied(old) if old.get() != &meaning => return Err(DiagnosticKind::Redefinition),
+            Entry::Occupied(_) => (),
             Entry::Vacant(empty) => {
                 empty.insert(meaning);
             },
         };
+        Ok(())
     }
 }
 
@@ -1,20 +1,34 @@
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use typed_index_collections::TiVec;
 
 use crate::{
     items::*,
-    parsers::z3::{VersionInfo, Z3LogParser},
+    parsers::{
+        cache::{self, CacheError},
+        diagnostics::{DiagnosticKind, Diagnostics},
+        observer::{EventQueue, ParseEvent},
+        z3::{ParseResult, VersionInfo, Z3LogParser},
+    },
 };
 
 use super::{
+    cost::CostModel,
     egraph::{EGraph, ENode},
     inst::Insts,
+    search::{Justification, JustificationKind, Literal, Search, SearchEventKind},
     stack::Stack,
     terms::Terms,
 };
 
+/// How deep to look for congruences when explaining an equality which Z3 did
+/// not explain, see `Z3Parser::explain_equality`.
+const MAX_CONGRUENCE_DEPTH: usize = 8;
+
 /// A parser for Z3 log files. Use one of the various `Z3Parser::from_*` methods
 /// to construct this parser.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Z3Parser {
     pub(super) version_info: Option<VersionInfo>,
     pub(super) terms: Terms,
@@ -26,6 +40,18 @@ pub struct Z3Parser {
 
     pub(super) egraph: EGraph,
     pub(super) stack: Stack,
+    pub(super) search: Search,
+    pub(super) queries: TiVec<QueryIdx, Query>,
+
+    /// Matches which blame an equality we could not explain yet, keyed by
+    /// both sides of the equality. Retried when either side is explained.
+    pub(super) unexplained: FxHashMap<ENodeIdx, Vec<MatchIdx>>,
+
+    pub(super) diagnostics: Diagnostics,
+    #[serde(skip)]
+    pub(super) events: EventQueue,
+    /// Position of the line currently being parsed.
+    pub(super) pos: LinePos,
 
     pub strings: StringTable,
 }
@@ -41,6 +67,12 @@ impl Default for Z3Parser {
             inst_stack: Default::default(),
             egraph: Default::default(),
             stack: Default::default(),
+            search: Default::default(),
+            queries: Default::default(),
+            unexplained: Default::default(),
+            diagnostics: Default::default(),
+            events: Default::default(),
+            pos: Default::default(),
             strings,
         }
     }
@@ -57,16 +89,107 @@ impl Z3Parser {
         self.version_info.as_ref().is_some_and(|v| v.version >= semver::Version::new(major, minor, patch))
     }
 
-    pub fn parse_existing_enode(&mut self, id: &str) -> Option<ENodeIdx> {
+    /// The decisions, assignments and conflicts of the SAT solver.
+    pub fn search(&self) -> &Search {
+        &self.search
+    }
+
+    /// The number of instantiations made in the log.
+    pub fn inst_count(&self) -> usize {
+        self.insts.insts.len()
+    }
+    /// All terms in the log, in the order they were created.
+    pub fn terms(&self) -> impl Iterator<Item = (TermIdx, &Term)> + '_ {
+        self.terms.iter()
+    }
+    /// All matches in the log, including those which were never instantiated.
+    pub fn matches(&self) -> impl Iterator<Item = (MatchIdx, &Match)> + '_ {
+        self.insts.matches.iter_enumerated()
+    }
+    /// The matches which Z3 found but never instantiated, e.g. because they
+    /// were cut off by the eager threshold or `qi.max_instances`.
+    pub fn pending_matches(&self) -> impl Iterator<Item = (MatchIdx, &Match)> + '_ {
+        self.matches()
+            .filter(|&(midx, _)| self.insts.instance_of(midx).is_none())
+    }
+    /// The instance of the match `midx`, `None` if it is pending.
+    pub fn instance_of(&self, midx: MatchIdx) -> Option<InstIdx> {
+        self.insts.instance_of(midx)
+    }
+    /// All enodes in the log, in the order they were attached.
+    pub fn enodes(&self) -> impl Iterator<Item = (ENodeIdx, &ENode)> + '_ {
+        self.egraph.iter()
+    }
+    /// The e-class of `enode` just after the line at `pos` was parsed, taking
+    /// into account which stack frames had been popped by then.
+    pub fn eclass_at(&self, enode: ENodeIdx, pos: LinePos) -> Vec<ENodeIdx> {
+        self.egraph.eclass_at(enode, &self.stack, pos)
+    }
+    /// The root of the e-class of `enode` just after the line at `pos`, see
+    /// [`eclass_at`](Self::eclass_at).
+    pub fn root_at(&self, enode: ENodeIdx, pos: LinePos) -> ENodeIdx {
+        self.egraph.root_at(enode, &self.stack, pos)
+    }
+
+    /// The `check-sat` queries made in the log, in order.
+    pub fn queries(&self) -> &TiVec<QueryIdx, Query> {
+        &self.queries
+    }
+    /// The instantiations made while checking the query `qidx`.
+    pub fn query_insts(&self, qidx: QueryIdx) -> impl Iterator<Item = InstIdx> {
+        let insts = self.query_inst_range(qidx);
+        (usize::from(insts.start)..usize::from(insts.end)).map(InstIdx::from)
+    }
+    fn query_inst_range(&self, qidx: QueryIdx) -> std::ops::Range<InstIdx> {
+        let query = &self.queries[qidx];
+        let is_running = query.end.is_none() && usize::from(qidx) + 1 == self.queries.len();
+        let end = if is_running { self.insts.insts.next_key() } else { query.insts.end };
+        query.insts.start..end
+    }
+    /// The query during which the instantiation `iidx` was made, or `None` if
+    /// it was made outside of any query.
+    pub fn query_of_inst(&self, iidx: InstIdx) -> Option<QueryIdx> {
+        let after = self.queries.partition_point(|q| q.insts.start <= iidx);
+        let qidx = QueryIdx::from(usize::from(after).checked_sub(1)?);
+        self.query_inst_range(qidx).contains(&iidx).then_some(qidx)
+    }
+    /// The query during which the match `midx` was found, or `None` if it
+    /// was found outside of any query.
+    pub fn query_of_match(&self, midx: MatchIdx) -> Option<QueryIdx> {
+        let pos = self.insts.matches[midx].pos;
+        let after = self.queries.partition_point(|q| q.begin <= pos);
+        let qidx = QueryIdx::from(usize::from(after).checked_sub(1)?);
+        self.queries[qidx].end.is_none_or(|end| pos < end).then_some(qidx)
+    }
+
+    /// Problems encountered so far while parsing the log.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Save a snapshot of this parser next to the `log` file it was parsed
+    /// from (see [`cache::cache_path`]). Should only be called once the entire
+    /// log has been parsed.
+    pub fn save_cache(&self, log: impl AsRef<Path>) -> Result<(), CacheError> {
+        cache::save(log.as_ref(), self)
+    }
+    /// Load the snapshot previously saved with [`save_cache`](Self::save_cache)
+    /// for `log`. Fails with [`CacheError::Stale`] if `log` has changed since.
+    pub fn load_cache(log: impl AsRef<Path>) -> Result<Self, CacheError> {
+        cache::load(log.as_ref())
+    }
+
+    pub fn parse_existing_enode(&mut self, id: &str) -> Result<ENodeIdx, DiagnosticKind> {
         let idx = self.terms.parse_existing_id(&mut self.strings, id)?;
         let enode = self.egraph.get_enode(idx, &self.stack);
         if self.is_version(4, 12, 2) && enode.is_none() {
             // Very rarely in version 4.12.2, an `[attach-enode]` is not emitted. Create it here.
-            // TODO: log somewhere when this happens.
-            self.egraph.new_enode(None, idx, None, &self.stack);
-            return self.egraph.get_enode(idx, &self.stack);
+            self.diagnostics.warn(DiagnosticKind::VersionQuirk);
+            let enode = self.egraph.new_enode(None, idx, None, self.pos, &self.stack);
+            self.events.push(ParseEvent::NewENode(enode));
+            return self.egraph.get_enode(idx, &self.stack).ok_or(DiagnosticKind::MissingEnode);
         }
-        enode
+        enode.ok_or(DiagnosticKind::MissingEnode)
     }
     pub fn parse_z3_generation<'a>(
         l: &mut impl Iterator<Item = &'a str>,
@@ -78,8 +201,7 @@ impl Z3Parser {
         }
     }
 
-    #[must_use]
-    fn gobble_children<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<Box<[TermIdx]>> {
+    fn gobble_children<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Result<Box<[TermIdx]>, DiagnosticKind> {
         l.map(|id| self.terms.parse_existing_id(&mut self.strings, id)).collect()
     }
     #[must_use]
@@ -88,7 +210,7 @@ impl Z3Parser {
         // TODO: if the list can be empty then remove the first `?` and
         // replace with default case.
         let (first, second) = t.next()??;
-        if first == "" {
+        if first.is_empty() {
             let first = Some(self.strings.get_or_intern(second));
             let tuples = t.map(|t| match t? {
                 ("", second) => Some(self.strings.get_or_intern(second)),
@@ -117,10 +239,10 @@ impl Z3Parser {
     ///  - `(A;B)`
     ///  - `(A B)`
     ///  - `(A ; B)`
+    ///
     /// The resulting iterator will contain `None` for any tuples which it failed to parse.
     /// If `FORMS_EQUAL` is true, then it will return `None` for any tuples which have a different
     /// form to the first tuple.
-    #[must_use]
     fn gobble_tuples<'a, const FORMS_EQUAL: bool>(
         mut l: impl Iterator<Item = &'a str>,
     ) -> impl Iterator<Item = Option<(&'a str, &'a str)>> {
@@ -159,151 +281,248 @@ impl Z3Parser {
         let inverted_gobble = move |_| gobble().map_or(Some(None), |x| x.map(Some));
         std::iter::repeat(()).map_while(inverted_gobble)
     }
-    #[must_use]
     fn gobble_enode_pairs<'a>(
         &mut self,
         l: impl Iterator<Item = &'a str>,
-    ) -> Option<Box<[(ENodeIdx, ENodeIdx)]>> {
+    ) -> Result<Box<[(ENodeIdx, ENodeIdx)]>, DiagnosticKind> {
         Self::gobble_tuples::<true>(l)
             .map(|t| {
-                let (first, second) = t?;
+                let (first, second) = t.ok_or(DiagnosticKind::MalformedLine)?;
                 let first = self.parse_existing_enode(first)?;
                 let second = self.parse_existing_enode(second)?;
-                Some((first, second))
+                Ok((first, second))
             })
             .collect()
     }
     /// Create a new iterator which will only consume elements from `l` until
     /// it finds `end`. The element `end` will also be consumed but no other elements after that will.
-    #[must_use]
     fn iter_until_eq<'a, 's>(
         l: &'a mut impl Iterator<Item = &'s str>,
         end: &'a str,
     ) -> impl Iterator<Item = &'s str> + 'a {
         l.take_while(move |elem| *elem != end)
     }
-    #[must_use]
-    fn expect_completed<'s>(mut l: impl Iterator<Item = &'s str>) -> Option<()> {
-        l.next().map_or(Some(()), |_| None)
+    /// Get the next element of `l`, it is an error if the line has ended.
+    fn expect_next<'s>(l: &mut impl Iterator<Item = &'s str>) -> Result<&'s str, DiagnosticKind> {
+        l.next().ok_or(DiagnosticKind::MalformedLine)
+    }
+    fn expect_completed<'s>(mut l: impl Iterator<Item = &'s str>) -> ParseResult {
+        l.next().map_or(Ok(()), |_| Err(DiagnosticKind::MalformedLine))
+    }
+    fn parse_term_id(&mut self, id: &str) -> Result<TermId, DiagnosticKind> {
+        TermId::parse(&mut self.strings, id).ok_or(DiagnosticKind::MalformedId)
+    }
+    fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, DiagnosticKind> {
+        value.parse::<T>().map_err(|_| DiagnosticKind::MalformedLine)
+    }
+    /// Parse a literal of the form `true`, `false`, `#12` or `(not #12)`
+    /// (which is split over two words) starting with the word `first`.
+    fn parse_literal<'a>(
+        &mut self,
+        first: &str,
+        l: &mut impl Iterator<Item = &'a str>,
+    ) -> Result<Literal, DiagnosticKind> {
+        let (id, value) = match first {
+            "true" | "false" => {
+                let value = first == "true";
+                return Ok(Literal { term: None, value });
+            }
+            "(not" => {
+                let id = Self::expect_next(l)?;
+                let id = id.strip_suffix(')').ok_or(DiagnosticKind::MalformedLine)?;
+                (id, false)
+            }
+            id => (id, true),
+        };
+        let term = self.terms.parse_existing_id(&mut self.strings, id)?;
+        Ok(Literal { term: Some(term), value })
+    }
+    /// Parse all literals in the remaining words of `l`, skipping over any
+    /// other data (e.g. the theory id of a justification).
+    fn gobble_literals<'a>(
+        &mut self,
+        mut l: impl Iterator<Item = &'a str>,
+    ) -> Result<Box<[Literal]>, DiagnosticKind> {
+        let mut lits = Vec::new();
+        while let Some(first) = l.next() {
+            let is_literal = matches!(first, "true" | "false" | "(not") || first.contains('#');
+            if is_literal {
+                lits.push(self.parse_literal(first, &mut l)?);
+            }
+        }
+        Ok(lits.into_boxed_slice())
+    }
+    fn new_search_event(&mut self, kind: SearchEventKind) -> ParseResult {
+        self.search.new_event(kind, self.pos, &self.stack);
+        Ok(())
+    }
+    /// Remember equality terms so that theory and axiom equalities can be
+    /// blamed on them. Only possible once both sides have enodes.
+    fn remember_equality_term(&mut self, idx: TermIdx, enode: ENodeIdx) {
+        let term = &self.terms[idx];
+        if let [lhs, rhs] = *term.child_ids {
+            let is_eq = term.kind.app_name().is_some_and(|app| &self.strings[app] == "=");
+            let lhs = self.egraph.get_enode(lhs, &self.stack);
+            let rhs = self.egraph.get_enode(rhs, &self.stack);
+            if let (true, Some(lhs), Some(rhs)) = (is_eq, lhs, rhs) {
+                self.egraph.new_equality_term(enode, lhs, rhs);
+            }
+        }
     }
 }
 
 impl Z3LogParser for Z3Parser {
-    fn version_info<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let solver = l.next()?.to_string();
-        let version = l.next()?;
+    fn diagnostics_mut(&mut self) -> &mut Diagnostics {
+        &mut self.diagnostics
+    }
+    fn events_mut(&mut self) -> &mut EventQueue {
+        &mut self.events
+    }
+    fn begin_line(&mut self, pos: LinePos) {
+        self.pos = pos;
+    }
+
+    fn version_info<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let solver = Self::expect_next(&mut l)?.to_string();
+        let version = Self::expect_next(&mut l)?;
         // Return if there is unexpectedly more data
         Self::expect_completed(l)?;
-        let version = semver::Version::parse(version).ok()?;
-        println!("{solver} {version}");
+        let version =
+            semver::Version::parse(version).map_err(|_| DiagnosticKind::MalformedLine)?;
         self.version_info = Some(VersionInfo { solver, version });
-        Some(())
+        Ok(())
     }
 
-    fn mk_quant<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let full_id = l
-            .next()
-            .and_then(|id| TermId::parse(&mut self.strings, id))?;
-        let mut quant_name = std::borrow::Cow::Borrowed(l.next()?);
-        let mut num_vars_str = l.next()?;
+    fn mk_quant<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let full_id = self.parse_term_id(Self::expect_next(&mut l)?)?;
+        let mut quant_name = std::borrow::Cow::Borrowed(Self::expect_next(&mut l)?);
+        let mut num_vars_str = Self::expect_next(&mut l)?;
         let mut num_vars = num_vars_str.parse::<usize>();
         // The name may contain spaces... TODO: PR to add quotes around name when logging in z3
         while num_vars.is_err() {
             quant_name = std::borrow::Cow::Owned(format!("{quant_name} {num_vars_str}"));
-            num_vars_str = l.next()?;
+            num_vars_str = Self::expect_next(&mut l)?;
             num_vars = num_vars_str.parse::<usize>();
         }
-        let quant_name = QuantKind::parse(&mut self.strings, &*quant_name);
+        let quant_name = QuantKind::parse(&mut self.strings, &quant_name);
         let num_vars = num_vars.unwrap();
         let child_ids = self.gobble_children(l)?;
-        assert!(!child_ids.is_empty());
+        if child_ids.is_empty() {
+            return Err(DiagnosticKind::MalformedLine);
+        }
         let qidx = self.quantifiers.next_key();
         let term = Term {
             id: full_id,
+            pos: self.pos,
             kind: TermKind::Quant(qidx),
             child_ids,
         };
         let tidx = self.terms.new_term(full_id, term);
         let q = Quantifier {
+            pos: self.pos,
             num_vars,
             kind: quant_name,
             term: Some(tidx),
             instances: Vec::new(),
             cost: 0.0,
+            useful: 0,
             vars: None,
         };
         let qidx2 = self.quantifiers.push_and_get_key(q);
         debug_assert_eq!(qidx, qidx2);
-        Some(())
+        self.events.push(ParseEvent::NewTerm(tidx));
+        self.events.push(ParseEvent::NewQuantifier(qidx));
+        Ok(())
     }
 
-    fn mk_var<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let full_id = l
-            .next()
-            .and_then(|id| TermId::parse(&mut self.strings, id))?;
-        let kind = l.next().and_then(TermKind::parse_var)?;
+    fn mk_var<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let full_id = self.parse_term_id(Self::expect_next(&mut l)?)?;
+        let kind =
+            TermKind::parse_var(Self::expect_next(&mut l)?).ok_or(DiagnosticKind::MalformedLine)?;
         // Return if there is unexpectedly more data
         Self::expect_completed(l)?;
         let term = Term {
             id: full_id,
+            pos: self.pos,
             kind,
             child_ids: Default::default(),
         };
-        self.terms.new_term(full_id, term);
-        Some(())
+        let tidx = self.terms.new_term(full_id, term);
+        self.events.push(ParseEvent::NewTerm(tidx));
+        Ok(())
     }
 
     fn mk_proof_app<'a>(
         &mut self,
         mut l: impl Iterator<Item = &'a str>,
         is_proof: bool,
-    ) -> Option<()> {
-        let full_id = l
-            .next()
-            .and_then(|id| TermId::parse(&mut self.strings, id))?;
-        let kind = TermKind::parse_proof_app(is_proof, self.strings.get_or_intern(l.next()?));
-        // TODO: add rewrite, monotonicity cases
+    ) -> ParseResult {
+        let full_id = self.parse_term_id(Self::expect_next(&mut l)?)?;
+        let name = self.strings.get_or_intern(Self::expect_next(&mut l)?);
+        let kind = TermKind::parse_proof_app(is_proof, name);
+        // For proofs the children are the premises followed by the
+        // conclusion, see `ProofGraph`.
         let child_ids = self.gobble_children(l)?;
+        let conclusion = child_ids.last().copied();
         let term = Term {
             id: full_id,
+            pos: self.pos,
             kind,
             child_ids,
         };
-        self.terms.new_term(full_id, term);
-        Some(())
+        let tidx = self.terms.new_term(full_id, term);
+        if let Some(conclusion) = conclusion.filter(|_| is_proof) {
+            let conclusion = &self.terms[conclusion];
+            let is_eq = conclusion.kind.app_name().is_some_and(|app| &self.strings[app] == "=");
+            if let (true, &[lhs, rhs]) = (is_eq, &*conclusion.child_ids) {
+                self.terms.new_proved_equality(tidx, lhs, rhs);
+            }
+        }
+        self.events.push(ParseEvent::NewTerm(tidx));
+        Ok(())
     }
 
-    fn attach_meaning<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let id = l.next()?;
-        let theory = self.strings.get_or_intern(l.next()?);
+    fn attach_meaning<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let id = Self::expect_next(&mut l)?;
+        let theory = self.strings.get_or_intern(Self::expect_next(&mut l)?);
         let value = self.strings.get_or_intern(l.collect::<Vec<_>>().join(" "));
         let meaning = Meaning { theory, value };
         let idx = self.terms.parse_existing_id(&mut self.strings, id)?;
-        self.terms.new_meaning(idx, meaning);
-        Some(())
+        self.terms.new_meaning(idx, meaning)
     }
 
-    fn attach_var_names<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let id = l.next()?;
-        let var_names = self.gobble_var_names_list(l)?;
+    fn attach_var_names<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let id = Self::expect_next(&mut l)?;
+        let var_names = self
+            .gobble_var_names_list(l)
+            .ok_or(DiagnosticKind::MalformedLine)?;
         let tidx = self.terms.parse_existing_id(&mut self.strings, id)?;
-        let qidx = self.terms[tidx].kind.quant_idx()?;
-        assert!(self.quantifiers[qidx].vars.is_none());
+        let qidx = self.terms[tidx]
+            .kind
+            .quant_idx()
+            .ok_or(DiagnosticKind::UnexpectedTerm)?;
+        if self.quantifiers[qidx].vars.is_some() {
+            return Err(DiagnosticKind::Redefinition);
+        }
         self.quantifiers[qidx].vars = Some(var_names);
-        Some(())
+        Ok(())
     }
 
-    fn attach_enode<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let idx = self.terms.parse_existing_id(&mut self.strings, l.next()?);
-        let Some(idx) = idx else {
-            if self.is_version(4, 8, 7) {
+    fn attach_enode<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let idx = self
+            .terms
+            .parse_existing_id(&mut self.strings, Self::expect_next(&mut l)?);
+        let idx = match idx {
+            Ok(idx) => idx,
+            Err(DiagnosticKind::UnknownId) if self.is_version(4, 8, 7) => {
                 // Z3 4.8.7 seems to have a bug where it can emit a non-existent term id here.
-                return Some(());
-            } else {
-                return None;
+                self.diagnostics.warn(DiagnosticKind::VersionQuirk);
+                return Ok(());
             }
+            Err(err) => return Err(err),
         };
-        let z3_generation = Self::parse_z3_generation(&mut l).ok()?;
+        let z3_generation =
+            Self::parse_z3_generation(&mut l).map_err(|_| DiagnosticKind::MalformedLine)?;
         // Return if there is unexpectedly more data
         Self::expect_completed(l)?;
 
@@ -311,50 +530,55 @@ impl Z3LogParser for Z3Parser {
         let iidx = created_by.as_ref().map(|(i, _)| *i);
         let enode = self
             .egraph
-            .new_enode(iidx, idx, z3_generation, &self.stack);
+            .new_enode(iidx, idx, z3_generation, self.pos, &self.stack);
+        self.events.push(ParseEvent::NewENode(enode));
         if let Some((_, yields_terms)) = created_by {
             // If `None` then this is a ground term not created by an instantiation.
             yields_terms.push(enode);
         }
-        Some(())
+        self.remember_equality_term(idx, enode);
+        Ok(())
     }
 
-    fn eq_expl<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let from = self.parse_existing_enode(l.next()?)?;
-        let kind = l.next()?;
+    fn eq_expl<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let from = self.parse_existing_enode(Self::expect_next(&mut l)?)?;
+        let kind = Self::expect_next(&mut l)?;
         let eq_expl = {
             let mut kind_dependent_info = Self::iter_until_eq(l.by_ref(), ";");
             match kind {
                 "root" => EqualityExpl::Root { id: from },
                 "lit" => {
-                    let eq = kind_dependent_info.next()?;
+                    let eq = Self::expect_next(&mut kind_dependent_info)?;
                     let eq = self.parse_existing_enode(eq)?;
                     Self::expect_completed(kind_dependent_info)?;
-                    let to = self.parse_existing_enode(l.next()?)?;
+                    let to = self.parse_existing_enode(Self::expect_next(&mut l)?)?;
                     EqualityExpl::Literal { from, eq, to }
                 }
                 "cg" => {
                     let arg_eqs = self.gobble_enode_pairs(kind_dependent_info)?;
-                    let to = self.parse_existing_enode(l.next()?)?;
+                    let to = self.parse_existing_enode(Self::expect_next(&mut l)?)?;
                     EqualityExpl::Congruence { from, arg_eqs, to }
                     // For each pair (#A #B), reconstruct dependent equality explanations connecting #A to #B ...
                 }
                 "th" => {
-                    let theory = self.strings.get_or_intern(kind_dependent_info.next()?);
+                    let theory = Self::expect_next(&mut kind_dependent_info)?;
+                    let theory = self.strings.get_or_intern(theory);
                     Self::expect_completed(kind_dependent_info)?;
-                    let to = self.parse_existing_enode(l.next()?)?;
-                    EqualityExpl::Theory { from, theory, to }
+                    let to = self.parse_existing_enode(Self::expect_next(&mut l)?)?;
+                    let eq = self.egraph.equality_term(from, to, &self.stack);
+                    EqualityExpl::Theory { from, theory, eq, to }
                 }
                 "ax" => {
                     Self::expect_completed(kind_dependent_info)?;
-                    let to = self.parse_existing_enode(l.next()?)?;
-                    EqualityExpl::Axiom { from, to }
+                    let to = self.parse_existing_enode(Self::expect_next(&mut l)?)?;
+                    let eq = self.egraph.equality_term(from, to, &self.stack);
+                    EqualityExpl::Axiom { from, eq, to }
                 }
                 kind => {
                     let args = kind_dependent_info
                         .map(|s| self.strings.get_or_intern(s))
                         .collect();
-                    let to = self.parse_existing_enode(l.next()?)?;
+                    let to = self.parse_existing_enode(Self::expect_next(&mut l)?)?;
                     EqualityExpl::Unknown {
                         kind: self.strings.get_or_intern(kind),
                         from,
@@ -367,22 +591,35 @@ impl Z3LogParser for Z3Parser {
         // Return if there is unexpectedly more data
         Self::expect_completed(l)?;
 
-        self.egraph.new_equality(from, eq_expl, &self.stack);
-        Some(())
+        let to = eq_expl.to();
+        self.egraph.new_equality(from, eq_expl, self.pos, &self.stack);
+        self.events.push(ParseEvent::NewEquality { from, to });
+        for midx in self.unexplained.remove(&from).unwrap_or_default() {
+            self.explain_unknown_equalities(midx);
+        }
+        Ok(())
     }
 
-    fn new_match<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let fingerprint = l.next().and_then(Fingerprint::parse)?;
-        let idx = self.terms.parse_existing_id(&mut self.strings, l.next()?)?;
-        let quant = self.terms[idx].kind.quant_idx()?;
-        let pattern = self.terms.parse_existing_id(&mut self.strings, l.next()?)?;
+    fn new_match<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let fingerprint = Fingerprint::parse(Self::expect_next(&mut l)?)
+            .ok_or(DiagnosticKind::MalformedId)?;
+        let idx = self
+            .terms
+            .parse_existing_id(&mut self.strings, Self::expect_next(&mut l)?)?;
+        let quant = self.terms[idx]
+            .kind
+            .quant_idx()
+            .ok_or(DiagnosticKind::UnexpectedTerm)?;
+        let pattern = self
+            .terms
+            .parse_existing_id(&mut self.strings, Self::expect_next(&mut l)?)?;
         let bound_terms = Self::iter_until_eq(&mut l, ";");
         let is_axiom = fingerprint.is_zero();
 
         let kind = if is_axiom {
             let bound_terms = bound_terms
                 .map(|id| self.terms.parse_existing_id(&mut self.strings, id))
-                .collect::<Option<Vec<_>>>()?;
+                .collect::<Result<Vec<_>, _>>()?;
             MatchKind::Axiom {
                 axiom: quant,
                 pattern,
@@ -391,7 +628,7 @@ impl Z3LogParser for Z3Parser {
         } else {
             let bound_terms = bound_terms
                 .map(|id| self.parse_existing_enode(id))
-                .collect::<Option<Vec<_>>>()?;
+                .collect::<Result<Vec<_>, _>>()?;
             MatchKind::Quantifier {
                 quant,
                 pattern,
@@ -403,57 +640,64 @@ impl Z3LogParser for Z3Parser {
         while let Some(word) = l.next() {
             if let Some(first_term) = word.strip_prefix('(') {
                 // assumes that if we see "(#A", the next word in the split is "#B)"
-                let second_term = l.next()?.strip_suffix(')')?;
+                let second_term = Self::expect_next(&mut l)?
+                    .strip_suffix(')')
+                    .ok_or(DiagnosticKind::MalformedLine)?;
                 let from = self.parse_existing_enode(first_term)?;
                 let to = self.parse_existing_enode(second_term)?;
                 // See comment in `EGraph::get_equalities`
                 let can_mismatch = || self.is_ge_version(4, 12, 3) &&
                     self.terms[self.egraph.get_owner(to)].kind.app_name().is_some_and(|app| &self.strings[app] == "if");
-                self.egraph.blame_equalities(from, to, &self.stack, &mut blamed, can_mismatch)?;
+                self.egraph.blame_equalities(from, to, &self.stack, &mut blamed, can_mismatch);
             } else {
                 let term = self.parse_existing_enode(word)?;
                 blamed.push(BlameKind::Term { term })
             };
         }
 
-        let match_ = Match { kind, blamed: blamed.into_boxed_slice() };
-        self.insts.new_match(fingerprint, match_);
-        Some(())
+        let match_ = Match {
+            kind,
+            pos: self.pos,
+            blamed: blamed.into_boxed_slice(),
+        };
+        let midx = self.insts.new_match(fingerprint, match_);
+        self.events.push(ParseEvent::NewMatch(midx));
+        self.explain_unknown_equalities(midx);
+        Ok(())
     }
 
-    fn inst_discovered<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let method = l.next()?;
-        let fingerprint = Fingerprint::parse(l.next()?)?;
+    fn inst_discovered<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let method = Self::expect_next(&mut l)?;
+        let fingerprint = Fingerprint::parse(Self::expect_next(&mut l)?)
+            .ok_or(DiagnosticKind::MalformedId)?;
 
         let (kind, blamed) = match method {
             "theory-solving" => {
-                debug_assert!(
-                    fingerprint.is_zero(),
-                    "Theory solving should have zero fingerprint"
-                );
-                let axiom_id = l
-                    .next()
-                    .and_then(|id| TermId::parse(&mut self.strings, id))?;
+                // Theory solving should have zero fingerprint
+                if !fingerprint.is_zero() {
+                    return Err(DiagnosticKind::MalformedId);
+                }
+                let axiom_id = self.parse_term_id(Self::expect_next(&mut l)?)?;
 
                 let bound_terms = Self::iter_until_eq(&mut l, ";")
                     .map(|id| self.terms.parse_existing_id(&mut self.strings, id))
-                    .collect::<Option<Vec<_>>>()?;
+                    .collect::<Result<Vec<_>, _>>()?;
 
                 let mut blamed = Vec::new();
                 let mut rewrite_of = None;
-                while let Some(word) = l.next() {
+                for word in l {
                     let term = self.terms.parse_existing_id(&mut self.strings, word)?;
+                    // A non-rewrite axiom should only blame valid enodes and a
+                    // rewrite axiom should only have one term.
                     if let Some(enode) = self.egraph.get_enode(term, &self.stack) {
-                        assert!(
-                            rewrite_of.is_none(),
-                            "theory-solving non-rewrite axiom should blame valid enodes"
-                        );
+                        if rewrite_of.is_some() {
+                            return Err(DiagnosticKind::MissingEnode);
+                        }
                         blamed.push(BlameKind::Term { term: enode });
                     } else {
-                        assert!(
-                            blamed.is_empty(),
-                            "theory-solving rewrite axiom should only have one term"
-                        );
+                        if !blamed.is_empty() || rewrite_of.is_some() {
+                            return Err(DiagnosticKind::MissingEnode);
+                        }
                         rewrite_of = Some(term);
                     }
                 }
@@ -466,23 +710,34 @@ impl Z3LogParser for Z3Parser {
                 (kind, blamed)
             }
             "MBQI" => {
-                let quant = self.terms.parse_existing_id(&mut self.strings, l.next()?)?;
-                let quant = self.terms[quant].kind.quant_idx()?;
+                let quant = self
+                    .terms
+                    .parse_existing_id(&mut self.strings, Self::expect_next(&mut l)?)?;
+                let quant = self.terms[quant]
+                    .kind
+                    .quant_idx()
+                    .ok_or(DiagnosticKind::UnexpectedTerm)?;
                 let bound_terms = l
                     .map(|id| self.parse_existing_enode(id))
-                    .collect::<Option<Vec<_>>>()?;
+                    .collect::<Result<Vec<_>, _>>()?;
                 let kind = MatchKind::MBQI { quant, bound_terms };
                 (kind, Vec::new())
             }
-            _ => return None,
+            _ => return Err(DiagnosticKind::MalformedLine),
+        };
+        let match_ = Match {
+            kind,
+            pos: self.pos,
+            blamed: blamed.into_boxed_slice(),
         };
-        let match_ = Match { kind, blamed: blamed.into_boxed_slice() };
-        self.insts.new_match(fingerprint, match_);
-        Some(())
+        let midx = self.insts.new_match(fingerprint, match_);
+        self.events.push(ParseEvent::NewMatch(midx));
+        Ok(())
     }
 
-    fn instance<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let fingerprint = l.next().and_then(Fingerprint::parse)?;
+    fn instance<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let fingerprint = Fingerprint::parse(Self::expect_next(&mut l)?)
+            .ok_or(DiagnosticKind::MalformedId)?;
         let mut proof = Self::iter_until_eq(&mut l, ";");
         let proof_id = if let Some(proof) = proof.next() {
             Some(self.terms.parse_id(&mut self.strings, proof)?)
@@ -490,68 +745,316 @@ impl Z3LogParser for Z3Parser {
             None
         };
         Self::expect_completed(proof)?;
-        let z3_generation = Self::parse_z3_generation(&mut l).ok()?;
+        let z3_generation =
+            Self::parse_z3_generation(&mut l).map_err(|_| DiagnosticKind::MalformedLine)?;
 
         let inst = Instantiation {
+            pos: self.pos,
             // Will be filled in by `new_inst`
             match_: MatchIdx::default(),
             fingerprint,
             proof_id,
             z3_generation,
             yields_terms: Default::default(),
-            cost: 1.0,
+            cost: 0.0,
+            useful: 0,
         };
-        let iidx = self.insts.new_inst(fingerprint, inst);
+        let iidx = self.insts.new_inst(fingerprint, inst)?;
+        if let Some(qidx) = self.insts[self.insts[iidx].match_].kind.quant_idx() {
+            self.quantifiers[qidx].instances.push(iidx);
+        }
         self.inst_stack.push((iidx, Vec::new()));
-        Some(())
+        self.events.push(ParseEvent::InstStart(iidx));
+        Ok(())
     }
 
-    fn end_of_instance<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let (iidx, yield_terms) = self.inst_stack.pop().unwrap();
+    fn end_of_instance<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let (iidx, yield_terms) = self
+            .inst_stack
+            .pop()
+            .ok_or(DiagnosticKind::UnknownMatch)?;
         self.insts[iidx].yields_terms = yield_terms.into_boxed_slice();
+        self.events.push(ParseEvent::InstEnd(iidx));
         Self::expect_completed(l)
     }
 
-    fn eof(&mut self) {
-        // TODO: this shouldn't be done here.
-        self.compute_costs();
-    }
+    fn eof(&mut self) {}
 
-    fn push<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let scope = l.next()?.parse::<usize>().ok()?;
+    fn push<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let scope = Self::parse_number(Self::expect_next(&mut l)?)?;
         // Return if there is unexpectedly more data
         Self::expect_completed(l)?;
-        self.stack.new_frame(scope)
+        if self.stack.is_above(scope) {
+            // Some versions (e.g. z3 v4.8.17 and v4.11.2) sometimes do not
+            // emit a `[pop]`, see `Stack::ensure_height`.
+            self.diagnostics.warn(DiagnosticKind::VersionQuirk);
+        }
+        // The frame is pushed even if the scope does not match our stack.
+        let res = self.stack.new_frame(scope, self.pos);
+        self.events.push(ParseEvent::Push { scope });
+        res
     }
 
-    fn pop<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> Option<()> {
-        let num = l.next()?.parse::<usize>().ok()?;
-        let scope = l.next()?.parse::<usize>().ok()?;
+    fn pop<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let num = Self::parse_number(Self::expect_next(&mut l)?)?;
+        let scope = Self::parse_number(Self::expect_next(&mut l)?)?;
         // Return if there is unexpectedly more data
         Self::expect_completed(l)?;
-        self.stack.pop_frames(num, scope)
+        if self.stack.is_above(scope) {
+            self.diagnostics.warn(DiagnosticKind::VersionQuirk);
+        }
+        self.search.pop(num);
+        let active = self.stack.active_frame();
+        let res = self.stack.pop_frames(num, scope, self.pos);
+        // Frames may have been popped even if the scope does not match.
+        if self.stack.active_frame() != active {
+            self.events.push(ParseEvent::Pop { num, scope });
+        }
+        res
+    }
+
+    fn begin_check<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let level = Self::parse_number(Self::expect_next(&mut l)?)?;
+        Self::expect_completed(l)?;
+        let next_inst = self.insts.insts.next_key();
+        if let Some(last) = self.queries.last_mut() {
+            if last.end.is_none() {
+                // The previous query never finished.
+                last.insts.end = next_inst;
+            }
+        }
+        self.queries.push(Query {
+            level,
+            begin: self.pos,
+            end: None,
+            result: None,
+            insts: next_inst..next_inst,
+        });
+        Ok(())
+    }
+
+    fn query_done<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let result = l.next().map(|r| QueryResult::parse(&mut self.strings, r));
+        let next_inst = self.insts.insts.next_key();
+        let query = self
+            .queries
+            .last_mut()
+            .filter(|q| q.end.is_none())
+            .ok_or(DiagnosticKind::MalformedLine)?;
+        query.end = Some(self.pos);
+        query.result = result;
+        query.insts.end = next_inst;
+        Ok(())
+    }
+
+    fn decide_and_or<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let parent = self
+            .terms
+            .parse_existing_id(&mut self.strings, Self::expect_next(&mut l)?)?;
+        let child = self
+            .terms
+            .parse_existing_id(&mut self.strings, Self::expect_next(&mut l)?)?;
+        Self::expect_completed(l)?;
+        self.new_search_event(SearchEventKind::DecideAndOr { parent, child })
+    }
+
+    fn decide<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let first = Self::expect_next(&mut l)?;
+        let lit = self.parse_literal(first, &mut l)?;
+        Self::expect_completed(l)?;
+        self.new_search_event(SearchEventKind::Decide { lit })
+    }
+
+    fn assign<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let first = Self::expect_next(&mut l)?;
+        let lit = self.parse_literal(first, &mut l)?;
+        let mut kind = Self::expect_next(&mut l)?;
+        let decision = kind == "decision";
+        if decision {
+            kind = Self::expect_next(&mut l)?;
+        }
+        let kind = match kind {
+            "axiom" => JustificationKind::Axiom,
+            "bin" => JustificationKind::Binary,
+            "clause" => JustificationKind::Clause,
+            "justification" => JustificationKind::Theory,
+            kind => JustificationKind::Unknown(self.strings.get_or_intern(kind)),
+        };
+        let lits = self.gobble_literals(l)?;
+        let justification = Justification { kind, lits };
+        // An equality term may have been attached before its sides, in which
+        // case it is only remembered once it is assigned.
+        if let Literal { term: Some(term), value: true } = lit {
+            if let Some(enode) = self.egraph.get_enode(term, &self.stack) {
+                self.remember_equality_term(term, enode);
+            }
+        }
+        self.new_search_event(SearchEventKind::Assign {
+            lit,
+            decision,
+            justification,
+        })
+    }
+
+    fn conflict<'a>(&mut self, l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let lits = self.gobble_literals(l)?;
+        self.attribute_conflict(&lits);
+        self.new_search_event(SearchEventKind::Conflict { lits, backjump: 0 })
+    }
+
+    fn resolve_lit<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let level_delta = Self::parse_number(Self::expect_next(&mut l)?)?;
+        let first = Self::expect_next(&mut l)?;
+        let lit = self.parse_literal(first, &mut l)?;
+        Self::expect_completed(l)?;
+        self.attribute_conflict(&[lit]);
+        self.new_search_event(SearchEventKind::ResolveLit { level_delta, lit })
+    }
+
+    fn resolve_process<'a>(&mut self, mut l: impl Iterator<Item = &'a str>) -> ParseResult {
+        let first = Self::expect_next(&mut l)?;
+        let lit = self.parse_literal(first, &mut l)?;
+        Self::expect_completed(l)?;
+        self.new_search_event(SearchEventKind::ResolveProcess { lit })
     }
 }
 
 impl Z3Parser {
-    fn compute_costs(&mut self) {
-        let mut insts = self.insts.insts.as_mut_slice();
-        while let Some((last, others)) = insts.split_last_mut() {
-            insts = others;
-            let match_ = &self.insts.matches[last.match_];
-            let deps: Vec<_> = match_
-                .due_to_enodes()
-                .filter_map(|(_, blame)| self.egraph[blame].created_by)
-                .collect();
-            let num_deps = deps.len() as f32;
-            for blamed in deps {
-                let cost = last.cost / num_deps;
-                let blamed_inst = &mut insts[blamed];
-                blamed_inst.cost += cost;
-                if let Some(qidx) = match_.kind.quant_idx() {
-                    self.quantifiers[qidx].cost += cost;
+    /// Replace the [`BlameKind::UnknownEquality`] blames of the match `midx`
+    /// with an explanation where possible, see `explain_equality`. Those which
+    /// remain unexplained record any `[mk-proof]` step concluding them, and the
+    /// match is retried once either side of the equality is explained by an
+    /// `[eq-expl]`, until it is instantiated. An explanation is only used if
+    /// it does not depend on anything created after the match.
+    fn explain_unknown_equalities(&mut self, midx: MatchIdx) {
+        if self.insts.instance_of(midx).is_some() {
+            return;
+        }
+        let blamed = &self.insts.matches[midx].blamed;
+        if !blamed.iter().any(|blame| matches!(blame, BlameKind::UnknownEquality { .. })) {
+            return;
+        }
+        let mut explained = Vec::with_capacity(blamed.len());
+        let mut pending = Vec::new();
+        for blame in blamed.iter() {
+            let &BlameKind::UnknownEquality { from, to, .. } = blame else {
+                explained.push(blame.clone());
+                continue;
+            };
+            let blames = self.explain_equality(from, to, 0);
+            if let Some(blames) = blames.filter(|blames| self.blamed_in_time(midx, blames)) {
+                explained.extend(blames);
+            } else {
+                let (lhs, rhs) = (self.egraph.get_owner(from), self.egraph.get_owner(to));
+                let proof = self.terms.proved_equality(lhs, rhs);
+                explained.push(BlameKind::UnknownEquality { from, to, proof });
+                pending.extend([from, to]);
+            }
+        }
+        self.insts.matches[midx].blamed = explained.into_boxed_slice();
+        for enode in pending {
+            let matches = self.unexplained.entry(enode).or_default();
+            if matches.last() != Some(&midx) {
+                matches.push(midx);
+            }
+        }
+    }
+
+    /// Whether the enodes of `blames` all existed before the match `midx`, or
+    /// were created before its instance. Instantiations can only depend on
+    /// earlier ones.
+    fn blamed_in_time(&self, midx: MatchIdx, blames: &[BlameKind]) -> bool {
+        let match_ = &self.insts.matches[midx];
+        let instance = self.insts.instance_of(midx);
+        blames.iter().filter_map(BlameKind::get_blame_node).all(|enode| {
+            let enode = &self.egraph[enode];
+            enode.pos < match_.pos
+                || enode
+                    .created_by
+                    .is_none_or(|by| instance.is_none_or(|instance| by < instance))
+        })
+    }
+
+    /// Explain `from = to` with the e-graph, falling back to an equality term
+    /// `from = to` or to congruence (`f(a, b) = f(c, d)` if `a = c` and
+    /// `b = d`) for equalities the e-graph cannot explain. Returns `None` if
+    /// any part remains unexplained.
+    fn explain_equality(&self, from: ENodeIdx, to: ENodeIdx, depth: usize) -> Option<Vec<BlameKind>> {
+        if depth > MAX_CONGRUENCE_DEPTH {
+            return None;
+        }
+        let mut blamed = Vec::new();
+        self.egraph.blame_equalities(from, to, &self.stack, &mut blamed, || false);
+        let mismatch = matches!(*blamed, [BlameKind::UnknownEquality { from: f, to: t, .. }] if (f, t) == (from, to));
+        if !mismatch {
+            // Any unknown equalities are between the arguments of congruences.
+            let mut explained = Vec::with_capacity(blamed.len());
+            for blame in blamed {
+                match blame {
+                    BlameKind::UnknownEquality { from, to, .. } => {
+                        explained.extend(self.explain_equality(from, to, depth + 1)?)
+                    }
+                    blame => explained.push(blame),
                 }
             }
+            return Some(explained);
+        }
+        if let Some(eq) = self.egraph.equality_term(from, to, &self.stack) {
+            return Some(vec![BlameKind::Equality { eq }]);
+        }
+        let lhs = &self.terms[self.egraph.get_owner(from)];
+        let rhs = &self.terms[self.egraph.get_owner(to)];
+        let name = lhs.kind.app_name();
+        if name.is_none() || name != rhs.kind.app_name() || lhs.child_ids.len() != rhs.child_ids.len() {
+            return None;
+        }
+        let mut explained = Vec::new();
+        for (&lhs, &rhs) in lhs.child_ids.iter().zip(rhs.child_ids.iter()) {
+            let lhs = self.egraph.get_enode(lhs, &self.stack)?;
+            let rhs = self.egraph.get_enode(rhs, &self.stack)?;
+            if lhs != rhs {
+                explained.extend(self.explain_equality(lhs, rhs, depth + 1)?);
+            }
+        }
+        Some(explained)
+    }
+
+    /// Mark the instantiations (and their quantifiers) which created the
+    /// terms of `lits` as having been useful in a conflict. Each is counted at
+    /// most once per call.
+    fn attribute_conflict(&mut self, lits: &[Literal]) {
+        let mut insts: Vec<_> = lits
+            .iter()
+            .filter_map(|lit| self.egraph.get_enode(lit.term?, &self.stack))
+            .filter_map(|enode| self.egraph[enode].created_by)
+            .collect();
+        insts.sort_unstable();
+        insts.dedup();
+        let mut quants: Vec<_> = insts
+            .iter()
+            .filter_map(|&iidx| {
+                let inst = &mut self.insts.insts[iidx];
+                inst.useful += 1;
+                self.insts.matches[inst.match_].kind.quant_idx()
+            })
+            .collect();
+        quants.sort_unstable();
+        quants.dedup();
+        for qidx in quants {
+            self.quantifiers[qidx].useful += 1;
+        }
+    }
+
+    /// Set the `cost` of every instantiation and quantifier as given by
+    /// `model`. Costs are zero until this is called, the
+    /// [`InstGraph`](super::inst_graph::InstGraph) costs its nodes itself.
+    pub fn compute_costs(&mut self, model: &impl CostModel) {
+        let costs = model.costs(self);
+        for (inst, cost) in self.insts.insts.iter_mut().zip(costs.insts) {
+            inst.cost = cost;
+        }
+        for (quant, cost) in self.quantifiers.iter_mut().zip(costs.quants) {
+            quant.cost = cost;
         }
     }
 
@@ -576,6 +1079,18 @@ impl std::ops::Index<QuantIdx> for Z3Parser {
         &self.quantifiers[idx]
     }
 }
+impl std::ops::Index<InstIdx> for Z3Parser {
+    type Output = Instantiation;
+    fn index(&self, idx: InstIdx) -> &Self::Output {
+        &self.insts[idx]
+    }
+}
+impl std::ops::Index<MatchIdx> for Z3Parser {
+    type Output = Match;
+    fn index(&self, idx: MatchIdx) -> &Self::Output {
+        &self.insts[idx]
+    }
+}
 impl std::ops::Index<ENodeIdx> for Z3Parser {
     type Output = ENode;
     fn index(&self, idx: ENodeIdx) -> &Self::Output {
@@ -0,0 +1,27 @@
+use smt_log_parser::{parsers::cache::CacheError, LogParser, Z3Parser};
+
+#[test]
+fn save_and_load_cache() {
+    let dir = std::env::temp_dir().join(format!("slp-cache-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("matching_loop.log");
+    std::fs::copy("tests/fixtures/matching_loop.log", &log).unwrap();
+
+    assert!(matches!(Z3Parser::load_cache(&log), Err(CacheError::Io(_))));
+    let (_, parser) = Z3Parser::from_file(&log).unwrap();
+    let parser = parser.process_all();
+    parser.save_cache(&log).unwrap();
+
+    let loaded = Z3Parser::load_cache(&log).unwrap();
+    assert_eq!(
+        serde_json::to_value(&parser).unwrap(),
+        serde_json::to_value(&loaded).unwrap()
+    );
+
+    let mut contents = std::fs::read_to_string(&log).unwrap();
+    contents.push_str("[push] 1\n");
+    std::fs::write(&log, contents).unwrap();
+    assert!(matches!(Z3Parser::load_cache(&log), Err(CacheError::Stale)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,24 @@
+use smt_log_parser::{
+    parsers::{Checkpoint, LogParser},
+    Z3Parser,
+};
+
+const LOG: &str = "tests/fixtures/matching_loop.log";
+
+#[test]
+fn resume_from_checkpoint() {
+    let (_, full) = Z3Parser::from_file(LOG).unwrap();
+    let full = serde_json::to_value(full.process_all()).unwrap();
+
+    for stop_at in [0, 1, 15, 30, 1000] {
+        let (_, mut parser) = Z3Parser::from_file(LOG).unwrap();
+        parser.process_until(|_, state| state.lines_read < stop_at);
+        let saved = serde_json::to_string(&parser.checkpoint()).unwrap();
+        drop(parser);
+
+        let checkpoint: Checkpoint<Z3Parser> = serde_json::from_str(&saved).unwrap();
+        let (_, resumed) = Z3Parser::resume_file(LOG, checkpoint).unwrap();
+        let resumed = serde_json::to_value(resumed.process_all()).unwrap();
+        assert_eq!(full, resumed, "Resuming after {stop_at} lines differs");
+    }
+}
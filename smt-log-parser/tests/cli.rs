@@ -0,0 +1,195 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Copies the fixture into a fresh directory so that the cache is not written
+/// next to it.
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("slp-cli-test-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy("tests/fixtures/matching_loop.log", dir.join("matching_loop.log")).unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_smt-log-parser"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn stats_and_check() {
+    let dir = fixture_dir("stats");
+    let stats = stdout(&run(&dir, &["stats", "matching_loop.log"]));
+    let row = stats.lines().nth(1).unwrap();
+    let row: Vec<_> = row.split_whitespace().collect();
+    assert_eq!(row, ["q1", "5", "5", "0", "10", "5", "3.0", "4", "10", "4", "0"]);
+
+    let csv = stdout(&run(&dir, &["stats", "matching_loop.log", "--format", "csv"]));
+    assert_eq!(csv.lines().nth(1), Some("0,q1,5,5,0,10.0,5,3.0,4,10,4,0"));
+
+    let args = ["stats", "matching_loop.log", "--format", "csv", "--cost-model", "yields"];
+    let csv = stdout(&run(&dir, &args));
+    assert_eq!(csv.lines().nth(1), Some("0,q1,5,5,0,20.0,5,3.0,4,10,4,0"));
+
+    let patterns = stdout(&run(&dir, &["patterns", "matching_loop.log"]));
+    let row: Vec<_> = patterns.lines().nth(1).unwrap().split_whitespace().collect();
+    assert_eq!(row, ["q1", "5", "5", "0", "5", "5", "4", "4", "1", "{f(qvar_0)}"]);
+
+    let check = stdout(&run(&dir, &["check", "matching_loop.log"]));
+    assert!(check.ends_with("0 errors, 0 warnings and 0 invariant violations\n"));
+
+    std::fs::write(dir.join("broken.log"), "[instance] 0x1 #1\n").unwrap();
+    let check = run(&dir, &["check", "broken.log"]);
+    assert!(!check.status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn export() {
+    let dir = fixture_dir("export");
+    let json = stdout(&run(&dir, &["export", "matching_loop.log", "--items", "instantiations"]));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let insts = json.as_array().unwrap();
+    assert_eq!(insts.len(), 5);
+    assert_eq!(insts[0]["quantifier"], "q1");
+    assert_eq!(insts[0]["line"], 16);
+
+    let args = ["export", "matching_loop.log", "--items", "terms", "--format", "csv"];
+    let csv = stdout(&run(&dir, &args));
+    let mut lines = csv.lines();
+    assert!(lines.next().unwrap().starts_with("idx,id,line,kind,name,children,text"));
+    assert_eq!(lines.count(), 19);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn graph_and_diff() {
+    let dir = fixture_dir("graph");
+    let args = ["graph", "matching_loop.log", "--most-costly", "2"];
+    let dot = stdout(&run(&dir, &args));
+    assert!(dot.starts_with("digraph {"));
+    assert_eq!(dot.matches("label=").count(), 2);
+    assert_eq!(dot.matches("->").count(), 1);
+    assert!(dot.contains("tooltip=\"cost 5\""));
+    let args = ["graph", "matching_loop.log", "--most-costly", "1", "--cost-model", "yields"];
+    assert!(stdout(&run(&dir, &args)).contains("tooltip=\"cost 10\""));
+
+    let splice = ("[new-match] 0x3", "[begin-check] 1\n[new-match] 0x3");
+    let queries = common::matching_loop_with(&[splice]);
+    std::fs::write(dir.join("queries.log"), queries).unwrap();
+    let dot = stdout(&run(&dir, &["graph", "queries.log", "--query", "0"]));
+    assert_eq!(dot.matches("label=").count(), 3);
+    let stats = run(&dir, &["stats", "queries.log", "--query", "1"]);
+    assert!(!stats.status.success());
+
+    let log = std::fs::read_to_string(dir.join("matching_loop.log")).unwrap();
+    let half: Vec<_> = log.lines().take(30).collect();
+    std::fs::write(dir.join("half.log"), half.join("\n")).unwrap();
+    let diff = stdout(&run(&dir, &["diff", "half.log", "matching_loop.log"]));
+    assert!(diff.contains("instantiations          3 ->         5  (+2)"));
+    let row = diff.lines().last().unwrap();
+    assert_eq!(row.split_whitespace().take(4).collect::<Vec<_>>(), ["q1", "3", "5", "+2"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn pending_matches() {
+    let dir = fixture_dir("pending");
+    let pending = "[new-match] 0x6 #8 #4 #18 ; #19\n";
+    let log = common::matching_loop_with(&[("[pop] 1 1\n", pending)]);
+    std::fs::write(dir.join("pending.log"), log).unwrap();
+
+    let args = ["export", "pending.log", "--items", "pending-matches", "--format", "csv"];
+    let csv = stdout(&run(&dir, &args));
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("idx,line,quantifier,pattern,triggered_by,bound_terms"));
+    assert_eq!(lines.next(), Some("5,50,q1,{f(qvar_0)},f(g(g(g(g(g(a)))))),g(g(g(g(g(a)))))"));
+
+    let dot = stdout(&run(&dir, &["graph", "pending.log", "--pending"]));
+    assert!(dot.contains("m5 [ label=\"pending\\nq1\" shape=box style=dotted ]"));
+    assert!(dot.contains("4 -> m5 [ style=dotted ]"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cache_only_when_asked() {
+    let dir = fixture_dir("cache");
+    let cache = dir.join("matching_loop.log.cache");
+    let stderr = |output: Output| String::from_utf8(output.stderr).unwrap();
+    let parsed = run(&dir, &["check", "matching_loop.log"]);
+    stdout(&parsed);
+    assert!(!cache.exists());
+    // A missing cache is not worth mentioning.
+    assert!(!stderr(parsed).contains("cache"));
+    stdout(&run(&dir, &["check", "matching_loop.log", "--cache"]));
+    assert!(cache.exists());
+    // An existing cache is used without asking.
+    let loaded = stderr(run(&dir, &["check", "matching_loop.log"]));
+    assert!(loaded.contains("Loaded from cache"), "{loaded}");
+    let parsed = stderr(run(&dir, &["check", "matching_loop.log", "--no-cache"]));
+    assert!(!parsed.contains("cache"), "{parsed}");
+    // Global flags may also come before the subcommand.
+    let parsed = stderr(run(&dir, &["--no-cache", "check", "matching_loop.log"]));
+    assert!(!parsed.contains("cache") && !parsed.contains("Skipping"), "{parsed}");
+    let args = ["--cost-model", "yields", "stats", "matching_loop.log", "--format", "csv"];
+    assert_eq!(stdout(&run(&dir, &args)).lines().nth(1), Some("0,q1,5,5,0,20.0,5,3.0,4,10,4,0"));
+    assert!(!run(&dir, &["matching_loop.log", "check", "matching_loop.log"]).status.success());
+    assert!(!run(&dir, &["check", "matching_loop.log", "--cache", "--no-cache"]).status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cvc5_logs() {
+    let dir = fixture_dir("cvc5");
+    std::fs::copy("tests/fixtures/cvc5/matching_loop.log", dir.join("cvc5.log")).unwrap();
+    let args = ["stats", "cvc5.log", "--solver", "cvc5", "--format", "csv"];
+    let csv = stdout(&run(&dir, &args));
+    let mut lines = csv.lines().skip(1);
+    assert_eq!(lines.next(), Some("0,q1,4,4,0,4.0,,,0,0,0,0"));
+    assert_eq!(lines.next(), Some("1,q2,1,1,0,1.0,,,0,0,0,0"));
+
+    let args = ["export", "cvc5.log", "--solver", "cvc5", "--items", "instantiations"];
+    let json = stdout(&run(&dir, &args));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let insts = json.as_array().unwrap();
+    assert_eq!(insts.len(), 5);
+    assert_eq!(insts[4]["quantifier"], "q2");
+    assert_eq!(insts[4]["query"], 0);
+
+    let args = ["export", "cvc5.log", "--solver", "cvc5", "--items", "terms"];
+    assert!(!run(&dir, &args).status.success());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn merge_reused_ids() {
+    let dir = fixture_dir("reuses");
+    let log = "[tool-version] Z3 4.12.1\n\
+               [mk-app] #1 a\n\
+               [mk-app] #2 f #1\n\
+               [mk-app] #1 b\n";
+    std::fs::write(dir.join("reuse.log"), log).unwrap();
+    let terms = |settings: &str| {
+        std::fs::write(dir.join("settings.json"), settings).unwrap();
+        let out = stdout(&run(&dir, &["--no-cache", "reuse.log"]));
+        let terms = out.lines().skip_while(|l| !l.starts_with("terms"));
+        terms.skip(1).take_while(|l| !l.is_empty()).map(String::from).collect::<Vec<_>>()
+    };
+    let merged = terms(r#"{ "verbose": true }"#);
+    assert_eq!(merged.len(), 2);
+    assert!(merged[0].starts_with("t2 at line 4"), "{merged:?}");
+    assert!(merged[0].contains("reused from line 2"), "{merged:?}");
+    let separate = terms(r#"{ "verbose": true, "reuses": true }"#);
+    assert_eq!(separate.len(), 3);
+    assert!(separate[0].starts_with("t0 at line 2"), "{separate:?}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
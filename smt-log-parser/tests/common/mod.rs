@@ -0,0 +1,11 @@
+/// The `matching_loop.log` fixture with each `(at, with)` splice applied in
+/// turn, replacing the first occurrence of `at`. Panics if `at` does not occur
+/// so that a change to the fixture cannot silently turn a splice into a no-op.
+pub fn matching_loop_with(splices: &[(&str, &str)]) -> String {
+    let mut log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    for (at, with) in splices {
+        assert!(log.contains(at), "{at:?} is not in the fixture");
+        log = log.replacen(at, with, 1);
+    }
+    log
+}
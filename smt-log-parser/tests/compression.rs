@@ -0,0 +1,144 @@
+use std::io::{Cursor, Read, Write};
+use std::{cell::Cell, rc::Rc};
+
+use flate2::{write::GzEncoder, Compression as Level};
+use smt_log_parser::{
+    parsers::{
+        compression::{Compression, Decompress},
+        diagnostics::DiagnosticKind,
+        AsyncCursorRead, CursorRead, IntoStreamParser, LogParser,
+    },
+    Z3Parser,
+};
+
+const LOG: &str = "tests/fixtures/matching_loop.log";
+const LOG_ZST: &str = "tests/fixtures/matching_loop.log.zst";
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn decompress(data: &[u8], compression: Compression) -> String {
+    let read = Rc::new(Cell::new(0));
+    let mut reader = Decompress::new(Cursor::new(data), compression, Rc::clone(&read));
+    let mut out = String::new();
+    reader.read_to_string(&mut out).unwrap();
+    assert_eq!(read.get(), data.len());
+    out
+}
+
+fn summary(parser: &Z3Parser) -> String {
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+    serde_json::to_string(parser).unwrap()
+}
+
+#[test]
+fn parse_gzip_log() {
+    let log = std::fs::read(LOG).unwrap();
+    let expected = summary(&Z3Parser::from_str(std::str::from_utf8(&log).unwrap()).process_all());
+    let compressed = gzip(&log);
+
+    let mut parser = compressed
+        .as_slice()
+        .into_cursor()
+        .into_parser::<Z3Parser>();
+    assert!(parser.process_until(|_, _| true).is_none());
+    let state = parser.reader_state();
+    assert_eq!(state.bytes_read, log.len());
+    assert_eq!(state.compressed_bytes_read, Some(compressed.len()));
+    assert_eq!(state.input_bytes_read(), compressed.len());
+    assert_eq!(summary(parser.parser()), expected);
+    drop(parser);
+
+    let parser = Z3Parser::from_async(compressed.into_async_cursor());
+    let parser = futures::executor::block_on(parser.process_all());
+    assert_eq!(summary(&parser), expected);
+}
+
+#[test]
+fn parse_zstd_file() {
+    let (_, plain) = Z3Parser::from_file(LOG).unwrap();
+    let expected = summary(&plain.process_all());
+    let (meta, mut parser) = Z3Parser::from_file(LOG_ZST).unwrap();
+    parser.process_until(|_, _| true);
+    let state = parser.reader_state();
+    assert_eq!(state.compressed_bytes_read, Some(meta.len() as usize));
+    assert_eq!(summary(parser.parser()), expected);
+}
+
+#[test]
+fn plain_log_not_compressed() {
+    let (_, mut parser) = Z3Parser::from_file(LOG).unwrap();
+    parser.process_until(|_, _| true);
+    assert_eq!(parser.reader_state().compressed_bytes_read, None);
+}
+
+#[test]
+fn large_and_multi_part() {
+    // Larger than a single buffered chunk of compressed input.
+    let mut seed = 1u64;
+    let data: String = (0..200_000)
+        .map(|_| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            format!("[line] {:x}\n", seed >> 20)
+        })
+        .collect();
+    let compressed = gzip(data.as_bytes());
+    assert!(compressed.len() > 256 * 1024);
+    assert_eq!(decompress(&compressed, Compression::Gzip), data);
+
+    // Concatenated gzip members and zstd frames.
+    let log = std::fs::read_to_string(LOG).unwrap();
+    let twice = [gzip(log.as_bytes()), gzip(log.as_bytes())].concat();
+    assert_eq!(decompress(&twice, Compression::Gzip), log.repeat(2));
+    let zst = std::fs::read(LOG_ZST).unwrap();
+    assert_eq!(Compression::detect(&zst), Some(Compression::Zstd));
+    let twice = [zst.clone(), zst].concat();
+    assert_eq!(decompress(&twice, Compression::Zstd), log.repeat(2));
+}
+
+#[test]
+fn truncated_input() {
+    let log = std::fs::read(LOG).unwrap();
+    for compressed in [gzip(&log), std::fs::read(LOG_ZST).unwrap()] {
+        let compression = Compression::detect(&compressed).unwrap();
+        let truncated = &compressed[..compressed.len() - 8];
+        let mut reader = Decompress::new(truncated, compression, Rc::default());
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+}
+
+#[test]
+fn truncated_log() {
+    let log = std::fs::read(LOG).unwrap();
+    // Without compression the data is decompressed as it comes in.
+    let mut stored = GzEncoder::new(Vec::new(), Level::none());
+    stored.write_all(&log).unwrap();
+    let stored = stored.finish().unwrap();
+    // Only the trailer is missing, or the log is cut off part way through a
+    // line.
+    for (compressed, cut, insts) in [(gzip(&log), 8, 5), (stored, 600, 1)] {
+        let truncated = &compressed[..compressed.len() - cut];
+        let mut parser = truncated.into_cursor().into_parser::<Z3Parser>();
+        assert!(parser.process_until(|_, _| true).is_none());
+        assert!(parser.is_done());
+        let state = parser.reader_state();
+        let parser = parser.take_parser();
+        let kinds: Vec<_> = parser.diagnostics().iter().map(|d| d.kind).collect();
+        assert_eq!(kinds, [DiagnosticKind::ReadError]);
+        let diagnostic = parser.diagnostics().iter().next().unwrap();
+        assert_eq!(diagnostic.pos.byte_offset, state.bytes_read);
+        assert_eq!(log[state.bytes_read - 1], b'\n');
+        assert_eq!(parser.inst_count(), insts);
+
+        let parser = Z3Parser::from_async(truncated.into_async_cursor());
+        let parser = futures::executor::block_on(parser.process_all());
+        assert_eq!(parser.inst_count(), insts);
+    }
+}
@@ -0,0 +1,150 @@
+use smt_log_parser::{
+    items::{InstIdx, QuantIdx},
+    parsers::z3::{
+        cost::{CostModel, CostModelKind, Descendants, EvenSplit, MAX_DESCENDANTS},
+        inst_graph::InstGraph,
+    },
+    LogParser, Z3Parser,
+};
+
+fn matching_loop() -> Z3Parser {
+    let log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    Z3Parser::from_str(&log).process_all()
+}
+
+/// `q2` has a multi-pattern which is matched by the terms yielded by two
+/// separate instances of `q1`.
+const TWO_PARENTS: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-var] #3 0
+[mk-app] #4 f #3
+[mk-app] #5 pattern #4
+[mk-app] #6 g #3
+[mk-quant] #7 q1 1 #5 #6
+[mk-var] #8 1
+[mk-app] #9 g #8
+[mk-app] #10 pattern #6 #9
+[mk-app] #11 h #3 #8
+[mk-quant] #12 q2 2 #10 #11
+[attach-enode] #1 0
+[attach-enode] #2 0
+[mk-app] #13 f #1
+[attach-enode] #13 0
+[mk-app] #14 f #2
+[attach-enode] #14 0
+[new-match] 0x1 #7 #5 #1 ; #13
+[instance] 0x1 ; 1
+[mk-app] #15 g #1
+[attach-enode] #15 1
+[end-of-instance]
+[new-match] 0x2 #7 #5 #2 ; #14
+[instance] 0x2 ; 1
+[mk-app] #16 g #2
+[attach-enode] #16 1
+[end-of-instance]
+[new-match] 0x3 #12 #10 #1 #2 ; #15 #16
+[instance] 0x3 ; 2
+[end-of-instance]
+";
+
+/// `q1: f(x) = f(g(x))` instantiated `len` times in a chain, as in the
+/// `matching_loop.log` fixture.
+fn long_chain(len: usize) -> Z3Parser {
+    let mut log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    log.truncate(log.find("[new-match]").unwrap());
+    let (mut x, mut f_x) = (1, 9);
+    for i in 1..=len {
+        let (g, f_g) = (2 * i + 8, 2 * i + 9);
+        log += &format!("[new-match] 0x{i:x} #8 #4 #{x} ; #{f_x}\n[instance] 0x{i:x} ; {i}\n");
+        log += &format!("[mk-app] #{g} g #{x}\n[attach-enode] #{g} {i}\n");
+        log += &format!("[mk-app] #{f_g} f #{g}\n[attach-enode] #{f_g} {i}\n[end-of-instance]\n");
+        (x, f_x) = (g, f_g);
+    }
+    Z3Parser::from_str(&log).process_all()
+}
+
+#[test]
+fn chain_costs() {
+    let parser = matching_loop();
+    let q1 = QuantIdx::from(0);
+    let expected: [(CostModelKind, [f32; 5], f32); 4] = [
+        (CostModelKind::EvenSplit, [5.0, 4.0, 3.0, 2.0, 1.0], 10.0),
+        (CostModelKind::Yields, [10.0, 8.0, 6.0, 4.0, 2.0], 20.0),
+        (CostModelKind::Generation, [20.0, 18.0, 15.0, 11.0, 6.0], 50.0),
+        (CostModelKind::Descendants, [5.0, 4.0, 3.0, 2.0, 1.0], 5.0),
+    ];
+    for (kind, insts, quant) in expected {
+        let costs = kind.costs(&parser);
+        assert_eq!(costs.insts.raw, insts, "{kind}");
+        assert_eq!(costs.quants[q1], quant, "{kind}");
+    }
+}
+
+#[test]
+fn descendants_bound() {
+    let parser = long_chain(MAX_DESCENDANTS + 5);
+    assert!(parser.diagnostics().is_empty());
+    let costs = Descendants.costs(&parser);
+    let max = MAX_DESCENDANTS as f32;
+    assert_eq!(costs.insts.raw[..7], [max, max, max, max, max, max, max - 1.0]);
+    assert_eq!(costs.insts.raw.last(), Some(&1.0));
+    assert_eq!(costs.quants[QuantIdx::from(0)], max);
+}
+
+#[test]
+fn compute_costs() {
+    let mut parser = matching_loop();
+    let first = InstIdx::from(0);
+    assert_eq!(parser[first].cost, 0.0);
+    parser.compute_costs(&EvenSplit);
+    assert_eq!(parser[first].cost, 5.0);
+    assert_eq!(parser[QuantIdx::from(0)].cost, 10.0);
+    parser.compute_costs(&CostModelKind::Yields);
+    assert_eq!(parser[first].cost, 10.0);
+    assert_eq!(parser[QuantIdx::from(0)].cost, 20.0);
+}
+
+#[test]
+fn shared_children() {
+    let parser = Z3Parser::from_str(TWO_PARENTS).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let (q1, q2) = (QuantIdx::from(0), QuantIdx::from(1));
+
+    // The child's cost is split between its parents...
+    let costs = EvenSplit.costs(&parser);
+    assert_eq!(costs.insts.raw, [1.5, 1.5, 1.0]);
+    assert_eq!((costs.quants[q1], costs.quants[q2]), (0.0, 1.0));
+
+    // ...but counted in full for each of them here.
+    let costs = Descendants.costs(&parser);
+    assert_eq!(costs.insts.raw, [2.0, 2.0, 1.0]);
+    assert_eq!((costs.quants[q1], costs.quants[q2]), (3.0, 1.0));
+}
+
+#[test]
+fn graph_costs() {
+    let parser = Z3Parser::from_str(TWO_PARENTS).process_all();
+    let mut graph = InstGraph::from(&parser);
+    let info = |graph: &InstGraph, idx| {
+        graph
+            .get_node_info_map()
+            .get_instantiation_info(idx, &parser, false)
+            .cost
+    };
+    assert_eq!(info(&graph, 0), 1.5);
+
+    graph.set_costs(&parser, &Descendants);
+    assert_eq!(info(&graph, 0), 2.0);
+    assert_eq!(info(&graph, 2), 1.0);
+    graph.keep_n_most_costly(2);
+    graph.retain_visible_nodes_and_reconnect();
+    let mut visible: Vec<_> = graph
+        .visible_graph
+        .node_weights()
+        .map(|node| node.inst_idx)
+        .collect();
+    visible.sort();
+    assert_eq!(visible, [InstIdx::from(0), InstIdx::from(1)]);
+}
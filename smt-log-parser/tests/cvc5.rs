@@ -0,0 +1,93 @@
+use smt_log_parser::{
+    items::{InstIdx, MatchKind, QuantIdx, QueryIdx, QueryResult},
+    Cvc5Parser, LogParser,
+};
+
+#[test]
+fn named_quantifiers() {
+    let (_, parser) = Cvc5Parser::from_file("tests/fixtures/cvc5/matching_loop.log").unwrap();
+    let parser = parser.process_all();
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+
+    let q1 = parser.quantifier("q1").unwrap();
+    let q2 = parser.quantifier("q2").unwrap();
+    assert_eq!(parser[q1].num_vars, 1);
+    assert_eq!(parser[q1].instances.len(), 4);
+    assert_eq!(parser.reported_inst_count(q1), Some(4));
+    assert_eq!(parser[q2].num_vars, 2);
+    assert_eq!(parser[q2].instances.len(), 1);
+
+    // `(g a)` is bound in the second instantiation and is the child of the
+    // term bound in the third.
+    let bound = |iidx: usize| {
+        let match_ = &parser[parser[InstIdx::from(iidx)].match_];
+        let MatchKind::Untriggered { quant, bound_terms } = &match_.kind else {
+            panic!("unexpected match {match_:?}");
+        };
+        assert_eq!(*quant, q1);
+        bound_terms[0]
+    };
+    assert_eq!(parser[bound(2)].child_ids[..], [bound(1)]);
+    assert_eq!(parser[bound(1)].child_ids[..], [bound(0)]);
+
+    let queries = parser.queries();
+    assert_eq!(queries.len(), 1);
+    assert_eq!(queries[QueryIdx::from(0)].result, Some(QueryResult::Unsat));
+    assert_eq!(queries[QueryIdx::from(0)].insts.end, InstIdx::from(5));
+}
+
+#[test]
+fn unnamed_quantifiers() {
+    let (_, parser) = Cvc5Parser::from_file("tests/fixtures/cvc5/unnamed.log").unwrap();
+    let parser = parser.process_all();
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+
+    // The same quantifier is identified by its (normalised) term.
+    let quant = parser
+        .quantifier("(forall ((x Int)) (! (> (f x) 0) :pattern ((f x))))")
+        .unwrap();
+    assert_eq!(parser[quant].instances.len(), 3);
+    assert_eq!(parser.quantifiers().len(), 2);
+
+    let queries = parser.queries();
+    assert_eq!(queries.len(), 2);
+    assert_eq!(
+        queries[QueryIdx::from(0)].insts,
+        InstIdx::from(0)..InstIdx::from(2)
+    );
+    assert_eq!(
+        queries[QueryIdx::from(1)].insts,
+        InstIdx::from(2)..InstIdx::from(5)
+    );
+    assert_eq!(
+        queries[QueryIdx::from(1)].result,
+        Some(QueryResult::Unknown)
+    );
+}
+
+#[test]
+fn malformed_entries() {
+    let log = "(instantiations q1\n  ( a )\n  ( a b )\n)\n(unknown-entry)\nunsat\n";
+    let parser = Cvc5Parser::from_str(log).process_all();
+    let kinds: Vec<_> = parser.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(kinds.len(), 2, "{kinds:?}");
+    assert!(parser.insts().is_empty());
+    // The quantifier is not created by an entry which is rejected.
+    assert!(parser.quantifiers().is_empty());
+    assert_eq!(parser.queries().len(), 1);
+
+    // Later instantiations must have as many terms as the earlier ones.
+    let log = "(instantiations q1\n  ( a )\n)\n(instantiations q1\n  ( a b )\n)\n";
+    let parser = Cvc5Parser::from_str(log).process_all();
+    assert_eq!(parser.diagnostics().iter().count(), 1);
+    assert_eq!(parser.insts().len(), 1);
+    assert_eq!(parser.quantifiers()[QuantIdx::from(0)].num_vars, 1);
+}
@@ -0,0 +1,64 @@
+use smt_log_parser::{parsers::diagnostics::DiagnosticKind, LogParser, Z3Parser};
+
+#[test]
+fn diagnostics_have_positions() {
+    let log = "[tool-version] Z3 4.12.1\n\
+               [mk-app] #1 true\n\
+               [foo] bar\n\
+               [mk-app] #2 f #3\n\
+               [push] 0\n\
+               [pop] 1 1\n";
+    let parser = Z3Parser::from_str(log).process_all();
+    let diagnostics = parser.diagnostics();
+    let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind).collect();
+    assert_eq!(
+        kinds,
+        [DiagnosticKind::UnknownTag, DiagnosticKind::UnknownId]
+    );
+
+    let unknown_tag = diagnostics.iter().next().unwrap();
+    assert_eq!(unknown_tag.raw, "[foo] bar");
+    assert_eq!(unknown_tag.pos.line_no, 2);
+    assert_eq!(
+        unknown_tag.pos.byte_offset,
+        "[tool-version] Z3 4.12.1\n[mk-app] #1 true\n".len()
+    );
+    assert!(diagnostics.has_errors());
+}
+
+/// Malformed lines which used to trip assertions are reported instead.
+#[test]
+fn malformed_lines_do_not_panic() {
+    let log = "[tool-version] Z3 4.12.1\n\
+               [mk-var] #1 0\n\
+               [mk-app] #2 f #1\n\
+               [mk-app] #3 pattern #2\n\
+               [mk-quant] #4 q1 1 #3 #2\n\
+               [attach-var-names] #4 (|x| ; |Int|)\n\
+               [attach-var-names] #4 (|y| ; |Int|)\n\
+               [mk-app] #5 a\n\
+               [attach-enode] #5 0\n\
+               [inst-discovered] theory-solving 0x1 #5 ;\n\
+               [push] 0\n\
+               [pop] 0 1\n\
+               [pop] 2 1\n\
+               [mk-app] #6 f #5\n\
+               [attach-enode] #6 0\n\
+               [new-match] 0x2 #4 #3 #5 ; #6\n\
+               [instance] 0x2 ; 1\n\
+               [end-of-instance]\n\
+               [instance] 0x2 ; 1\n";
+    let parser = Z3Parser::from_str(log).process_all();
+    let kinds: Vec<_> = parser.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            DiagnosticKind::Redefinition,
+            DiagnosticKind::MalformedId,
+            DiagnosticKind::StackMismatch,
+            DiagnosticKind::StackMismatch,
+            DiagnosticKind::Redefinition,
+        ]
+    );
+    assert_eq!(parser.inst_count(), 1);
+}
@@ -0,0 +1,78 @@
+use smt_log_parser::{
+    items::{BlameKind, ENodeIdx, MatchIdx},
+    LogParser, Z3Parser,
+};
+
+/// `a = b` holds throughout, but inside a pushed frame `a` is instead
+/// explained to be equal to `c`. `q1` matches once inside the frame and once
+/// after it was popped.
+const PUSH_POP: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 c
+[mk-app] #4 f #1
+[mk-app] #5 f #3
+[mk-var] #6 0
+[mk-app] #7 f #6
+[mk-app] #8 pattern #7
+[mk-app] #9 k #6
+[mk-quant] #10 q1 1 #8 #9
+[mk-app] #11 p
+[mk-app] #12 q
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+[attach-enode] #4 0
+[attach-enode] #5 0
+[attach-enode] #11 0
+[attach-enode] #12 0
+[eq-expl] #1 lit #11 ; #2
+[eq-expl] #2 root
+[push] 0
+[eq-expl] #1 lit #12 ; #3
+[eq-expl] #3 root
+[new-match] 0x1 #10 #8 #3 ; #4 (#1 #3)
+[instance] 0x1 ; 1
+[end-of-instance]
+[pop] 1 1
+[new-match] 0x2 #10 #8 #2 ; #4 (#1 #2)
+[instance] 0x2 ; 1
+[end-of-instance]
+";
+
+fn enodes(idxs: &[usize]) -> Vec<ENodeIdx> {
+    idxs.iter().copied().map(ENodeIdx::from).collect()
+}
+
+fn blamed_equality(parser: &Z3Parser, idx: usize) -> Option<ENodeIdx> {
+    match parser[MatchIdx::from(idx)].blamed[1] {
+        BlameKind::Equality { eq } => Some(eq),
+        _ => None,
+    }
+}
+
+#[test]
+fn popped_equalities_are_ignored() {
+    let parser = Z3Parser::from_str(PUSH_POP).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let (p, q) = (ENodeIdx::from(5), ENodeIdx::from(6));
+    assert_eq!(blamed_equality(&parser, 0), Some(q));
+    // Once the frame is popped `a = b` is current again.
+    assert_eq!(blamed_equality(&parser, 1), Some(p));
+}
+
+#[test]
+fn eclass_history() {
+    let parser = Z3Parser::from_str(PUSH_POP).process_all();
+    let a = ENodeIdx::from(0);
+    let in_frame = parser[MatchIdx::from(0)].pos;
+    let after_pop = parser[MatchIdx::from(1)].pos;
+    assert_eq!(parser.eclass_at(a, in_frame), enodes(&[0, 2]));
+    assert_eq!(parser.root_at(a, in_frame), ENodeIdx::from(2));
+    assert_eq!(parser.eclass_at(a, after_pop), enodes(&[0, 1]));
+    assert_eq!(parser.root_at(a, after_pop), ENodeIdx::from(1));
+    // Before the first `[eq-expl]` each enode is in its own e-class.
+    let start = parser[ENodeIdx::from(6)].pos;
+    assert_eq!(parser.eclass_at(a, start), enodes(&[0]));
+}
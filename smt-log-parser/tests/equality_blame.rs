@@ -0,0 +1,327 @@
+use smt_log_parser::{
+    items::{BlameKind, ENodeIdx, EqualityDependency, InstIdx, MatchIdx},
+    parsers::z3::{
+        cost::{CostModel, Descendants},
+        inst_graph::InstGraph,
+    },
+    LogParser, Z3Parser,
+};
+
+/// A theory-solving instantiation derives `a = b`, which `q1` then uses to
+/// match `f(x)` against `f(a)` with `x = b`. Also `c = b` by an axiom and
+/// `g(a) = g(c)` by congruence.
+const THEORY_EQUALITY: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 c
+[mk-app] #4 f #1
+[mk-var] #5 0
+[mk-app] #6 f #5
+[mk-app] #7 pattern #6
+[mk-app] #8 h #5
+[mk-quant] #9 q1 1 #7 #8
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+[attach-enode] #4 0
+[mk-app] #10 = #3 #2
+[attach-enode] #10 0
+[inst-discovered] theory-solving 0x0 arith# ;
+[instance] 0x0 ; 0
+[mk-app] #11 = #1 #2
+[attach-enode] #11 0
+[end-of-instance]
+[eq-expl] #1 th arith ; #2
+[eq-expl] #3 ax ; #2
+[eq-expl] #2 root
+[new-match] 0x1 #9 #7 #2 ; #4 (#1 #2)
+[instance] 0x1 ; 1
+[end-of-instance]
+[mk-app] #12 g #1
+[attach-enode] #12 0
+[mk-app] #13 g #3
+[attach-enode] #13 0
+[eq-expl] #12 cg (#1 #3) ; #13
+[eq-expl] #13 root
+";
+
+fn enode(idx: usize) -> ENodeIdx {
+    ENodeIdx::from(idx)
+}
+
+#[test]
+fn theory_equality_blame() {
+    let parser = Z3Parser::from_str(THEORY_EQUALITY).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let (theory, q1) = (InstIdx::from(0), InstIdx::from(1));
+    let eq_ab = parser[theory].yields_terms[0];
+
+    // `a = b` depends on the equality term yielded by the theory.
+    let a_expl = &parser[enode(0)].equalities()[0].expl;
+    assert_eq!(a_expl.dependency_on(), [EqualityDependency::Term(eq_ab)]);
+    // `c = b` depends on the (axiom) equality term `c = b`.
+    let c_expl = &parser[enode(2)].equalities()[0].expl;
+    assert_eq!(c_expl.dependency_on(), [EqualityDependency::Term(enode(4))]);
+
+    let blamed: Vec<_> = parser[parser[q1].match_]
+        .due_to_enodes()
+        .map(|(_, enode)| enode)
+        .collect();
+    assert_eq!(blamed, [enode(3), eq_ab]);
+
+    let graph = InstGraph::from(&parser);
+    let edges: Vec<_> = graph
+        .visible_graph
+        .raw_edges()
+        .iter()
+        .map(|e| (e.source().index(), e.target().index()))
+        .collect();
+    assert_eq!(edges, [(0, 1)]);
+}
+
+#[test]
+fn congruence_dependencies() {
+    let parser = Z3Parser::from_str(THEORY_EQUALITY).process_all();
+    let g_a = enode(6);
+    let expl = &parser[g_a].equalities()[0].expl;
+    assert_eq!(
+        expl.dependency_on(),
+        [EqualityDependency::Equality {
+            from: enode(0),
+            to: enode(2)
+        }]
+    );
+}
+
+/// `q1` matches `g(x)` twice, each time using an equality which was not
+/// explained by a prior `[eq-expl]`: `g(a) = g(c)` (which holds by congruence)
+/// and `g(b) = g(d)` (between terms yielded by the first instantiation).
+/// `UNKNOWN_SUFFIX` explains the latter after the fact, but before it is
+/// instantiated (`UNKNOWN_INST`).
+const UNKNOWN_PREFIX: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 c
+[mk-app] #3 g #1
+[mk-app] #4 g #2
+[mk-var] #5 0
+[mk-app] #6 g #5
+[mk-app] #7 pattern #6
+[mk-app] #8 k #5
+[mk-quant] #9 q1 1 #7 #8
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+[attach-enode] #4 0
+[mk-app] #10 = #1 #2
+[attach-enode] #10 0
+[eq-expl] #1 lit #10 ; #2
+[eq-expl] #2 root
+[new-match] 0x1 #9 #7 #2 ; #4 (#3 #4)
+[instance] 0x1 ; 1
+[mk-app] #11 b
+[attach-enode] #11 1
+[mk-app] #12 d
+[attach-enode] #12 1
+[mk-app] #13 g #11
+[attach-enode] #13 1
+[mk-app] #14 g #12
+[attach-enode] #14 1
+[end-of-instance]
+";
+const UNKNOWN_MATCH: &str = "\
+[new-match] 0x2 #9 #7 #11 ; #13 (#13 #14)
+";
+const UNKNOWN_INST: &str = "\
+[instance] 0x2 ; 2
+[end-of-instance]
+";
+const UNKNOWN_SUFFIX: &str = "\
+[mk-app] #15 = #11 #12
+[attach-enode] #15 0
+[eq-expl] #12 lit #15 ; #11
+[eq-expl] #11 root
+[eq-expl] #14 cg (#12 #11) ; #13
+[eq-expl] #13 root
+";
+
+fn blamed(parser: &Z3Parser, idx: usize) -> &[BlameKind] {
+    &parser[MatchIdx::from(idx)].blamed
+}
+
+#[test]
+fn unknown_equality_recovery() {
+    let log = format!("{UNKNOWN_PREFIX}{UNKNOWN_MATCH}{UNKNOWN_INST}");
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(parser.diagnostics().is_empty());
+
+    // `g(a) = g(c)` is recovered by congruence from `a = c`.
+    assert!(matches!(
+        blamed(&parser, 0),
+        [BlameKind::Term { term: t }, BlameKind::Equality { eq }]
+            if *t == enode(3) && *eq == enode(4)
+    ));
+    // `g(b) = g(d)` remains unexplained.
+    let unknown = &blamed(&parser, 1)[1];
+    assert!(unknown.is_unexplained());
+
+    // The GUI shows an edge for it rather than panicking.
+    let graph = InstGraph::from(&parser);
+    let edge_info = graph.get_edge_info_map();
+    let blame_terms: Vec<_> = graph
+        .visible_graph
+        .edge_indices()
+        .map(|edge| edge_info.get_edge_info(edge, &parser, true).blame_term)
+        .collect();
+    assert_eq!(blame_terms, ["g(b)", "g(b) = g(d)"]);
+
+    // An explanation which only comes later is still picked up, as long as
+    // the match has not been instantiated yet.
+    let log = format!("{UNKNOWN_PREFIX}{UNKNOWN_MATCH}{UNKNOWN_SUFFIX}{UNKNOWN_INST}");
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(matches!(
+        blamed(&parser, 1),
+        [BlameKind::Term { .. }, BlameKind::Equality { eq }] if *eq == enode(9)
+    ));
+    let log = format!("{UNKNOWN_PREFIX}{UNKNOWN_MATCH}{UNKNOWN_INST}{UNKNOWN_SUFFIX}");
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(blamed(&parser, 1)[1].is_unexplained());
+}
+
+#[test]
+fn unknown_equality_proof() {
+    let proof = "[mk-app] #15 = #13 #14\n[mk-proof] #16 asserted #15\n";
+    let log = format!("{UNKNOWN_PREFIX}{proof}{UNKNOWN_MATCH}{UNKNOWN_INST}");
+    let parser = Z3Parser::from_str(&log).process_all();
+    // The proof is the last term created.
+    let (proof, _) = parser.terms().last().unwrap();
+    let unknown = &blamed(&parser, 1)[1];
+    assert!(matches!(unknown, BlameKind::UnknownEquality { proof: Some(p), .. } if *p == proof));
+    assert!(!unknown.is_unexplained());
+}
+
+/// `q1` matches `f(x)` against `f(a)` with `x = b`, where `a = b` is a theory
+/// equality. The equality term `a = b` is attached before its sides, so is
+/// only known once it is assigned.
+const EARLY_EQUALITY: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 f #1
+[mk-var] #4 0
+[mk-app] #5 f #4
+[mk-app] #6 pattern #5
+[mk-app] #7 h #4
+[mk-quant] #8 q1 1 #6 #7
+[mk-app] #9 = #1 #2
+[attach-enode] #9 0
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+";
+const EARLY_MATCH: &str = "\
+[eq-expl] #1 th arith ; #2
+[eq-expl] #2 root
+[new-match] 0x1 #8 #6 #2 ; #3 (#1 #2)
+";
+
+#[test]
+fn assigned_equality_blame() {
+    let log = format!("{EARLY_EQUALITY}[assign] #9 axiom\n{EARLY_MATCH}");
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let expl = &parser[enode(1)].equalities()[0].expl;
+    assert_eq!(expl.dependency_on(), [EqualityDependency::Term(enode(0))]);
+    assert!(matches!(
+        blamed(&parser, 0),
+        [BlameKind::Term { .. }, BlameKind::Equality { eq }] if *eq == enode(0)
+    ));
+
+    // Without the assignment the equality is recorded as unexplained.
+    let parser = Z3Parser::from_str(&format!("{EARLY_EQUALITY}{EARLY_MATCH}")).process_all();
+    assert!(parser[enode(1)].equalities()[0].expl.dependency_on().is_empty());
+    assert!(matches!(
+        blamed(&parser, 0),
+        [BlameKind::Term { .. }, BlameKind::UnknownEquality { from, to, .. }]
+            if (*from, *to) == (enode(1), enode(2))
+    ));
+    assert!(blamed(&parser, 0)[1].is_unexplained());
+    // The same holds for axiom equalities.
+    let log = format!("{EARLY_EQUALITY}{}", EARLY_MATCH.replace("th arith", "ax"));
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(blamed(&parser, 0)[1].is_unexplained());
+    // And for equalities of a kind we do not know.
+    let log = format!("{EARLY_EQUALITY}{}", EARLY_MATCH.replace("th arith", "custom"));
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(blamed(&parser, 0)[1].is_unexplained());
+}
+
+/// As `EARLY_EQUALITY`, but the equality term `a = b` is only attached in a
+/// frame which is popped before `a = b` is explained.
+const POPPED_EQUALITY: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 f #1
+[mk-var] #4 0
+[mk-app] #5 f #4
+[mk-app] #6 pattern #5
+[mk-app] #7 h #4
+[mk-quant] #8 q1 1 #6 #7
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+[push] 0
+[mk-app] #9 = #1 #2
+[attach-enode] #9 0
+[pop] 1 1
+";
+
+#[test]
+fn popped_equality_term() {
+    let parser = Z3Parser::from_str(&format!("{POPPED_EQUALITY}{EARLY_MATCH}")).process_all();
+    assert!(parser.diagnostics().is_empty());
+    assert!(parser[enode(0)].equalities()[0].expl.dependency_on().is_empty());
+    assert!(blamed(&parser, 0)[1].is_unexplained());
+}
+
+/// `q1` is instantiated on `f(a)` with `x = b` although `a = b` is not known
+/// yet. The equality term `a = b` is only created by the next instance, and
+/// `a = b` explained after that.
+const LATE_EXPLANATION: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 f #1
+[mk-var] #4 0
+[mk-app] #5 f #4
+[mk-app] #6 pattern #5
+[mk-app] #7 h #4
+[mk-quant] #8 q1 1 #6 #7
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+[new-match] 0x1 #8 #6 #2 ; #3 (#1 #2)
+[instance] 0x1 ; 1
+[end-of-instance]
+[new-match] 0x2 #8 #6 #1 ; #3
+[instance] 0x2 ; 1
+[mk-app] #10 = #1 #2
+[attach-enode] #10 1
+[end-of-instance]
+[eq-expl] #1 lit #10 ; #2
+[eq-expl] #2 root
+";
+
+#[test]
+fn late_explanation() {
+    let parser = Z3Parser::from_str(LATE_EXPLANATION).process_all();
+    assert!(parser.diagnostics().is_empty());
+    // The first instance cannot depend on the second.
+    assert!(blamed(&parser, 0)[1].is_unexplained());
+    let graph = InstGraph::from(&parser);
+    assert_eq!(graph.visible_graph.edge_count(), 0);
+    let costs = Descendants.costs(&parser);
+    assert_eq!(costs.insts.raw, [1.0, 1.0]);
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use smt_log_parser::{
+    items::InstIdx,
+    parsers::z3::{cost::EvenSplit, inst_graph::InstGraph},
+    LogParser, Z3Parser,
+};
+
+/// A second quantifier `q2` triggered by terms created by the first and last
+/// instances of `q1`.
+const Q2: &str = "\
+[mk-var] #20 0
+[mk-var] #21 1
+[mk-app] #22 g #20
+[mk-app] #23 g #21
+[mk-app] #24 pattern #22 #23
+[mk-app] #25 h #20 #21
+[mk-quant] #26 q2 2 #24 #25
+[new-match] 0x6 #26 #24 #1 #16 ; #10 #18
+[instance] 0x6 ; 6
+[end-of-instance]
+";
+
+fn log() -> String {
+    common::matching_loop_with(&[("[pop] 1 1\n", Q2)])
+}
+
+/// Everything about each node which is computed when building the graph.
+fn nodes(graph: &InstGraph, count: usize) -> Vec<(usize, Option<usize>, usize, usize)> {
+    (0..count)
+        .map(InstIdx::from)
+        .map(|inst| {
+            let node = graph.node_data(inst);
+            let size = graph.subgraph_size(inst);
+            (node.child_count(), node.min_depth, node.max_depth(), size)
+        })
+        .collect()
+}
+
+fn edges(graph: &InstGraph) -> Vec<(usize, usize)> {
+    let edges = graph.visible_graph.raw_edges().iter();
+    edges.map(|e| (e.source().index(), e.target().index())).collect()
+}
+
+fn most_branching(mut graph: InstGraph, n: usize) -> Vec<InstIdx> {
+    graph.keep_n_most_branching(n);
+    graph.retain_visible_nodes_and_reconnect();
+    graph.visible_graph.node_weights().map(|node| node.inst_idx).collect()
+}
+
+fn costs(graph: &InstGraph) -> Vec<f32> {
+    graph.visible_graph.node_weights().map(|node| node.cost()).collect()
+}
+
+fn most_costly(mut graph: InstGraph, n: usize) -> Vec<InstIdx> {
+    graph.keep_n_most_costly(n);
+    graph.retain_visible_nodes_and_reconnect();
+    graph.visible_graph.node_weights().map(|node| node.inst_idx).collect()
+}
+
+#[test]
+fn extend_while_parsing() {
+    let log = log();
+    let full = Z3Parser::from_str(&log).process_all();
+    assert!(full.diagnostics().is_empty());
+    let expected = InstGraph::from(&full);
+    assert_eq!(full.inst_count(), 6);
+    assert_eq!(edges(&expected), [(0, 1), (1, 2), (2, 3), (3, 4), (0, 5), (4, 5)]);
+
+    let mut parser = Z3Parser::from_str(&log);
+    // Stop in the middle of the third instance.
+    parser.process_until(|_, state| state.lines_read < 32);
+    assert_eq!(parser.parser().inst_count(), 3);
+    let mut graph = InstGraph::from(parser.parser());
+    assert_eq!(edges(&graph), [(0, 1), (1, 2)]);
+    assert_eq!(nodes(&graph, 3), [(1, Some(0), 0, 3), (1, Some(1), 1, 2), (0, Some(2), 2, 1)]);
+
+    parser.process_until(|_, _| true);
+    graph.extend(parser.parser());
+    assert_eq!(edges(&graph), edges(&expected));
+    assert_eq!(nodes(&graph, 6), nodes(&expected, 6));
+    assert_eq!(graph.node_data(InstIdx::from(5)).min_depth, Some(1));
+    assert_eq!(graph.node_data(InstIdx::from(5)).max_depth(), 5);
+
+    // Ranks are updated for both new nodes and the parents of new nodes.
+    let top = [InstIdx::from(0), InstIdx::from(1)];
+    assert_eq!(most_branching(graph.clone(), 2), top);
+    assert_eq!(most_branching(graph, 2), most_branching(expected, 2));
+}
+
+#[test]
+fn extend_costs() {
+    let log = log();
+    let mut full = Z3Parser::from_str(&log).process_all();
+    let expected = InstGraph::from(&full);
+    full.compute_costs(&EvenSplit);
+    let parser_costs: Vec<_> = (0..6).map(|idx| full[InstIdx::from(idx)].cost).collect();
+    assert_eq!(costs(&expected), parser_costs);
+
+    // The graph costs its nodes itself, also while the log is being parsed.
+    let mut parser = Z3Parser::from_str(&log);
+    parser.process_until(|_, state| state.lines_read < 32);
+    assert_eq!(parser.parser()[InstIdx::from(0)].cost, 0.0);
+    let mut graph = InstGraph::from(parser.parser());
+    assert_eq!(costs(&graph), [3.0, 2.0, 1.0]);
+
+    // The ancestors of the new nodes are updated and reranked.
+    parser.process_until(|_, _| true);
+    graph.extend(parser.parser());
+    assert_eq!(costs(&graph), costs(&expected));
+    assert_eq!(graph.node_data(InstIdx::from(0)).cost(), 6.0);
+    assert_eq!(most_costly(graph, 3), most_costly(expected, 3));
+}
+
+#[test]
+fn extend_filtered() {
+    let log = log();
+    let mut parser = Z3Parser::from_str(&log);
+    parser.process_until(|_, state| state.lines_read < 32);
+    let mut graph = InstGraph::from(parser.parser());
+    graph.retain_nodes(|node| node.inst_idx != InstIdx::from(1));
+    graph.retain_visible_nodes_and_reconnect();
+    assert_eq!(graph.visible_graph.node_count(), 2);
+
+    // The filtered graph is only updated once reconnected, and reachability
+    // takes the new nodes into account.
+    parser.process_until(|_, _| true);
+    graph.extend(parser.parser());
+    assert_eq!(graph.visible_graph.node_count(), 2);
+    graph.retain_visible_nodes_and_reconnect();
+    assert_eq!(graph.visible_graph.node_count(), 5);
+    assert_eq!(edges(&graph), [(1, 2), (2, 3), (0, 4), (3, 4), (0, 1)]);
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use smt_log_parser::{
+    display_with::{DisplayCtxt, DisplayWithCtxt},
+    items::{InstIdx, QuantIdx},
+    parsers::z3::inst_graph::InstGraph,
+    LogParser, Z3Parser,
+};
+
+fn explain(parser: &Z3Parser) -> Vec<String> {
+    let ctxt = DisplayCtxt {
+        parser,
+        display_term_ids: false,
+        display_quantifier_name: false,
+        use_mathematical_symbols: false,
+    };
+    InstGraph::from(parser)
+        .matching_loops()
+        .iter()
+        .map(|l| l.explain(parser).with(&ctxt).to_string())
+        .collect()
+}
+
+#[test]
+fn single_quantifier_loop() {
+    let log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    let parser = Z3Parser::from_str(&log).process_all();
+    let loops = InstGraph::from(&parser).matching_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].quants, [QuantIdx::from(0)]);
+    assert_eq!(loops[0].nodes.len(), 5);
+    assert_eq!(
+        loops[0].chain,
+        (0..5).map(InstIdx::from).collect::<Vec<_>>()
+    );
+    assert_eq!(loops[0].iterations(), 5);
+    assert_eq!(
+        explain(&parser),
+        ["q1 produces f(g(_x0)), which re-triggers q1 on g(_x0)"]
+    );
+}
+
+/// `q1: f(x) = g(h(x))` and `q2: g(y) = f(y)` trigger each other on ever
+/// deeper `h` terms, though neither loops on its own.
+fn two_quantifier_log(iterations: usize) -> String {
+    let mut log = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-var] #2 0
+[mk-app] #3 f #2
+[mk-app] #4 pattern #3
+[mk-app] #5 h #2
+[mk-app] #6 g #5
+[mk-app] #7 = #3 #6
+[mk-quant] #8 q1 1 #4 #7
+[mk-app] #9 g #2
+[mk-app] #10 pattern #9
+[mk-app] #11 = #9 #3
+[mk-quant] #12 q2 1 #10 #11
+[attach-enode] #1 0
+[mk-app] #13 f #1
+[attach-enode] #13 0
+"
+    .to_string();
+    let (mut arg, mut f_term, mut next) = (1, 13, 14);
+    for i in 0..iterations {
+        let (q1, q2) = (2 * i + 1, 2 * i + 2);
+        log += &format!(
+            "\
+[new-match] 0x{q1:x} #8 #4 #{arg} ; #{f_term}
+[instance] 0x{q1:x} ; {q1}
+[mk-app] #{next} h #{arg}
+[attach-enode] #{next} {q1}
+[mk-app] #{} g #{next}
+[attach-enode] #{} {q1}
+[end-of-instance]
+[new-match] 0x{q2:x} #12 #10 #{next} ; #{}
+[instance] 0x{q2:x} ; {q2}
+[mk-app] #{} f #{next}
+[attach-enode] #{} {q2}
+[end-of-instance]
+",
+            next + 1,
+            next + 1,
+            next + 1,
+            next + 2,
+            next + 2,
+        );
+        (arg, f_term, next) = (next, next + 2, next + 3);
+    }
+    log
+}
+
+#[test]
+fn multi_quantifier_loop() {
+    let parser = Z3Parser::from_str(&two_quantifier_log(4)).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let loops = InstGraph::from(&parser).matching_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].quants, [QuantIdx::from(0), QuantIdx::from(1)]);
+    assert_eq!(loops[0].nodes.len(), 8);
+    assert_eq!(loops[0].iterations(), 4);
+    assert_eq!(
+        explain(&parser),
+        ["q1 produces g(h(_x0)), which triggers q2 on h(_x0)\n\
+             q2 produces f(h(_x0)), which re-triggers q1 on h(_x0)"]
+    );
+
+    // Two iterations are a chain, not a loop.
+    let parser = Z3Parser::from_str(&two_quantifier_log(2)).process_all();
+    assert!(InstGraph::from(&parser).matching_loops().is_empty());
+}
+
+/// The third instantiation is triggered by a ground term `f(c)`, which is
+/// only connected to the loop by the unexplained equality `f(c) = f(g(g(a)))`.
+#[test]
+fn loop_through_unknown_equality() {
+    let log = common::matching_loop_with(&[
+        (
+            "[attach-enode] #9 0\n",
+            "[attach-enode] #9 0\n[mk-app] #20 c\n[mk-app] #21 f #20\n[attach-enode] #21 0\n",
+        ),
+        (
+            "[new-match] 0x3 #8 #4 #12 ; #13",
+            "[new-match] 0x3 #8 #4 #12 ; #21 (#21 #13)",
+        ),
+    ]);
+    let parser = Z3Parser::from_str(&log).process_all();
+    let loops = InstGraph::from(&parser).matching_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(
+        loops[0].chain,
+        (0..5).map(InstIdx::from).collect::<Vec<_>>()
+    );
+    // The term produced for the third instantiation is the side of the
+    // equality created by the second.
+    assert_eq!(
+        explain(&parser),
+        ["q1 produces f(g(_x0)), which re-triggers q1 on g(_x0)"]
+    );
+}
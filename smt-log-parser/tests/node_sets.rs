@@ -0,0 +1,63 @@
+use petgraph::{graph::NodeIndex, Direction};
+use roaring::RoaringBitmap;
+use smt_log_parser::{
+    items::InstIdx,
+    parsers::z3::inst_graph::InstGraph,
+    LogParser, Z3Parser,
+};
+
+/// The chain of five instantiations `0 -> 1 -> 2 -> 3 -> 4`.
+fn graph() -> InstGraph {
+    let log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(parser.diagnostics().is_empty());
+    InstGraph::from(&parser)
+}
+
+fn set(nodes: &[u32]) -> RoaringBitmap {
+    nodes.iter().copied().collect()
+}
+
+#[test]
+fn node_sets() {
+    let graph = graph();
+    let node = NodeIndex::new;
+    assert_eq!(graph.all_nodes(), set(&[0, 1, 2, 3, 4]));
+    assert_eq!(graph.visible_nodes(), &graph.all_nodes());
+    assert_eq!(graph.descendants(node(2)), set(&[2, 3, 4]));
+    assert_eq!(graph.ancestors(node(2)), set(&[0, 1, 2]));
+    assert_eq!(graph.neighbours(node(2), Direction::Incoming), set(&[1]));
+    assert_eq!(graph.neighbours(node(2), Direction::Outgoing), set(&[3]));
+    let odd = graph.nodes_where(|node| usize::from(node.inst_idx) % 2 == 1);
+    assert_eq!(odd, set(&[1, 3]));
+    // Each instantiation is more costly than those which depend on it.
+    assert_eq!(graph.n_most_costly(2, &odd), set(&[1, 3]));
+    assert_eq!(graph.n_most_costly(1, &odd), set(&[1]));
+    assert_eq!(graph.n_most_costly(1, &graph.descendants(node(2))), set(&[2]));
+    let path: Vec<_> = (0..5).map(node).collect();
+    assert_eq!(graph.longest_path_through(node(2)), path);
+}
+
+#[test]
+fn combine_filters() {
+    let mut graph = graph();
+    let node = NodeIndex::new;
+    // Filters are independent of the order in which their sets are computed.
+    let nodes = (graph.descendants(node(1)) - graph.descendants(node(3))) | graph.ancestors(node(0));
+    assert_eq!(nodes, set(&[0, 1, 2]));
+    graph.set_visible_nodes(nodes);
+    graph.retain_visible_nodes_and_reconnect();
+    let insts: Vec<_> = graph.visible_graph.node_weights().map(|node| node.inst_idx).collect();
+    assert_eq!(insts, [InstIdx::from(0), InstIdx::from(1), InstIdx::from(2)]);
+    assert_eq!(graph.visible_graph.edge_count(), 2);
+
+    // The mutating filters are the same as intersecting with a set.
+    let mut retained = graph.clone();
+    retained.reset_visibility_to(true);
+    retained.retain_nodes(|node| node.inst_idx != InstIdx::from(1));
+    retained.keep_n_most_costly(3);
+    let all = graph.all_nodes();
+    let expected = graph.n_most_costly(3, &(all - set(&[1])));
+    assert_eq!(retained.visible_nodes(), &expected);
+    assert_eq!(expected, set(&[0, 2, 3]));
+}
@@ -0,0 +1,102 @@
+use std::{cell::RefCell, rc::Rc};
+
+use fxhash::FxHashMap;
+use smt_log_parser::{
+    items::QuantIdx,
+    parsers::observer::{ParseEvent, ParseObserver},
+    LogParser, Z3Parser,
+};
+
+const LOG: &str = "tests/fixtures/matching_loop.log";
+
+/// Alerts once a quantifier has been instantiated more than `limit` times.
+#[derive(Default)]
+struct RunawayQuantifiers {
+    limit: usize,
+    counts: FxHashMap<QuantIdx, usize>,
+    alerts: Rc<RefCell<Vec<(QuantIdx, usize)>>>,
+    done: Rc<RefCell<bool>>,
+}
+
+impl ParseObserver<Z3Parser> for RunawayQuantifiers {
+    fn on_event(&mut self, parser: &Z3Parser, event: ParseEvent) {
+        let ParseEvent::InstStart(iidx) = event else {
+            return;
+        };
+        let quant = parser[parser[iidx].match_].kind.quant_idx().unwrap();
+        let count = self.counts.entry(quant).or_default();
+        *count += 1;
+        if *count == self.limit + 1 {
+            self.alerts.borrow_mut().push((quant, usize::from(iidx)));
+        }
+    }
+    fn on_eof(&mut self, _parser: &Z3Parser) {
+        *self.done.borrow_mut() = true;
+    }
+}
+
+#[test]
+fn streaming_events() {
+    let (_, mut parser) = Z3Parser::from_file(LOG).unwrap();
+    let runaway = RunawayQuantifiers {
+        limit: 3,
+        ..Default::default()
+    };
+    let (alerts, done) = (Rc::clone(&runaway.alerts), Rc::clone(&runaway.done));
+    parser.add_observer(runaway);
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let all = Rc::clone(&events);
+    parser.add_observer(move |_: &Z3Parser, event| all.borrow_mut().push(event));
+
+    // Events are delivered while parsing, not only at the end.
+    parser.process_until(|_, state| state.lines_read < 17);
+    assert_eq!(events.borrow().last(), Some(&ParseEvent::NewTerm(9.into())));
+    assert!(alerts.borrow().is_empty());
+    parser.process_until(|_, _| true);
+    assert!(*done.borrow());
+    assert_eq!(*alerts.borrow(), [(QuantIdx::from(0), 3)]);
+
+    let events = events.borrow();
+    let count = |f: fn(&ParseEvent) -> bool| events.iter().filter(|e| f(e)).count();
+    assert_eq!(count(|e| matches!(e, ParseEvent::NewTerm(_))), 19);
+    assert_eq!(count(|e| matches!(e, ParseEvent::NewQuantifier(_))), 1);
+    assert_eq!(count(|e| matches!(e, ParseEvent::NewENode(_))), 12);
+    assert_eq!(count(|e| matches!(e, ParseEvent::NewMatch(_))), 5);
+    assert_eq!(count(|e| matches!(e, ParseEvent::InstEnd(_))), 5);
+    assert_eq!(events.first(), Some(&ParseEvent::NewTerm(0.into())));
+    assert_eq!(events.last(), Some(&ParseEvent::Pop { num: 1, scope: 1 }));
+    assert!(events.contains(&ParseEvent::Push { scope: 0 }));
+}
+
+#[test]
+fn no_events_without_observers() {
+    let (_, mut parser) = Z3Parser::from_file(LOG).unwrap();
+    parser.process_until(|_, _| true);
+    let mut parser = parser.take_parser();
+    let mut events = Vec::new();
+    parser.take_events(&mut events);
+    assert!(events.is_empty());
+}
+
+#[test]
+fn stack_events_on_mismatch() {
+    let log = "[tool-version] Z3 4.12.1\n\
+               [push] 0\n\
+               [push] 2\n\
+               [pop] 0 3\n\
+               [pop] 1 3\n";
+    let mut parser = Z3Parser::from_str(log);
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let all = Rc::clone(&events);
+    parser.add_observer(move |_: &Z3Parser, event| all.borrow_mut().push(event));
+    let parser = parser.process_all();
+    assert_eq!(parser.diagnostics().len(), 2);
+    assert_eq!(
+        *events.borrow(),
+        [
+            ParseEvent::Push { scope: 0 },
+            ParseEvent::Push { scope: 2 },
+            ParseEvent::Pop { num: 1, scope: 3 },
+        ]
+    );
+}
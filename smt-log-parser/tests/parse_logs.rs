@@ -4,8 +4,6 @@ use smt_log_parser::{LogParser, Z3Parser};
 
 #[test]
 fn parse_all_logs() {
-    std::env::set_var("SLP_TEST_MODE", "true");
-
     let mut all_logs: Vec<_> = std::fs::read_dir("../logs").unwrap().map(|r| r.unwrap()).collect();
     all_logs.sort_by_key(|dir| dir.path());
     for log in all_logs {
@@ -43,6 +41,9 @@ fn parse_all_logs() {
             });
             let elapsed = now.elapsed();
             println!("Finished parsing in {elapsed:?} ({} kB/ms)", file_size_kb as u128 / elapsed.as_millis());
+            let parser = parser.take_parser();
+            let errors: Vec<_> = parser.diagnostics().errors().collect();
+            assert!(errors.is_empty(), "Errors while parsing {}: {errors:#?}", filename.display());
             println!();
             drop(parser);
         });
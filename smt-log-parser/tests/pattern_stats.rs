@@ -0,0 +1,78 @@
+use smt_log_parser::{
+    items::{QuantIdx, TermIdx},
+    parsers::z3::pattern_stats::PatternStats,
+    LogParser, Z3Parser,
+};
+
+/// `q1` matches `f(x)` against both `f(a)` and `f(b)` where `a = b`, only the
+/// first is instantiated and its instance triggers `q2`.
+const REDUNDANT_MATCH: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 f #1
+[mk-app] #4 f #2
+[mk-var] #5 0
+[mk-app] #6 f #5
+[mk-app] #7 pattern #6
+[mk-app] #8 g #5
+[mk-quant] #9 q1 1 #7 #8
+[mk-app] #10 pattern #8
+[mk-app] #11 h #5
+[mk-quant] #12 q2 1 #10 #11
+[mk-app] #13 = #2 #1
+[attach-enode] #1 0
+[attach-enode] #2 0
+[attach-enode] #3 0
+[attach-enode] #4 0
+[attach-enode] #13 0
+[eq-expl] #2 lit #13 ; #1
+[eq-expl] #1 root
+[new-match] 0x1 #9 #7 #1 ; #3
+[instance] 0x1 ; 1
+[mk-app] #14 g #1
+[attach-enode] #14 1
+[end-of-instance]
+[new-match] 0x2 #9 #7 #2 ; #4
+[new-match] 0x3 #12 #10 #1 ; #14
+[instance] 0x3 ; 2
+[end-of-instance]
+";
+
+#[test]
+fn pattern_stats() {
+    let parser = Z3Parser::from_str(REDUNDANT_MATCH).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let stats = PatternStats::compute(&parser);
+    let expected = [
+        PatternStats {
+            quant: QuantIdx::from(0),
+            name: "q1".to_string(),
+            pattern: TermIdx::from(6),
+            pattern_text: "{f(qvar_0)}".to_string(),
+            matches: 2,
+            instantiations: 1,
+            pending_matches: 1,
+            distinct_bindings: 2,
+            distinct_bindings_modulo_eq: 1,
+            productive_instantiations: 1,
+            children: 1,
+            permissiveness: 1,
+        },
+        PatternStats {
+            quant: QuantIdx::from(1),
+            name: "q2".to_string(),
+            pattern: TermIdx::from(9),
+            pattern_text: "{g(qvar_0)}".to_string(),
+            matches: 1,
+            instantiations: 1,
+            pending_matches: 0,
+            distinct_bindings: 1,
+            distinct_bindings_modulo_eq: 1,
+            productive_instantiations: 0,
+            children: 0,
+            permissiveness: 1,
+        },
+    ];
+    assert_eq!(stats, expected);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use smt_log_parser::{
+    items::{InstIdx, MatchIdx},
+    parsers::z3::{inst_graph::InstGraph, pattern_stats::PatternStats},
+    LogParser, Z3Parser,
+};
+
+/// After the loop `q1` matches the last term it created without instantiating
+/// it, and matches `f(a)` twice with the same fingerprint, instantiating only
+/// the second match.
+const PENDING: &str = "\
+[new-match] 0x6 #8 #4 #18 ; #19
+[new-match] 0x7 #8 #4 #1 ; #9
+[new-match] 0x7 #8 #4 #1 ; #9
+[instance] 0x7 ; 1
+[end-of-instance]
+";
+
+fn pending() -> Z3Parser {
+    let log = common::matching_loop_with(&[("[pop] 1 1\n", PENDING)]);
+    Z3Parser::from_str(&log).process_all()
+}
+
+#[test]
+fn pending_matches() {
+    let parser = pending();
+    assert!(parser.diagnostics().is_empty());
+    let pending: Vec<_> = parser.pending_matches().map(|(midx, _)| midx).collect();
+    assert_eq!(pending, [MatchIdx::from(5), MatchIdx::from(6)]);
+    // A repeated fingerprint is instantiated by the latest match.
+    assert_eq!(parser.instance_of(MatchIdx::from(7)), Some(InstIdx::from(5)));
+
+    let stats = PatternStats::compute(&parser);
+    assert_eq!((stats[0].matches, stats[0].instantiations, stats[0].pending_matches), (8, 6, 2));
+}
+
+#[test]
+fn ghost_nodes() {
+    let parser = pending();
+    let mut graph = InstGraph::from(&parser);
+    assert!(graph.visible_ghosts().is_empty());
+    graph.add_pending_matches(&parser);
+    // Only the first pending match was triggered by a term created by an
+    // instantiation.
+    let ghosts = graph.visible_ghosts();
+    assert_eq!(ghosts.len(), 1);
+    let (midx, parents) = &ghosts[0];
+    assert_eq!(*midx, MatchIdx::from(5));
+    let parents: Vec<_> = parents.iter().map(|&node| graph.visible_graph[node].inst_idx).collect();
+    assert_eq!(parents, [InstIdx::from(4)]);
+
+    graph.retain_nodes(|node| node.inst_idx != InstIdx::from(4));
+    graph.retain_visible_nodes_and_reconnect();
+    assert!(graph.visible_ghosts().is_empty());
+}
@@ -0,0 +1,65 @@
+use smt_log_parser::{
+    items::InstIdx,
+    parsers::z3::proof::{ProofGraph, ProofRule},
+    LogParser, Z3Parser,
+};
+
+const LOG: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 a
+[mk-var] #2 0
+[mk-app] #3 f #2
+[mk-app] #4 pattern #3
+[mk-app] #5 P #3
+[mk-app] #6 b
+[mk-quant] #8 q1 1 #4 #5
+[attach-var-names] #8 (|x| ; |Int|)
+[mk-app] #9 f #1
+[mk-app] #10 P #9
+[mk-app] #11 not #8
+[mk-app] #12 or #11 #10
+[mk-proof] #13 quant-inst #12
+[mk-app] #14 f #6
+[mk-app] #15 P #14
+[mk-app] #16 or #11 #15
+[mk-proof] #17 quant-inst #16
+[mk-proof] #18 asserted #8
+[mk-app] #19 not #10
+[mk-proof] #20 asserted #19
+[mk-app] #21 false
+[mk-proof] #22 unit-resolution #13 #18 #20 #21
+[attach-enode] #1 0
+[attach-enode] #9 0
+[attach-enode] #6 0
+[attach-enode] #14 0
+[new-match] 0x1 #8 #4 #1 ; #9
+[instance] 0x1 #13 ; 1
+[end-of-instance]
+[new-match] 0x2 #8 #4 #6 ; #14
+[instance] 0x2 #17 ; 1
+[end-of-instance]
+";
+
+#[test]
+fn used_instantiations() {
+    let parser = Z3Parser::from_str(LOG).process_all();
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+
+    let proofs = ProofGraph::from(&parser);
+    assert_eq!(proofs.graph.node_count(), 5);
+    let [root] = proofs.roots() else {
+        panic!("expected a single proof of false: {:?}", proofs.roots());
+    };
+    assert_eq!(proofs.graph[*root].rule, ProofRule::UnitResolution);
+    assert_eq!(proofs.premises(*root).count(), 3);
+
+    let (first, second) = (InstIdx::from(0), InstIdx::from(1));
+    let step = proofs.inst_step(first).unwrap();
+    assert_eq!(proofs.graph[step].rule, ProofRule::QuantInst);
+    assert!(proofs.inst_step(second).is_some());
+    assert_eq!(proofs.used_insts(), vec![first]);
+}
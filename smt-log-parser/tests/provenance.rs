@@ -0,0 +1,41 @@
+mod common;
+
+use smt_log_parser::{items::InstIdx, parsers::z3::inst_graph::InstGraph, LogParser, Z3Parser};
+
+#[test]
+fn inst_info_has_log_positions() {
+    let log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    let parser = Z3Parser::from_str(&log).process_all();
+    let inst_graph = InstGraph::from(&parser);
+    let node_info = inst_graph.get_node_info_map();
+
+    let lines: Vec<_> = log.lines().collect();
+    for node in 0..5 {
+        let info = node_info.get_instantiation_info(node, &parser, false);
+        let inst_line = lines[info.inst_pos.line_no];
+        let match_line = lines[info.match_pos.line_no];
+        assert!(inst_line.starts_with("[instance]"), "{inst_line}");
+        assert!(match_line.starts_with("[new-match]"), "{match_line}");
+        assert_eq!(
+            &log[info.inst_pos.byte_offset..][..inst_line.len()],
+            inst_line
+        );
+    }
+}
+
+/// Entries continued over several lines count as all of their lines, so that
+/// later positions still point at the right line.
+#[test]
+fn multi_line_entries() {
+    let multi_line = "[mk-quant] #8 q1\nloop\nbody 1 #4 #7\n";
+    let log = common::matching_loop_with(&[("[mk-quant] #8 q1 1 #4 #7\n", multi_line)]);
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(parser.diagnostics().is_empty());
+    let lines: Vec<_> = log.lines().collect();
+    for idx in 0..parser.inst_count() {
+        let inst = &parser[InstIdx::from(idx)];
+        assert!(lines[inst.pos.line_no].starts_with("[instance]"));
+        assert_eq!(log[inst.pos.byte_offset..].lines().next(), Some(lines[inst.pos.line_no]));
+    }
+    assert_eq!(parser.inst_count(), 5);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use smt_log_parser::{
+    items::{InstIdx, QuantIdx},
+    parsers::z3::{cost::EvenSplit, inst_graph::InstGraph, quant_stats::QuantifierStats},
+    LogParser, Z3Parser,
+};
+
+/// A second quantifier `q2` with one instance triggered by a term created by
+/// `q1` and one match that is never instantiated.
+const Q2: &str = "\
+[mk-var] #20 0
+[mk-app] #21 g #20
+[mk-app] #22 pattern #21
+[mk-app] #23 h #20
+[mk-quant] #24 q2 1 #22 #23
+[new-match] 0x6 #24 #22 #1 ; #10
+[instance] 0x6 ; 2
+[end-of-instance]
+[new-match] 0x7 #24 #22 #12 ; #12
+";
+
+#[test]
+fn quantifier_stats() {
+    let log = common::matching_loop_with(&[("[pop] 1 1\n", Q2)]);
+    let parser = Z3Parser::from_str(&log).process_all();
+    let (q1, q2) = (QuantIdx::from(0), QuantIdx::from(1));
+    let insts = |range: std::ops::Range<usize>| range.map(InstIdx::from).collect::<Vec<_>>();
+    assert_eq!(parser[q1].instances, insts(0..5));
+    assert_eq!(parser[q2].instances, insts(5..6));
+
+    let stats = QuantifierStats::compute(&parser);
+    assert_eq!(stats.len(), 2);
+    let expected = QuantifierStats {
+        quant: q1,
+        name: "q1".to_string(),
+        matches: 5,
+        instantiations: 5,
+        unused_matches: 0,
+        cost: parser[q1].cost,
+        max_generation: Some(5),
+        mean_generation: Some(3.0),
+        max_depth: 4,
+        yielded_enodes: 10,
+        children: 5,
+        foreign_children: 1,
+    };
+    assert_eq!(stats[q1], expected);
+    let expected = QuantifierStats {
+        quant: q2,
+        name: "q2".to_string(),
+        matches: 2,
+        instantiations: 1,
+        unused_matches: 1,
+        cost: parser[q2].cost,
+        max_generation: Some(2),
+        mean_generation: Some(2.0),
+        max_depth: 1,
+        yielded_enodes: 0,
+        children: 0,
+        foreign_children: 0,
+    };
+    assert_eq!(stats[q2], expected);
+}
+
+/// The statistics, costs and graph agree on the parents of an instantiation,
+/// including those only blamed through an unexplained equality.
+#[test]
+fn unknown_equality_parents() {
+    let log = common::matching_loop_with(&[
+        (
+            "[attach-enode] #9 0\n",
+            "[attach-enode] #9 0\n[mk-app] #20 c\n[mk-app] #21 f #20\n[attach-enode] #21 0\n",
+        ),
+        (
+            "[new-match] 0x3 #8 #4 #12 ; #13",
+            "[new-match] 0x3 #8 #4 #12 ; #21 (#21 #13)",
+        ),
+    ]);
+    let mut parser = Z3Parser::from_str(&log).process_all();
+    parser.compute_costs(&EvenSplit);
+    let q1 = QuantIdx::from(0);
+    let stats = QuantifierStats::compute(&parser);
+    assert_eq!((stats[q1].max_depth, stats[q1].children), (4, 4));
+    assert_eq!(parser[InstIdx::from(0)].cost, 5.0);
+    let graph = InstGraph::from(&parser);
+    assert_eq!(graph.visible_graph.edge_count(), 4);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use smt_log_parser::{
+    items::{InstIdx, QuantIdx, QueryIdx, QueryResult},
+    parsers::z3::{inst_graph::InstGraph, pattern_stats::PatternStats, quant_stats::QuantifierStats},
+    LogParser, Z3Parser,
+};
+
+/// The matching loop split into a first query with two instantiations and a
+/// second, unfinished, one with the other three.
+fn two_queries() -> Z3Parser {
+    let log = common::matching_loop_with(&[
+        ("[new-match]", "[begin-check] 1\n[new-match]"),
+        (
+            "[new-match] 0x3",
+            "[query-done] unsat\n[begin-check] 1\n[new-match] 0x3",
+        ),
+    ]);
+    Z3Parser::from_str(&log).process_all()
+}
+
+#[test]
+fn split_at_queries() {
+    let parser = two_queries();
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+
+    let queries = parser.queries();
+    assert_eq!(queries.len(), 2);
+    let (first, second) = (QueryIdx::from(0), QueryIdx::from(1));
+    assert_eq!(queries[first].result, Some(QueryResult::Unsat));
+    assert!(queries[second].end.is_none());
+    assert_eq!(parser.query_insts(first).count(), 2);
+    assert_eq!(parser.query_insts(second).count(), 3);
+    assert_eq!(parser.query_of_inst(InstIdx::from(1)), Some(first));
+    assert_eq!(parser.query_of_inst(InstIdx::from(2)), Some(second));
+
+    let inst_graph = InstGraph::from(&parser);
+    let node_info = inst_graph.get_node_info_map();
+    let info = node_info.get_instantiation_info(4, &parser, false);
+    assert_eq!(info.query, Some(second));
+}
+
+#[test]
+fn query_scoped_stats() {
+    let parser = two_queries();
+    let (first, second) = (QueryIdx::from(0), QueryIdx::from(1));
+    let q1 = QuantIdx::from(0);
+
+    let stats = QuantifierStats::compute_for_query(&parser, first);
+    assert_eq!((stats[q1].matches, stats[q1].instantiations), (2, 2));
+    assert_eq!((stats[q1].max_depth, stats[q1].children), (1, 1));
+    // The first instantiation of the second query depends on one of the first.
+    let stats = QuantifierStats::compute_for_query(&parser, second);
+    assert_eq!((stats[q1].matches, stats[q1].instantiations), (3, 3));
+    assert_eq!((stats[q1].max_depth, stats[q1].children), (2, 2));
+    let cost: f32 = parser.query_insts(second).map(|iidx| parser[iidx].cost).sum();
+    assert_eq!(stats[q1].cost, cost);
+    assert_eq!(QuantifierStats::compute(&parser)[q1].children, 4);
+
+    let patterns = PatternStats::compute_for_query(&parser, second);
+    assert_eq!(patterns.len(), 1);
+    assert_eq!((patterns[0].matches, patterns[0].children), (3, 2));
+}
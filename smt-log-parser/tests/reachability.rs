@@ -0,0 +1,66 @@
+use petgraph::{algo::has_path_connecting, graph::NodeIndex, Graph};
+use smt_log_parser::{
+    items::InstIdx,
+    parsers::z3::{
+        inst_graph::InstGraph,
+        reachability::{IntervalLabels, ReachabilityIndex, TransitiveClosure},
+    },
+    LogParser, Z3Parser,
+};
+
+/// A DAG with edges only from earlier to later nodes, mostly between nearby
+/// nodes like chains of instantiations.
+fn random_dag(nodes: usize, seed: u64) -> Graph<(), ()> {
+    let mut state = seed;
+    let mut next = move |bound: usize| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as usize % bound
+    };
+    let mut graph = Graph::new();
+    for idx in 0..nodes {
+        let node = graph.add_node(());
+        for _ in 0..next(4) {
+            if idx == 0 {
+                break;
+            }
+            let distance = if next(4) == 0 { next(idx) } else { next(idx.min(5)) };
+            graph.add_edge(NodeIndex::new(idx - 1 - distance), node, ());
+        }
+    }
+    graph
+}
+
+fn index<R: ReachabilityIndex>(graph: &Graph<(), ()>) -> R {
+    let mut index = R::default();
+    for node in graph.node_indices() {
+        index.add_node(graph, node);
+    }
+    index
+}
+
+#[test]
+fn indexes_agree_with_search() {
+    for seed in 0..5 {
+        let graph = random_dag(150, seed);
+        let closure: TransitiveClosure = index(&graph);
+        let labels: IntervalLabels = index(&graph);
+        for from in graph.node_indices() {
+            for to in graph.node_indices() {
+                let expected = has_path_connecting(&graph, from, to, None);
+                assert_eq!(closure.reaches(&graph, from, to), expected, "{from:?} -> {to:?}");
+                assert_eq!(labels.reaches(&graph, from, to), expected, "{from:?} -> {to:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn graph_reachability() {
+    let log = std::fs::read_to_string("tests/fixtures/matching_loop.log").unwrap();
+    let parser = Z3Parser::from_str(&log).process_all();
+    let graph = InstGraph::from(&parser);
+    let inst = InstIdx::from;
+    assert!(graph.reaches(inst(0), inst(4)));
+    assert!(graph.reaches(inst(2), inst(2)));
+    assert!(!graph.reaches(inst(4), inst(0)));
+}
@@ -0,0 +1,159 @@
+use std::fmt::Write;
+
+use fxhash::FxHashSet;
+use smt_log_parser::{
+    items::InstIdx,
+    parsers::z3::inst_graph::{EdgeType, InstGraph},
+    LogParser, Z3Parser,
+};
+
+/// A log where instantiation `i` is triggered by the terms created by the
+/// instantiations `parents[i]`, through one of three quantifiers with one
+/// pattern term per parent. Instantiations without parents are triggered by
+/// a term created outside of any instantiation.
+fn log(parents: &[Vec<usize>]) -> String {
+    let mut log = "[tool-version] Z3 4.12.1\n[mk-app] #1 a\n[mk-app] #2 g #1\n".to_string();
+    log += "[attach-enode] #1 0\n[attach-enode] #2 0\n";
+    let mut next = 3;
+    let mut fresh = || {
+        next += 1;
+        next - 1
+    };
+    let mut quants = Vec::new();
+    for arity in 1..=3 {
+        let mut terms = Vec::new();
+        for var in 0..arity {
+            let (v, t) = (fresh(), fresh());
+            writeln!(log, "[mk-var] #{v} {var}\n[mk-app] #{t} g #{v}").unwrap();
+            terms.push(format!("#{t}"));
+        }
+        let (pattern, body, quant) = (fresh(), fresh(), fresh());
+        writeln!(log, "[mk-app] #{pattern} pattern {}", terms.join(" ")).unwrap();
+        writeln!(log, "[mk-app] #{body} h {}", terms.join(" ")).unwrap();
+        writeln!(log, "[mk-quant] #{quant} q{arity} {arity} #{pattern} #{body}").unwrap();
+        quants.push((quant, pattern));
+    }
+    // The argument and `g` application created by each instantiation.
+    let mut created = Vec::new();
+    for (idx, parents) in parents.iter().enumerate() {
+        let triggers: Vec<_> = parents.iter().map(|&parent| created[parent]).collect();
+        let triggers = if triggers.is_empty() { vec![(1, 2)] } else { triggers };
+        let (quant, pattern) = quants[triggers.len() - 1];
+        let bound: Vec<_> = triggers.iter().map(|(arg, _)| format!("#{arg}")).collect();
+        let blamed: Vec<_> = triggers.iter().map(|(_, app)| format!("#{app}")).collect();
+        let fingerprint = idx + 1;
+        writeln!(
+            log,
+            "[new-match] {fingerprint:#x} #{quant} #{pattern} {} ; {}",
+            bound.join(" "),
+            blamed.join(" ")
+        )
+        .unwrap();
+        let (arg, app) = (fresh(), fresh());
+        writeln!(log, "[instance] {fingerprint:#x} ; 1").unwrap();
+        writeln!(log, "[mk-app] #{arg} c{idx}\n[mk-app] #{app} g #{arg}").unwrap();
+        writeln!(log, "[attach-enode] #{arg} 1\n[attach-enode] #{app} 1").unwrap();
+        log += "[end-of-instance]\n";
+        created.push((arg, app));
+    }
+    log
+}
+
+/// Random parents, mostly among the preceding few instantiations.
+fn random_parents(insts: usize, next: &mut impl FnMut(usize) -> usize) -> Vec<Vec<usize>> {
+    (0..insts)
+        .map(|idx| {
+            let mut parents: Vec<_> = (0..next(4).min(idx))
+                .map(|_| {
+                    let distance = if next(4) == 0 { next(idx) } else { next(idx.min(5)) };
+                    idx - 1 - distance
+                })
+                .collect();
+            parents.sort_unstable();
+            parents.dedup();
+            parents
+        })
+        .collect()
+}
+
+type Edges = FxHashSet<(usize, usize)>;
+
+/// The edges of the visible graph as pairs of instantiations, split into
+/// direct and indirect ones.
+fn edges(graph: &InstGraph) -> (Edges, Edges) {
+    let visible = &graph.visible_graph;
+    let (mut direct, mut indirect) = (FxHashSet::default(), FxHashSet::default());
+    for edge in visible.raw_edges() {
+        let (from, to) = (visible[edge.source()].inst_idx, visible[edge.target()].inst_idx);
+        let edges = match edge.weight {
+            EdgeType::Direct { .. } => &mut direct,
+            EdgeType::Indirect => &mut indirect,
+        };
+        assert!(edges.insert((from.into(), to.into())), "duplicate edge");
+    }
+    (direct, indirect)
+}
+
+#[test]
+fn reconnect_random_graphs() {
+    let mut state = 0x853C_49E6_748F_EA9B_u64;
+    let mut next = move |bound: usize| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as usize % bound.max(1)
+    };
+    for _ in 0..10 {
+        let insts = 80;
+        let parents = random_parents(insts, &mut next);
+        let parser = Z3Parser::from_str(&log(&parents)).process_all();
+        assert!(parser.diagnostics().is_empty());
+        assert_eq!(parser.inst_count(), insts);
+        let mut graph = InstGraph::from(&parser);
+        let hidden_every = 1 + next(4);
+        let visible: Vec<_> = (0..insts).filter(|_| next(hidden_every + 1) != 0).collect();
+        let visible_set: FxHashSet<_> = visible.iter().copied().collect();
+        graph.retain_nodes(|node| visible_set.contains(&usize::from(node.inst_idx)));
+        graph.retain_visible_nodes_and_reconnect();
+
+        let reaches = |from: usize, to: usize| graph.reaches(InstIdx::from(from), InstIdx::from(to));
+        let expected_direct: FxHashSet<_> = visible
+            .iter()
+            .flat_map(|&to| parents[to].iter().map(move |&from| (from, to)))
+            .filter(|(from, _)| visible_set.contains(from))
+            .collect();
+        // The transitive reduction of the visible graph, without the edges
+        // which are already direct.
+        let mut expected_indirect = FxHashSet::default();
+        for &from in &visible {
+            for &to in &visible {
+                let implied = visible
+                    .iter()
+                    .any(|&via| via != from && via != to && reaches(from, via) && reaches(via, to));
+                if from != to
+                    && reaches(from, to)
+                    && !implied
+                    && !expected_direct.contains(&(from, to))
+                {
+                    expected_indirect.insert((from, to));
+                }
+            }
+        }
+        let (direct, indirect) = edges(&graph);
+        assert_eq!(direct, expected_direct);
+        assert_eq!(indirect, expected_indirect);
+    }
+}
+
+/// Hiding the middle of a long chain leaves a single indirect edge.
+#[test]
+fn reconnect_long_chain() {
+    let insts = 5000;
+    let parents: Vec<_> = (0..insts).map(|idx| (0..idx).rev().take(1).collect()).collect();
+    let parser = Z3Parser::from_str(&log(&parents)).process_all();
+    let mut graph = InstGraph::from(&parser);
+    let last = InstIdx::from(insts - 1);
+    graph.retain_nodes(|node| node.inst_idx == InstIdx::from(0) || node.inst_idx == last);
+    graph.retain_visible_nodes_and_reconnect();
+    let (direct, indirect) = edges(&graph);
+    assert!(direct.is_empty());
+    assert_eq!(indirect, FxHashSet::from_iter([(0, insts - 1)]));
+}
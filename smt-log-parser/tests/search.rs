@@ -0,0 +1,95 @@
+mod common;
+
+use smt_log_parser::{
+    items::QuantIdx,
+    parsers::z3::{
+        inst_graph::InstGraph,
+        search::{JustificationKind, Literal, SearchEventKind},
+    },
+    LogParser, Z3Parser,
+};
+
+const LOG: &str = "\
+[tool-version] Z3 4.12.1
+[mk-app] #1 p
+[mk-app] #2 q
+[mk-app] #3 or #1 #2
+[assign] #3 axiom
+[push] 0
+[decide-and-or] #3 #1
+[assign] (not #1) decision axiom
+[assign] #2 clause #2 #1
+[push] 1
+[decide] (not #2)
+[assign] (not #2) decision axiom
+[conflict] #2 (not #2)
+[resolve-lit] 1 #2
+[resolve-process] (not #2)
+[pop] 2 2
+[assign] #1 justification -1: #1
+";
+
+#[test]
+fn search_timeline() {
+    let parser = Z3Parser::from_str(LOG).process_all();
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+    let search = parser.search();
+    assert_eq!(search.events().len(), 10);
+    assert_eq!(search.decisions().count(), 2);
+    assert_eq!(search.conflicts().count(), 1);
+    assert_eq!(search.total_backjump(), 2);
+    assert_eq!(search.max_level(), 2);
+
+    let conflict = search.conflicts().next().unwrap();
+    assert_eq!(conflict.level, 2);
+    let SearchEventKind::Conflict { lits, .. } = &conflict.kind else {
+        unreachable!()
+    };
+    assert_eq!(lits.len(), 2);
+    assert!(lits[0].value && !lits[1].value);
+
+    let last = search.events().last().unwrap();
+    let SearchEventKind::Assign {
+        lit: Literal { value: true, .. },
+        decision: false,
+        justification,
+    } = &last.kind
+    else {
+        panic!("{last:?}")
+    };
+    assert_eq!(justification.kind, JustificationKind::Theory);
+    assert_eq!(last.level, 0);
+
+    // Term `#2` (q) appears in the conflict twice and is resolved twice.
+    let (term, count) = search.conflict_terms()[0];
+    assert_eq!(count, 4);
+    assert_eq!(lits[0].term, Some(term));
+}
+
+#[test]
+fn conflicts_are_attributed_to_instantiations() {
+    let conflict = "[conflict] #11 (not #13)\n[resolve-lit] 0 #13\n";
+    let log = common::matching_loop_with(&[("[pop] 1 1\n", conflict)]);
+    let parser = Z3Parser::from_str(&log).process_all();
+    assert!(
+        parser.diagnostics().is_empty(),
+        "{:?}",
+        parser.diagnostics()
+    );
+
+    let inst_graph = InstGraph::from(&parser);
+    let node_info = inst_graph.get_node_info_map();
+    let useful: Vec<_> = (0..5)
+        .map(|node| {
+            node_info
+                .get_instantiation_info(node, &parser, false)
+                .useful
+        })
+        .collect();
+    assert_eq!(useful, [1, 2, 0, 0, 0]);
+    assert_eq!(parser[QuantIdx::from(0)].useful, 2);
+}